@@ -1,3 +1,4 @@
+pub(super) mod block_cache;
 pub(super) mod block_witness_data;
 pub(super) mod fetch_compact_blocks;
 pub(super) mod fetch_full_tx;
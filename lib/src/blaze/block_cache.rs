@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+use prost::Message;
+use zcash_encoding::Vector;
+
+use crate::compact_formats::CompactBlock;
+
+// Blocks are stored in files of this many consecutive heights
+const CHUNK_SIZE: u64 = 1_000;
+
+// Blocks closer than this to the tip can still be reorged, so they aren't cached
+pub const CACHE_MIN_DEPTH: u64 = 100;
+
+/// Compact blocks saved in the data directory, so rescans don't have to download the chain again. The blocks are
+/// kept in files of CHUNK_SIZE heights, along with the spam filter threshold they were downloaded with. Once the
+/// cache is over its size limit, the least recently written files are removed.
+pub struct CompactBlockCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl CompactBlockCache {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    pub fn new(dir: &Path, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_size,
+        })
+    }
+
+    /// The cached blocks from `low` to `high`, if they were downloaded with this spam filter threshold
+    pub fn read_range(&self, low: u64, high: u64, spam_filter_threshold: i64) -> BTreeMap<u64, CompactBlock> {
+        let mut blocks = BTreeMap::new();
+        for chunk in (low / CHUNK_SIZE)..=(high / CHUNK_SIZE) {
+            match self.read_chunk(chunk) {
+                Ok(Some((threshold, chunk_blocks))) if threshold == spam_filter_threshold => {
+                    blocks.extend(chunk_blocks.into_iter().filter(|(h, _)| *h >= low && *h <= high));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Removing unreadable block cache file {}: {}", chunk, e);
+                    let _ = fs::remove_file(self.chunk_path(chunk));
+                }
+            }
+        }
+
+        blocks
+    }
+
+    /// Add blocks to the cache, replacing the cached blocks at the same heights
+    pub fn store(&self, blocks: &[CompactBlock], spam_filter_threshold: i64) -> io::Result<()> {
+        let mut chunks = BTreeMap::<u64, Vec<&CompactBlock>>::new();
+        for block in blocks {
+            chunks.entry(block.height / CHUNK_SIZE).or_default().push(block);
+        }
+
+        for (chunk, new_blocks) in chunks {
+            // Blocks downloaded with another spam filter threshold are thrown away
+            let mut cached = match self.read_chunk(chunk) {
+                Ok(Some((threshold, cached))) if threshold == spam_filter_threshold => cached,
+                _ => BTreeMap::new(),
+            };
+            cached.extend(new_blocks.into_iter().map(|b| (b.height, b.clone())));
+
+            self.write_chunk(chunk, spam_filter_threshold, &cached)?;
+        }
+
+        self.enforce_size_limit()
+    }
+
+    /// Remove the cached blocks at `height` and above, because they were reorged away
+    pub fn remove_from(&self, height: u64) -> io::Result<()> {
+        for (chunk, path) in self.chunk_files()? {
+            if chunk > height / CHUNK_SIZE {
+                fs::remove_file(path)?;
+            }
+        }
+
+        let chunk = height / CHUNK_SIZE;
+        if let Ok(Some((threshold, mut blocks))) = self.read_chunk(chunk) {
+            blocks.split_off(&height);
+            self.write_chunk(chunk, threshold, &blocks)?;
+        }
+
+        Ok(())
+    }
+
+    /// Total size of the cache files, in bytes
+    pub fn size(&self) -> io::Result<u64> {
+        let mut size = 0;
+        for (_, path) in self.chunk_files()? {
+            size += fs::metadata(path)?.len();
+        }
+
+        Ok(size)
+    }
+
+    fn chunk_path(&self, chunk: u64) -> PathBuf {
+        self.dir.join(format!("{}.blocks", chunk))
+    }
+
+    fn chunk_files(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |e| e == "blocks") {
+                if let Some(chunk) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()) {
+                    files.push((chunk, path));
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    // The spam filter threshold and blocks of a chunk, or None if nothing is cached for it
+    fn read_chunk(&self, chunk: u64) -> io::Result<Option<(i64, BTreeMap<u64, CompactBlock>)>> {
+        let path = self.chunk_path(chunk);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let _version = reader.read_u64::<LittleEndian>()?;
+        let threshold = reader.read_i64::<LittleEndian>()?;
+        let blocks = Vector::read(&mut reader, |r| {
+            let buf = Vector::read(r, |r| r.read_u8())?;
+            CompactBlock::decode(&buf[..]).map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))
+        })?;
+
+        Ok(Some((threshold, blocks.into_iter().map(|b| (b.height, b)).collect())))
+    }
+
+    fn write_chunk(&self, chunk: u64, threshold: i64, blocks: &BTreeMap<u64, CompactBlock>) -> io::Result<()> {
+        let path = self.chunk_path(chunk);
+        if blocks.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        // Write a temporary file first, so an interrupted write never leaves a broken chunk behind
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+            writer.write_i64::<LittleEndian>(threshold)?;
+            Vector::write(&mut writer, &blocks.values().collect::<Vec<_>>(), |w, b| {
+                let mut buf = vec![];
+                b.encode(&mut buf)?;
+                Vector::write(w, &buf, |w, b| w.write_u8(*b))
+            })?;
+            writer.flush()?;
+        }
+
+        fs::rename(tmp_path, path)
+    }
+
+    fn enforce_size_limit(&self) -> io::Result<()> {
+        let mut files = vec![];
+        for (_, path) in self.chunk_files()? {
+            let metadata = fs::metadata(&path)?;
+            files.push((metadata.modified()?, metadata.len(), path));
+        }
+
+        let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in files {
+            if size <= self.max_size {
+                break;
+            }
+
+            fs::remove_file(path)?;
+            size -= len;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempdir::TempDir;
+
+    use super::CompactBlockCache;
+    use crate::blaze::test_utils::FakeCompactBlockList;
+
+    #[test]
+    fn block_cache() {
+        let dir = TempDir::new("blockcache").unwrap();
+        let cache = CompactBlockCache::new(dir.path(), 10_000_000).unwrap();
+
+        // Blocks 1-1500 span two chunk files. The blocks are in reverse order, like the server sends them.
+        let mut fcbl = FakeCompactBlockList::new(1500);
+        let blocks = fcbl.into_compact_blocks();
+        cache.store(&blocks, -1).unwrap();
+
+        let cached = cache.read_range(990, 1010, -1);
+        assert_eq!(cached.len(), 21);
+        assert_eq!(cached[&1000].hash, blocks.iter().find(|b| b.height == 1000).unwrap().hash);
+
+        // Blocks downloaded with another spam filter threshold don't count
+        assert!(cache.read_range(990, 1010, 50).is_empty());
+
+        // Reorged blocks are removed, along with everything above them
+        cache.remove_from(995).unwrap();
+        let heights = cache.read_range(990, 1010, -1).keys().copied().collect::<Vec<_>>();
+        assert_eq!(heights, (990..995).collect::<Vec<_>>());
+        assert!(cache.read_range(1000, 1500, -1).is_empty());
+
+        // The oldest files are removed once the cache is over its size limit
+        let size = cache.size().unwrap();
+        let cache = CompactBlockCache::new(dir.path(), size).unwrap();
+        cache.store(&blocks[..10], -1).unwrap();
+        assert!(cache.size().unwrap() <= size);
+        assert!(cache.read_range(1, 994, -1).is_empty());
+        assert_eq!(cache.read_range(1000, 1500, -1).len(), 10);
+    }
+}
@@ -59,6 +59,10 @@ pub struct BlockAndWitnessData {
     // Orchard witnesses
     orchard_witnesses: Arc<RwLock<Option<BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>>>>,
 
+    // The first of the newest blocks while a recent-first sync scans them. The wallet's older notes have witnesses
+    // from below them, which aren't updated in this batch.
+    recent_start: Option<u64>,
+
     // Link to the syncstatus where we can update progress
     sync_status: Arc<RwLock<SyncStatus>>,
 
@@ -75,6 +79,7 @@ impl BlockAndWitnessData {
             verified_tree: None,
             orchard_note_positions: Arc::new(RwLock::new(HashMap::new())),
             orchard_witnesses: Arc::new(RwLock::new(None)),
+            recent_start: None,
             sync_status,
             sapling_activation_height: config.sapling_activation_height,
         }
@@ -101,6 +106,7 @@ impl BlockAndWitnessData {
         }
         self.verification_list.write().await.clear();
         self.verified_tree = verified_tree;
+        self.recent_start = None;
 
         self.blocks.write().await.clear();
 
@@ -110,6 +116,11 @@ impl BlockAndWitnessData {
         self.existing_blocks.write().await.extend(existing_blocks);
     }
 
+    // Mark this batch as the newest blocks of a recent-first sync, starting at `first`
+    pub fn set_recent_start(&mut self, first: u64) {
+        self.recent_start = Some(first);
+    }
+
     // Finish up the sync. This method will delete all the elements in the blocks, and return
     // the top `num` blocks
     pub async fn finish_get_blocks(&self, num: usize) -> Vec<BlockData> {
@@ -643,6 +654,12 @@ impl BlockAndWitnessData {
         if height > self.wait_for_first_block().await {
             return witnesses;
         }
+
+        // In the newest blocks of a recent-first sync, the witnesses of the older notes stop below this batch. They
+        // are brought up to date once the older blocks are scanned.
+        if self.recent_start.map_or(false, |first| height < first) {
+            return witnesses;
+        }
         self.wait_for_block(height).await;
 
         let mut fsb = FixedSizeBuffer::new(MAX_REORG);
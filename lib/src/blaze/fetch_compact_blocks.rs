@@ -3,17 +3,28 @@ use std::{cmp::max, sync::Arc};
 use crate::{
     compact_formats::CompactBlock, grpc_connector::GrpcConnector, lightclient::lightclient_config::LightClientConfig,
 };
-use log::info;
+use log::{info, warn};
 use tokio::sync::mpsc::{Sender, UnboundedReceiver};
 use zcash_primitives::consensus;
 
+use super::block_cache::CompactBlockCache;
+
 pub struct FetchCompactBlocks<P> {
     config: LightClientConfig<P>,
+
+    // Blocks are read from the cache first, if there is one. Downloaded blocks up to `cacheable_height` are added
+    // to it, the ones above it are too close to the tip to be safe from reorgs.
+    cache: Option<CompactBlockCache>,
+    cacheable_height: u64,
 }
 
 impl<P: consensus::Parameters> FetchCompactBlocks<P> {
-    pub fn new(config: &LightClientConfig<P>) -> Self {
-        Self { config: config.clone() }
+    pub fn new(config: &LightClientConfig<P>, cache: Option<CompactBlockCache>, cacheable_height: u64) -> Self {
+        Self {
+            config: config.clone(),
+            cache,
+            cacheable_height,
+        }
     }
 
     async fn fetch_blocks_range(
@@ -34,16 +45,86 @@ impl<P: consensus::Parameters> FetchCompactBlocks<P> {
                 return Err(format!("Wrong block order"));
             }
 
-            info!("Fetching blocks {}-{}", start, end);
+            let blocks = self.get_blocks(&grpc_client, start, end, spam_filter_threshold).await?;
 
-            grpc_client
-                .get_block_range(start, end, spam_filter_threshold, receivers)
-                .await?;
+            // Send all the blocks to the recievers
+            for block in blocks {
+                receivers[0].send(block.clone()).await.map_err(|e| format!("{}", e))?;
+                receivers[1].send(block).await.map_err(|e| format!("{}", e))?;
+            }
         }
 
         Ok(())
     }
 
+    // The blocks from `start` down to `end`, read from the cache where possible and downloaded otherwise
+    async fn get_blocks(
+        &self,
+        grpc_client: &GrpcConnector,
+        start: u64,
+        end: u64,
+        spam_filter_threshold: i64,
+    ) -> Result<Vec<CompactBlock>, String> {
+        let cache = match self.cache.as_ref() {
+            Some(cache) => cache,
+            None => {
+                info!("Fetching blocks {}-{}", start, end);
+                return grpc_client.get_block_range(start, end, spam_filter_threshold).await;
+            }
+        };
+
+        let mut blocks = cache.read_range(end, start, spam_filter_threshold);
+        let from_cache = blocks.len();
+
+        // Download the ranges that aren't cached
+        let mut missing: Vec<(u64, u64)> = vec![];
+        for height in (end..=start).rev().filter(|h| !blocks.contains_key(h)) {
+            match missing.last_mut() {
+                Some((_, low)) if *low == height + 1 => *low = height,
+                _ => missing.push((height, height)),
+            }
+        }
+
+        let mut downloaded = vec![];
+        for (high, low) in missing {
+            info!("Fetching blocks {}-{}", high, low);
+            downloaded.extend(grpc_client.get_block_range(high, low, spam_filter_threshold).await?);
+        }
+        if from_cache > 0 {
+            info!("Read {} blocks in {}-{} from the block cache", from_cache, start, end);
+        }
+
+        blocks.extend(downloaded.iter().map(|b| (b.height, b.clone())));
+
+        // Cached blocks from before a reorg don't link up with the downloaded ones, so throw them away
+        let linked = blocks
+            .values()
+            .zip(blocks.values().skip(1))
+            .all(|(prev, block)| block.height != prev.height + 1 || block.prev_hash == prev.hash);
+        if !linked {
+            warn!("Cached blocks in {}-{} don't match the chain, downloading them again", start, end);
+            if let Err(e) = cache.remove_from(end) {
+                warn!("Couldn't clear the block cache: {}", e);
+            }
+
+            downloaded = grpc_client.get_block_range(start, end, spam_filter_threshold).await?;
+            blocks = downloaded.iter().map(|b| (b.height, b.clone())).collect();
+        }
+
+        let cacheable = downloaded
+            .into_iter()
+            .filter(|b| b.height <= self.cacheable_height)
+            .collect::<Vec<_>>();
+        if !cacheable.is_empty() {
+            if let Err(e) = cache.store(&cacheable, spam_filter_threshold) {
+                warn!("Couldn't add blocks to the block cache: {}", e);
+            }
+        }
+
+        // The server sends the blocks highest first
+        Ok(blocks.into_iter().rev().map(|(_, b)| b).collect())
+    }
+
     // Load all the blocks from LightwalletD
     pub async fn start(
         &self,
@@ -63,6 +144,13 @@ impl<P: consensus::Parameters> FetchCompactBlocks<P> {
 
         // After fetching all the normal blocks, we actually wait to see if any re-org'd blocks are recieved
         while let Some(Some(reorg_block)) = reorg_rx.recv().await {
+            // A cached block at this height was reorged away, so it has to be downloaded again
+            if let Some(cache) = self.cache.as_ref() {
+                if let Err(e) = cache.remove_from(reorg_block) {
+                    warn!("Couldn't remove reorged blocks from the block cache: {}", e);
+                }
+            }
+
             // Fetch the additional block.
             self.fetch_blocks_range(&receivers, reorg_block, reorg_block, spam_filter_threshold)
                 .await?;
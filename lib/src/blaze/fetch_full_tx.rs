@@ -174,9 +174,10 @@ impl<P: consensus::Parameters + Send + Sync + 'static> FetchFullTxns<P> {
         wallet_txns: Arc<RwLock<WalletTxns>>,
         price: Option<f64>,
     ) {
-        // Collect our t-addresses for easy checking
+        // Collect our t-addresses and multisig addresses for easy checking
         let taddrs = keys.read().await.get_all_taddrs();
-        let taddrs_set: HashSet<_> = taddrs.iter().map(|t| t.clone()).collect();
+        let multisigs = keys.read().await.get_all_multisig_addresses();
+        let taddrs_set: HashSet<_> = taddrs.iter().chain(multisigs.iter()).map(|t| t.clone()).collect();
 
         // Step 1: Scan all transparent outputs to see if we recieved any money
         if let Some(t_bundle) = tx.transparent_bundle() {
@@ -200,6 +201,21 @@ impl<P: consensus::Parameters + Send + Sync + 'static> FetchFullTxns<P> {
                             keys.write().await.ensure_hd_taddresses(&output_taddr);
                         }
                     }
+                    Some(TransparentAddress::Script(hash)) => {
+                        let output_taddr = hash.to_base58check(&config.base58_script_address(), &[]);
+                        if taddrs_set.contains(&output_taddr) {
+                            // This is one of our multisig addresses
+                            wallet_txns.write().await.add_new_taddr_output(
+                                tx.txid(),
+                                output_taddr,
+                                height.into(),
+                                unconfirmed,
+                                block_time as u64,
+                                &vout,
+                                n as u32,
+                            );
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -305,7 +321,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> FetchFullTxns<P> {
         let mut outgoing_metadatas = vec![];
 
         if let Some(s_bundle) = tx.sapling_bundle() {
-            for output in s_bundle.shielded_outputs.iter() {
+            for (output_num, output) in s_bundle.shielded_outputs.iter().enumerate() {
                 // Search all of our keys
                 for (i, ivk) in s_ivks.iter().enumerate() {
                     let (note, to, memo_bytes) =
@@ -323,6 +339,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> FetchFullTxns<P> {
                             note.clone(),
                             to,
                             &extfvks.get(i).unwrap(),
+                            output_num as u32,
                         );
                     }
 
@@ -39,7 +39,13 @@ impl<P: consensus::Parameters + Send + Sync + 'static> FetchTaddrTxns<P> {
         let keys = self.keys.clone();
 
         tokio::spawn(async move {
-            let taddrs = keys.read().await.get_all_taddrs();
+            // Multisig addresses are fetched like the wallet's own t addresses
+            let taddrs = {
+                let keys = keys.read().await;
+                let mut taddrs = keys.get_all_taddrs();
+                taddrs.extend(keys.get_all_multisig_addresses());
+                taddrs
+            };
 
             // Fetch all transactions for all t-addresses in parallel, and process them in height order
             let req = (taddrs, start_height, end_height);
@@ -1,6 +1,22 @@
 use core::fmt;
 use std::cmp;
 
+/// What a sync should do when it gets to the next batch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncControl {
+    Run,
+    // Stop, and don't sync again until resumed
+    Pause,
+    // Stop only the sync that is running now
+    Cancel,
+}
+
+impl Default for SyncControl {
+    fn default() -> Self {
+        SyncControl::Run
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct SyncStatus {
     pub in_progress: bool,
@@ -18,6 +34,9 @@ pub struct SyncStatus {
 
     pub batch_num: usize,
     pub batch_total: usize,
+
+    // The block ranges this sync has finished, in the order they were done
+    pub done_ranges: Vec<(u64, u64)>,
 }
 
 impl SyncStatus {
@@ -31,6 +50,12 @@ impl SyncStatus {
         self.txn_scan_done = 0;
         self.batch_num = 0;
         self.batch_total = batch_total;
+        self.done_ranges = vec![];
+    }
+
+    /// The blocks from `first` to `last` have been scanned
+    pub fn add_done_range(&mut self, first: u64, last: u64) {
+        self.done_ranges.push((first, last));
     }
 
     /// Setup a new sync status in prep for an upcoming sync
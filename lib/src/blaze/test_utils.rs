@@ -207,8 +207,15 @@ impl FakeTransaction {
 
         let taddr_bytes = hash160.finalize();
 
-        // let mut t_bundle = self.td.transparent_bundle().unwrap().clone();
+        self.add_t_script_output(
+            TransparentAddress::PublicKey(taddr_bytes.try_into().unwrap()).script(),
+            taddr,
+            value,
+        );
+    }
 
+    // Add a t output paying the given script, such as a P2SH multisig address
+    pub fn add_t_script_output(&mut self, script_pubkey: Script, taddr: String, value: u64) {
         let mut t_bundle = if self.td.transparent_bundle().is_some() {
             self.td.transparent_bundle().unwrap().clone()
         } else {
@@ -221,7 +228,7 @@ impl FakeTransaction {
 
         t_bundle.vout.push(TxOut {
             value: Amount::from_u64(value).unwrap(),
-            script_pubkey: TransparentAddress::PublicKey(taddr_bytes.try_into().unwrap()).script(),
+            script_pubkey,
         });
 
         self.td = TransactionData::from_parts(
@@ -408,11 +415,14 @@ impl FakeCompactBlockList {
                     .vout
                     .iter()
                     .filter_map(|vout| {
-                        if let Some(TransparentAddress::PublicKey(taddr_hash)) = vout.script_pubkey.address() {
-                            let taddr = taddr_hash.to_base58check(&config.base58_pubkey_address(), &[]);
-                            Some(taddr)
-                        } else {
-                            None
+                        match vout.script_pubkey.address() {
+                            Some(TransparentAddress::PublicKey(taddr_hash)) => {
+                                Some(taddr_hash.to_base58check(&config.base58_pubkey_address(), &[]))
+                            }
+                            Some(TransparentAddress::Script(script_hash)) => {
+                                Some(script_hash.to_base58check(&config.base58_script_address(), &[]))
+                            }
+                            None => None,
                         }
                     })
                     .collect::<Vec<_>>()
@@ -468,6 +478,19 @@ impl FakeCompactBlockList {
         (tx, height, note)
     }
 
+    // Add blocks with a tx each, that has `outputs` real sapling outputs paying the given address
+    pub fn add_blocks_paying(&mut self, extfvk: &ExtendedFullViewingKey, len: u64, outputs: usize) -> &mut Self {
+        for _ in 0..len {
+            let mut ftx = FakeTransaction::new();
+            for _ in 0..outputs {
+                ftx.add_tx_paying(extfvk, 1);
+            }
+            self.add_ftx(ftx);
+        }
+
+        self
+    }
+
     pub fn add_empty_block(&mut self) -> &'_ mut FakeCompactBlock {
         let newblk = FakeCompactBlock::new(self.next_height, self.prev_hash);
         self.next_height += 1;
@@ -584,3 +607,109 @@ impl TxProver for FakeTxProver {
         Signature::read(&fake_bytes[..]).map_err(|_e| ())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cmp,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use tokio::runtime::Runtime;
+    use zcash_primitives::{
+        sapling::SaplingIvk,
+        zip32::{ExtendedFullViewingKey, ExtendedSpendingKey},
+    };
+
+    use super::FakeCompactBlockList;
+    use crate::blaze::trial_decryptions::{decryption_threads, TrialDecryptions};
+    use crate::compact_formats::CompactBlock;
+    use crate::lightclient::lightclient_config::UnitTestNetwork;
+
+    // (height, tx_num, output_num, value, ivk_num) of a decrypted note
+    type FoundNote = (u64, usize, usize, u64, usize);
+
+    // Four keys, and blocks paying the last of them
+    fn blocks_paying_last_key(blocks: u64, outputs: usize) -> (Arc<Vec<SaplingIvk>>, Vec<CompactBlock>) {
+        let extfvks = (0..4u8)
+            .map(|i| ExtendedFullViewingKey::from(&ExtendedSpendingKey::master(&[i; 32])))
+            .collect::<Vec<_>>();
+        let s_ivks = Arc::new(extfvks.iter().map(|k| k.fvk.vk.ivk()).collect::<Vec<_>>());
+
+        let cbs = FakeCompactBlockList::new(0)
+            .add_blocks_paying(&extfvks[3], blocks, outputs)
+            .into_compact_blocks();
+
+        (s_ivks, cbs)
+    }
+
+    // Trial-decrypt the blocks on a pool of `threads`. Returns the notes found, sorted, and how long it took.
+    fn decrypt(
+        rt: &Runtime,
+        threads: usize,
+        s_ivks: Arc<Vec<SaplingIvk>>,
+        cbs: Vec<CompactBlock>,
+    ) -> (Vec<FoundNote>, Duration) {
+        rt.block_on(async move {
+            let start = Instant::now();
+            let (block_tx, mut decrypted_rx) =
+                TrialDecryptions::start_pool(threads, UnitTestNetwork, s_ivks, Arc::new(vec![]));
+            tokio::spawn(async move {
+                for cb in cbs {
+                    block_tx.send(cb).await.unwrap();
+                }
+            });
+
+            let mut notes = vec![];
+            while let Some(block) = decrypted_rx.recv().await {
+                for tx in block.txs {
+                    for (output_num, note, _, ivk_num) in tx.sapling_notes {
+                        notes.push((block.height, tx.tx_num, output_num, note.value, ivk_num));
+                    }
+                }
+            }
+
+            let elapsed = start.elapsed();
+            notes.sort();
+            (notes, elapsed)
+        })
+    }
+
+    #[test]
+    fn trial_decryption_pool() {
+        let (blocks, outputs) = (20, 3);
+        let (s_ivks, cbs) = blocks_paying_last_key(blocks, outputs);
+        let rt = Runtime::new().unwrap();
+
+        let (single, _) = decrypt(&rt, 1, s_ivks.clone(), cbs.clone());
+        assert_eq!(single.len(), (blocks as usize) * outputs);
+        assert!(single.iter().all(|(_, _, _, _, ivk_num)| *ivk_num == 3));
+
+        // A pool finds the same notes
+        let (pooled, _) = decrypt(&rt, cmp::max(decryption_threads(), 2), s_ivks, cbs);
+        assert_eq!(pooled, single);
+    }
+
+    // How many trial decryptions a second the pool does. Timings depend on the machine, so this only reports them:
+    // cargo test --release trial_decryption_throughput -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn trial_decryption_throughput() {
+        let (blocks, outputs) = (200, 5);
+        let (s_ivks, cbs) = blocks_paying_last_key(blocks, outputs);
+        let rt = Runtime::new().unwrap();
+
+        let threads = decryption_threads();
+        let (_, single) = decrypt(&rt, 1, s_ivks.clone(), cbs.clone());
+        let (_, multi) = decrypt(&rt, threads, s_ivks.clone(), cbs);
+
+        let trials = (blocks as usize * outputs * s_ivks.len()) as f64;
+        println!(
+            "Trial decryptions per second: {:.0} on 1 thread, {:.0} on {} threads",
+            trials / single.as_secs_f64(),
+            trials / multi.as_secs_f64(),
+            threads
+        );
+    }
+}
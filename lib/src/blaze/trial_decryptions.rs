@@ -8,11 +8,10 @@ use orchard::{keys::IncomingViewingKey, note_encryption::OrchardDomain};
 use std::convert::TryFrom;
 use zcash_note_encryption::batch::try_compact_note_decryption;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::{
-    runtime::Handle,
     sync::{
-        mpsc::{channel, Sender, UnboundedSender},
+        mpsc::{channel, Receiver, Sender, UnboundedSender},
         oneshot, RwLock,
     },
     task::JoinHandle,
@@ -20,12 +19,43 @@ use tokio::{
 
 use zcash_primitives::{
     consensus::{self, BlockHeight},
-    sapling::{self, note_encryption::SaplingDomain, SaplingIvk},
+    sapling::{self, note_encryption::SaplingDomain, PaymentAddress, SaplingIvk},
     transaction::{Transaction, TxId},
 };
 
 use super::syncdata::BlazeSyncData;
 
+// How many blocks can wait for each decryption thread, and how many decrypted blocks can wait to be processed
+const BLOCKS_PER_THREAD: usize = 4;
+const DECRYPTED_BLOCKS: usize = 64;
+
+/// The notes that trial decryption found in a compact block
+pub struct DecryptedBlock {
+    pub height: u64,
+    pub time: u32,
+    pub txs: Vec<DecryptedTx>,
+}
+
+pub struct DecryptedTx {
+    pub tx_num: usize,
+    pub hash: Vec<u8>,
+
+    // (output_num, note, ivk_num) of the notes for the wallet's keys
+    pub orchard_notes: Vec<(usize, orchard::Note, usize)>,
+    pub sapling_notes: Vec<(usize, sapling::Note, PaymentAddress, usize)>,
+}
+
+impl DecryptedTx {
+    pub fn is_wallet_tx(&self) -> bool {
+        !self.orchard_notes.is_empty() || !self.sapling_notes.is_empty()
+    }
+}
+
+/// Number of threads to trial-decrypt with, one for each core
+pub fn decryption_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
 pub struct TrialDecryptions<P> {
     keys: Arc<RwLock<Keys<P>>>,
     wallet_txns: Arc<RwLock<WalletTxns>>,
@@ -44,69 +74,136 @@ impl<P: consensus::Parameters + Send + Sync + 'static> TrialDecryptions<P> {
     ) -> (JoinHandle<Result<(), String>>, Sender<CompactBlock>) {
         //info!("Starting trial decrptions processor");
 
-        // Create a new channel where we'll receive the blocks. only 64 in the queue
-        let (tx, mut rx) = channel::<CompactBlock>(64);
-
         let keys = self.keys.clone();
         let wallet_txns = self.wallet_txns.clone();
 
+        let s_ivks = Arc::new(
+            keys.read()
+                .await
+                .zkeys
+                .iter()
+                .map(|zk| zk.extfvk().fvk.vk.ivk())
+                .collect::<Vec<_>>(),
+        );
+        let o_ivks = Arc::new(keys.read().await.get_all_orchard_ivks());
+        let params = keys.read().await.config().get_params();
+
+        // The decryption itself is CPU-bound, so it runs on threads of its own. Only the notes it finds come back
+        // to the runtime, to get their witnesses and be added to the wallet.
+        let (tx, mut decrypted_rx) = Self::start_pool(decryption_threads(), params, s_ivks, o_ivks);
+
         let h = tokio::spawn(async move {
             let mut workers = FuturesUnordered::new();
-            let mut cbs = vec![];
-
-            let s_ivks = Arc::new(
-                keys.read()
-                    .await
-                    .zkeys
-                    .iter()
-                    .map(|zk| zk.extfvk().fvk.vk.ivk())
-                    .collect::<Vec<_>>(),
-            );
-
-            let o_ivks = Arc::new(keys.read().await.get_all_orchard_ivks());
-
-            while let Some(cb) = rx.recv().await {
-                //println!("trial_witness recieved {:?}", cb.height);
-                cbs.push(cb);
-
-                if cbs.len() >= 50 {
-                    let keys = keys.clone();
-                    let s_ivks = s_ivks.clone();
-                    let o_ivks = o_ivks.clone();
-                    let wallet_txns = wallet_txns.clone();
-                    let bsync_data = bsync_data.clone();
-                    let detected_txid_sender = detected_txid_sender.clone();
-
-                    workers.push(tokio::spawn(Self::trial_decrypt_batch(
-                        cbs.split_off(0),
-                        keys,
-                        bsync_data,
-                        s_ivks,
-                        o_ivks,
-                        wallet_txns,
-                        detected_txid_sender,
-                        fulltx_fetcher.clone(),
-                    )));
+            let download_memos = bsync_data.read().await.wallet_options.download_memos;
+
+            while let Some(block) = decrypted_rx.recv().await {
+                let height = BlockHeight::from_u32(block.height as u32);
+                let timestamp = block.time as u64;
+
+                for dtx in block.txs {
+                    let wallet_tx = dtx.is_wallet_tx();
+                    let tx_num = dtx.tx_num;
+                    let txid = WalletTx::new_txid(&dtx.hash);
+
+                    for (output_num, note, ivk_num) in dtx.orchard_notes {
+                        let keys = keys.read().await;
+                        let fvk = keys.okeys[ivk_num].fvk();
+                        let have_spending_key = keys.have_orchard_spending_key(fvk);
+
+                        // Tell the orchard witness tree to track this note.
+                        bsync_data
+                            .read()
+                            .await
+                            .block_data
+                            .track_orchard_note(block.height, tx_num, output_num as u32)
+                            .await;
+
+                        wallet_txns.write().await.add_new_orchard_note(
+                            txid,
+                            height,
+                            false,
+                            timestamp,
+                            note,
+                            (height.into(), tx_num, output_num as u32),
+                            fvk,
+                            have_spending_key,
+                        );
+
+                        detected_txid_sender
+                            .send((txid, None, height, Some(output_num as u32)))
+                            .await
+                            .unwrap();
+                    }
+
+                    for (output_num, note, to, ivk_num) in dtx.sapling_notes {
+                        let keys = keys.clone();
+                        let bsync_data = bsync_data.clone();
+                        let wallet_txns = wallet_txns.clone();
+                        let detected_txid_sender = detected_txid_sender.clone();
+
+                        workers.push(tokio::spawn(async move {
+                            let keys = keys.read().await;
+                            let extfvk = keys.zkeys[ivk_num].extfvk();
+                            // Witnesses are kept for the notes we can spend, and with offline signing, for the
+                            // notes of viewing keys too
+                            let have_spending_key = keys.have_sapling_spending_key(extfvk);
+                            let keep_witnesses =
+                                have_spending_key || bsync_data.read().await.wallet_options.offline_signing;
+                            let uri = bsync_data.read().await.uri().clone();
+
+                            // Get the witness for the note
+                            let witness = bsync_data
+                                .read()
+                                .await
+                                .block_data
+                                .get_note_witness(uri, height, tx_num, output_num)
+                                .await?;
+
+                            let nullifier = note.nf(&extfvk.fvk.vk.nk, witness.position() as u64);
+
+                            wallet_txns.write().await.add_new_sapling_note(
+                                txid.clone(),
+                                height,
+                                false,
+                                timestamp,
+                                note,
+                                to,
+                                &extfvk,
+                                have_spending_key,
+                                keep_witnesses,
+                                witness,
+                                output_num as u32,
+                            );
+
+                            info!("Trial decrypt Detected txid {}", &txid);
+
+                            detected_txid_sender
+                                .send((txid, Some(nullifier), height, Some(output_num as u32)))
+                                .await
+                                .unwrap();
+
+                            Ok::<_, String>(())
+                        }));
+                    }
+
+                    // Check option to see if we are fetching all txns.
+                    if !wallet_tx && download_memos == MemoDownloadOption::AllMemos {
+                        let (tx, rx) = oneshot::channel();
+                        fulltx_fetcher.send((txid, tx)).unwrap();
+
+                        workers.push(tokio::spawn(async move {
+                            // Discard the result, because this was not a wallet tx.
+                            rx.await.unwrap().map(|_r| ())
+                        }));
+                    }
                 }
-            }
 
-            workers.push(tokio::spawn(Self::trial_decrypt_batch(
-                cbs,
-                keys,
-                bsync_data,
-                s_ivks,
-                o_ivks,
-                wallet_txns,
-                detected_txid_sender,
-                fulltx_fetcher,
-            )));
+                // Update sync status
+                bsync_data.read().await.sync_status.write().await.trial_dec_done += 1;
+            }
 
             while let Some(r) = workers.next().await {
-                match r {
-                    Ok(Ok(_)) => (),
-                    Ok(Err(s)) => return Err(s),
-                    Err(e) => return Err(e.to_string()),
-                };
+                r.map_err(|e| e.to_string())??;
             }
 
             //info!("Finished final trial decryptions");
@@ -116,32 +213,62 @@ impl<P: consensus::Parameters + Send + Sync + 'static> TrialDecryptions<P> {
         return (h, tx);
     }
 
-    async fn trial_decrypt_batch(
-        cbs: Vec<CompactBlock>,
-        keys: Arc<RwLock<Keys<P>>>,
-        bsync_data: Arc<RwLock<BlazeSyncData>>,
+    /// Start `threads` threads that trial-decrypt the compact blocks sent to them, one block at a time. Both
+    /// channels are bounded, so the blocks are only fetched as fast as they are decrypted and processed. The threads
+    /// stop once the block sender is dropped and they've sent all their decrypted blocks.
+    pub fn start_pool(
+        threads: usize,
+        params: P,
         s_ivks: Arc<Vec<SaplingIvk>>,
         o_ivks: Arc<Vec<IncomingViewingKey>>,
-        wallet_txns: Arc<RwLock<WalletTxns>>,
-        detected_txid_sender: Sender<(TxId, Option<sapling::Nullifier>, BlockHeight, Option<u32>)>,
-        fulltx_fetcher: UnboundedSender<(TxId, oneshot::Sender<Result<Transaction, String>>)>,
-    ) -> Result<(), String> {
-        // println!("Starting batch at {}", temp_start);
-        let config = keys.read().await.config().clone();
-        let params = config.get_params();
-        let blk_count = cbs.len();
-        let mut workers = FuturesUnordered::new();
-
-        let download_memos = bsync_data.read().await.wallet_options.download_memos;
+    ) -> (Sender<CompactBlock>, Receiver<DecryptedBlock>) {
+        let threads = threads.max(1);
+        let (block_tx, block_rx) = channel::<CompactBlock>(threads * BLOCKS_PER_THREAD);
+        let (decrypted_tx, decrypted_rx) = channel::<DecryptedBlock>(DECRYPTED_BLOCKS);
+
+        // The threads take turns waiting for the next block
+        let block_rx = Arc::new(Mutex::new(block_rx));
+
+        for _ in 0..threads {
+            let block_rx = block_rx.clone();
+            let decrypted_tx = decrypted_tx.clone();
+            let params = params.clone();
+            let s_ivks = s_ivks.clone();
+            let o_ivks = o_ivks.clone();
+
+            std::thread::spawn(move || loop {
+                let cb = match block_rx.lock().unwrap().blocking_recv() {
+                    Some(cb) => cb,
+                    None => break,
+                };
 
-        for cb in cbs {
-            let height = BlockHeight::from_u32(cb.height as u32);
+                let decrypted = Self::trial_decrypt_block(&params, cb, &s_ivks, &o_ivks);
+                if decrypted_tx.blocking_send(decrypted).is_err() {
+                    // Nobody is waiting for the decrypted blocks anymore
+                    break;
+                }
+            });
+        }
 
-            for (tx_num, ctx) in cb.vtx.into_iter().enumerate() {
-                let tokio_handle = Handle::current();
+        (block_tx, decrypted_rx)
+    }
 
-                let ctx_hash = ctx.hash;
-                let mut wallet_tx = false;
+    /// Trial-decrypt all the outputs in a compact block with the wallet's incoming viewing keys
+    pub fn trial_decrypt_block(
+        params: &P,
+        cb: CompactBlock,
+        s_ivks: &[SaplingIvk],
+        o_ivks: &[IncomingViewingKey],
+    ) -> DecryptedBlock {
+        let height = BlockHeight::from_u32(cb.height as u32);
+
+        let txs = cb
+            .vtx
+            .into_iter()
+            .enumerate()
+            .map(|(tx_num, ctx)| {
+                let mut orchard_notes = vec![];
+                let mut sapling_notes = vec![];
 
                 // If the epk or ciphertext is missing, that means this was a spam Tx, so we can't decrypt it
                 if ctx.actions.len() > 0
@@ -165,43 +292,10 @@ impl<P: consensus::Parameters + Send + Sync + 'static> TrialDecryptions<P> {
                         })
                         .collect::<Vec<_>>();
 
-                    let decrypts = try_compact_note_decryption(o_ivks.as_ref(), orchard_actions.as_ref());
+                    let decrypts = try_compact_note_decryption(o_ivks, orchard_actions.as_ref());
                     for (output_num, maybe_decrypted) in decrypts.into_iter().enumerate() {
                         if let Some(((note, _to), ivk_num)) = maybe_decrypted {
-                            wallet_tx = true;
-
-                            let ctx_hash = ctx_hash.clone();
-
-                            let keys = keys.read().await;
-                            let detected_txid_sender = detected_txid_sender.clone();
-                            let timestamp = cb.time as u64;
-                            let fvk = keys.okeys[ivk_num].fvk();
-                            let have_spending_key = keys.have_orchard_spending_key(fvk);
-
-                            // Tell the orchard witness tree to track this note.
-                            bsync_data
-                                .read()
-                                .await
-                                .block_data
-                                .track_orchard_note(cb.height, tx_num, output_num as u32)
-                                .await;
-
-                            let txid = WalletTx::new_txid(&ctx_hash);
-                            wallet_txns.write().await.add_new_orchard_note(
-                                txid,
-                                height,
-                                false,
-                                timestamp,
-                                note,
-                                (height.into(), tx_num, output_num as u32),
-                                fvk,
-                                have_spending_key,
-                            );
-
-                            detected_txid_sender
-                                .send((txid, None, height, Some(output_num as u32)))
-                                .await
-                                .unwrap();
+                            orchard_notes.push((output_num, note, ivk_num));
                         }
                     }
                 }
@@ -209,8 +303,6 @@ impl<P: consensus::Parameters + Send + Sync + 'static> TrialDecryptions<P> {
                 // If the epk or ciphertext is missing, that means this was a spam Tx, so we can't decrypt it
                 if ctx.outputs.len() > 0 && ctx.outputs[0].epk.len() > 0 && ctx.outputs[0].ciphertext.len() > 0 {
                     // Sapling
-                    let outputs_total = ctx.outputs.len();
-                    // if outputs_total < 100 {
                     let outputs = ctx
                         .outputs
                         .into_iter()
@@ -218,86 +310,27 @@ impl<P: consensus::Parameters + Send + Sync + 'static> TrialDecryptions<P> {
                         .collect::<Vec<_>>();
 
                     // Batch decryption for sapling
-                    let decrypts = try_compact_note_decryption(s_ivks.as_ref(), outputs.as_ref());
-
-                    for (dec_num, maybe_decrypted) in decrypts.into_iter().enumerate() {
+                    let decrypts = try_compact_note_decryption(s_ivks, outputs.as_ref());
+                    for (output_num, maybe_decrypted) in decrypts.into_iter().enumerate() {
                         if let Some(((note, to), ivk_num)) = maybe_decrypted {
-                            wallet_tx = true;
-
-                            let ctx_hash = ctx_hash.clone();
-                            let output_num = dec_num % outputs_total;
-
-                            let keys = keys.clone();
-                            let bsync_data = bsync_data.clone();
-                            let wallet_txns = wallet_txns.clone();
-                            let detected_txid_sender = detected_txid_sender.clone();
-                            let timestamp = cb.time as u64;
-
-                            workers.push(tokio_handle.spawn(async move {
-                                let keys = keys.read().await;
-                                let extfvk = keys.zkeys[ivk_num].extfvk();
-                                let have_spending_key = keys.have_sapling_spending_key(extfvk);
-                                let uri = bsync_data.read().await.uri().clone();
-
-                                // Get the witness for the note
-                                let witness = bsync_data
-                                    .read()
-                                    .await
-                                    .block_data
-                                    .get_note_witness(uri, height, tx_num, output_num)
-                                    .await?;
-
-                                let txid = WalletTx::new_txid(&ctx_hash);
-                                let nullifier = note.nf(&extfvk.fvk.vk.nk, witness.position() as u64);
-
-                                wallet_txns.write().await.add_new_sapling_note(
-                                    txid.clone(),
-                                    height,
-                                    false,
-                                    timestamp,
-                                    note,
-                                    to,
-                                    &extfvk,
-                                    have_spending_key,
-                                    witness,
-                                );
-
-                                info!("Trial decrypt Detected txid {}", &txid);
-
-                                detected_txid_sender
-                                    .send((txid, Some(nullifier), height, Some(output_num as u32)))
-                                    .await
-                                    .unwrap();
-
-                                Ok::<_, String>(())
-                            }));
+                            sapling_notes.push((output_num, note, to, ivk_num));
                         }
                     }
                 }
 
-                // Check option to see if we are fetching all txns.
-                if !wallet_tx && download_memos == MemoDownloadOption::AllMemos {
-                    let txid = WalletTx::new_txid(&ctx_hash);
-                    let (tx, rx) = oneshot::channel();
-                    fulltx_fetcher.send((txid, tx)).unwrap();
-
-                    workers.push(tokio::spawn(async move {
-                        // Discard the result, because this was not a wallet tx.
-                        rx.await.unwrap().map(|_r| ())
-                    }));
+                DecryptedTx {
+                    tx_num,
+                    hash: ctx.hash,
+                    orchard_notes,
+                    sapling_notes,
                 }
-            }
-        }
+            })
+            .collect();
 
-        while let Some(r) = workers.next().await {
-            r.map_err(|e| e.to_string())??;
+        DecryptedBlock {
+            height: cb.height,
+            time: cb.time,
+            txs,
         }
-
-        // Update sync status
-        bsync_data.read().await.sync_status.write().await.trial_dec_done += blk_count as u64;
-
-        // Return a nothing-value
-        // println!("Finished batch at {}", temp_start);
-        Ok::<(), String>(())
     }
 }
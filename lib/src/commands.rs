@@ -1,13 +1,22 @@
+use crate::lightwallet::data::{CoinSelection, SendFrom, SendMaxSource};
+use crate::lightwallet::fee::FeePolicy;
 use crate::lightwallet::keys::Keys;
+use crate::lightwallet::multisig::PartiallySignedTx;
+use crate::lightwallet::offline::UnsignedTx;
+use crate::lightwallet::privacy::PrivacyPolicy;
+use crate::lightwallet::sync_order::SyncOrder;
 use crate::lightwallet::MemoDownloadOption;
-use crate::{lightclient::LightClient, lightwallet::utils};
+use crate::{
+    lightclient::{LightClient, UnlockDuration},
+    lightwallet::utils,
+};
 use json::object;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use tokio::runtime::Runtime;
 use zcash_primitives::consensus::{self};
-use zcash_primitives::transaction::components::amount::DEFAULT_FEE;
+use zcash_primitives::transaction::TxId;
 
 lazy_static! {
     static ref RT: Runtime = tokio::runtime::Runtime::new().unwrap();
@@ -47,6 +56,91 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SyncComman
     }
 }
 
+struct CancelSyncCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for CancelSyncCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Stop the running sync once it finishes its current batch of blocks");
+        h.push("The blocks synced so far are saved, and the next sync continues from them.");
+        h.push("Usage:");
+        h.push("cancelsync");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Stop the running sync".to_string()
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
+        RT.block_on(async move {
+            match lightclient.cancel_sync().await {
+                Ok(_) => object! { "result" => "success" },
+                Err(e) => object! {
+                    "result" => "error",
+                    "error"  => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct PauseSyncCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for PauseSyncCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Pause syncing. The running sync stops once it finishes its current batch of blocks, and the");
+        h.push("wallet doesn't sync again until 'resumesync'.");
+        h.push("Usage:");
+        h.push("pausesync");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Pause syncing until 'resumesync'".to_string()
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
+        RT.block_on(async move {
+            lightclient.pause_sync().await;
+            object! { "result" => "success" }.pretty(2)
+        })
+    }
+}
+
+struct ResumeSyncCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ResumeSyncCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Resume syncing after 'pausesync', and sync the rest of the blocks");
+        h.push("Usage:");
+        h.push("resumesync");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Resume a paused sync".to_string()
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
+        RT.block_on(async move {
+            match lightclient.resume_sync().await {
+                Ok(j) => j.pretty(2),
+                Err(e) => e,
+            }
+        })
+    }
+}
+
 struct EncryptionStatusCommand {}
 
 impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for EncryptionStatusCommand {
@@ -89,8 +183,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SyncStatus
     fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
         RT.block_on(async move {
             let status = lightclient.do_sync_status().await;
+            let paused = lightclient.is_sync_paused().await;
+            let done_ranges = status
+                .done_ranges
+                .iter()
+                .map(|(first, last)| object! { "first_block" => *first, "last_block" => *last })
+                .collect::<Vec<_>>();
 
-            let o = if status.in_progress {
+            let mut o = if status.in_progress {
                 object! {
                     "sync_id" => status.sync_id,
                     "in_progress" => status.in_progress,
@@ -102,15 +202,27 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SyncStatus
                     "txn_scan_blocks" => status.txn_scan_done,
                     "total_blocks" => status.blocks_total,
                     "batch_num" => status.batch_num,
-                    "batch_total" => status.batch_total
+                    "batch_total" => status.batch_total,
+                    "done_ranges" => done_ranges,
+                    "paused" => paused
                 }
             } else {
                 object! {
                     "sync_id" => status.sync_id,
                     "in_progress" => status.in_progress,
                     "last_error" => status.last_error,
+                    "done_ranges" => done_ranges,
+                    "paused" => paused,
                 }
             };
+
+            // What a recent-first sync found in the newest blocks, while it backfills the older ones
+            let provisional = lightclient.wallet.provisional_funds().await;
+            o["provisional"] = provisional.is_some().into();
+            if let Some((received, spent)) = provisional {
+                o["provisional_received"] = received.into();
+                o["provisional_spent"] = spent.into();
+            }
             o.pretty(2)
         })
     }
@@ -465,6 +577,125 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for DecryptCom
     }
 }
 
+struct ChangePasswordCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ChangePasswordCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Change the password of an encrypted wallet");
+        h.push("Note 1: The seed and private keys are re-encrypted in memory with the new password, and the");
+        h.push("        wallet is saved. The wallet is never written to disk unencrypted.");
+        h.push("Note 2: If you've forgotten the password, the only way to recover the wallet is to restore");
+        h.push("        from the seed phrase.");
+        h.push("Usage:");
+        h.push("changepassword old_password new_password");
+        h.push("");
+        h.push("Example:");
+        h.push("changepassword my_strong_password my_stronger_password");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Change the wallet's encryption password".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 2 {
+            return Command::<P>::help(self);
+        }
+
+        let old_passwd = args[0].to_string();
+        let new_passwd = args[1].to_string();
+        RT.block_on(async move {
+            match lightclient.do_change_password(old_passwd, new_passwd).await {
+                Ok(j) => j,
+                Err(e) => object! {
+                    "result" => "error",
+                    "error"  => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct EncryptFileCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for EncryptFileCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Encrypt the whole wallet file, including the transaction history, memos and addresses");
+        h.push("Note 1: The wallet has to be encrypted with 'encrypt' first. The file is encrypted with the same");
+        h.push("        password.");
+        h.push("Note 2: When the wallet is next opened, its history can't be read or synced until it is unlocked.");
+        h.push("Usage:");
+        h.push("encryptfile password");
+        h.push("");
+        h.push("Example:");
+        h.push("encryptfile my_strong_password");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Encrypt the whole wallet file".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let passwd = args[0].to_string();
+        RT.block_on(async move {
+            match lightclient.do_set_file_encryption(passwd, true).await {
+                Ok(j) => j,
+                Err(e) => object! {
+                    "result" => "error",
+                    "error"  => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct DecryptFileCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for DecryptFileCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Stop encrypting the whole wallet file. The keys in the wallet stay encrypted.");
+        h.push("Usage:");
+        h.push("decryptfile password");
+        h.push("");
+        h.push("Example:");
+        h.push("decryptfile my_strong_password");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Stop encrypting the whole wallet file".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let passwd = args[0].to_string();
+        RT.block_on(async move {
+            match lightclient.do_set_file_encryption(passwd, false).await {
+                Ok(j) => j,
+                Err(e) => object! {
+                    "result" => "error",
+                    "error"  => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
 struct UnlockCommand {}
 
 impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for UnlockCommand {
@@ -475,11 +706,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for UnlockComm
         h.push("        Use 'decrypt' to remove the encryption permanatly.");
         h.push("Note 2: If you've forgotten the password, the only way to recover the wallet is to restore");
         h.push("        from the seed phrase.");
+        h.push("Note 3: Pass a number of seconds to lock the wallet again automatically after that long, or");
+        h.push("        'onesend' to lock it again right after the next send. The 'auto_lock_timeout' option");
+        h.push("        additionally locks the wallet after that many seconds without a send. Without either,");
+        h.push("        the wallet is locked again the next time it is saved.");
         h.push("Usage:");
-        h.push("unlock password");
+        h.push("unlock password [seconds | onesend]");
         h.push("");
         h.push("Example:");
-        h.push("unlock my_strong_password");
+        h.push("unlock my_strong_password 300");
 
         h.join("\n")
     }
@@ -488,17 +723,26 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for UnlockComm
         "Unlock wallet encryption for spending".to_string()
     }
     fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
-        if args.len() != 1 {
+        if args.len() < 1 || args.len() > 2 {
             return Command::<P>::help(self);
         }
 
+        let duration = match args.get(1) {
+            None => UnlockDuration::UntilLocked,
+            Some(&"onesend") => UnlockDuration::OneSend,
+            Some(s) => match s.parse::<u64>() {
+                Ok(secs) => UnlockDuration::Seconds(secs),
+                Err(_) => return format!("Couldn't parse '{}'. Expected seconds or 'onesend'", s),
+            },
+        };
+
         let passwd = args[0].to_string();
         RT.block_on(async move {
-            match lightclient.wallet.unlock(passwd).await {
-                Ok(_) => object! { "result" => "success" },
+            match lightclient.do_unlock(passwd, duration).await {
+                Ok(j) => j,
                 Err(e) => object! {
                     "result" => "error",
-                    "error"  => e.to_string()
+                    "error"  => e
                 },
             }
             .pretty(2)
@@ -556,9 +800,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ShieldComm
         let mut h = vec![];
         h.push("Shield all your transparent funds");
         h.push("Usage:");
-        h.push("shield [optional address]");
+        h.push("shield [optional address] [optional fee in zatoshis]");
+        h.push("OR");
+        h.push("shield '{'address': <optional address>, 'fee': <optional fee>, 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...]}'");
         h.push("");
-        h.push("NOTE: The fee required to send this transaction (currently ZEC 0.0001) is additionally deducted from your balance.");
+        h.push("NOTE: The fee required to send this transaction (see 'defaultfee') is additionally deducted from your balance.");
+        h.push("Passing a fee overrides the wallet's fee policy for this transaction.");
+        h.push("If 'utxos' are given, only those are shielded. Otherwise all transparent funds are.");
+        h.push("Too many utxos for one transaction are shielded in several, each paying its own fee. See 'sendprogress'.");
         h.push("Example:");
         h.push("shield");
         h.push("");
@@ -571,13 +820,46 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ShieldComm
     }
     fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
         // Parse the address or amount
-        let address = if args.len() > 0 {
-            Some(args[0].to_string())
+        let (address, fee, utxos) = if args.len() == 1 && args[0].starts_with('{') {
+            let j = match json::parse(args[0]) {
+                Ok(j) => j,
+                Err(e) => return format!("Couldn't understand JSON: {}\n{}", e, Command::<P>::help(self)),
+            };
+
+            let fee = match (j.has_key("fee"), j["fee"].as_u64()) {
+                (false, _) => None,
+                (true, Some(fee)) => Some(fee),
+                (true, None) => return format!("Couldn't parse fee\n{}", Command::<P>::help(self)),
+            };
+            let utxos = if j.has_key("utxos") {
+                match parse_outpoints(&j["utxos"]) {
+                    Ok(utxos) => Some(utxos),
+                    Err(e) => return format!("Error: {}\n{}", e, Command::<P>::help(self)),
+                }
+            } else {
+                None
+            };
+
+            (j["address"].as_str().map(|s| s.to_string()), fee, utxos)
         } else {
-            None
+            let address = if args.len() > 0 {
+                Some(args[0].to_string())
+            } else {
+                None
+            };
+            let fee = if args.len() > 1 {
+                match args[1].parse::<u64>() {
+                    Ok(fee) => Some(fee),
+                    Err(e) => return format!("Couldn't parse fee: {}", e),
+                }
+            } else {
+                None
+            };
+
+            (address, fee, None)
         };
         RT.block_on(async move {
-            match lightclient.do_shield(address).await {
+            match lightclient.do_shield(address, fee, utxos).await {
                 Ok(txid) => {
                     object! { "txid" => txid }
                 }
@@ -702,8 +984,17 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SendComman
         h.push("send <address> <amount in zatoshis || \"entire-verified-zbalance\"> \"optional_memo\"");
         h.push("OR");
         h.push("send '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'");
+        h.push("OR");
+        h.push("send '{'tos': [{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...], 'fee': <optional fee in zatoshis>,");
+        h.push("       'notes': [{'txid': <txid>, 'output_index': <index>}, ...], 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...],");
+        h.push("       'from': <optional source address>, 'change_to_source': <optional true/false>}'");
         h.push("");
-        h.push("NOTE: The fee required to send this transaction (currently ZEC 0.0001) is additionally deducted from your balance.");
+        h.push("NOTE: The fee required to send this transaction (see 'defaultfee') is additionally deducted from your balance.");
+        h.push("A 'fee' overrides the wallet's fee policy for this transaction.");
+        h.push("If 'notes' or 'utxos' are given, exactly those inputs are spent, as listed by the 'notes' command.");
+        h.push("If 'from' is given, only the funds received at that t or z address are spent. With 'change_to_source',");
+        h.push("the change goes back to that address instead of the wallet's first sapling address.");
+        h.push("A payment to one address whose inputs don't fit in one transaction is split over several. See 'sendprogress'.");
         h.push("Example:");
         h.push("send ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000 \"Hello from the command line\"");
         h.push("");
@@ -718,100 +1009,25 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SendComman
         // Parse the args. There are two argument types.
         // 1 - A set of 2(+1 optional) arguments for a single address send representing address, value, memo?
         // 2 - A single argument in the form of a JSON string that is "[{address: address, value: value, memo: memo},...]"
+        //     or "{tos: [...], fee: fee, notes: [...], utxos: [...], from: address}" to override the fee, choose the
+        //     inputs or the address they come from
         if args.len() < 1 || args.len() > 3 {
             return Command::<P>::help(self);
         }
 
         RT.block_on(async move {
-            // Check for a single argument that can be parsed as JSON
-            let send_args = if args.len() == 1 {
-                let arg_list = args[0];
-
-                let json_args = match json::parse(&arg_list) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        let es = format!("Couldn't understand JSON: {}", e);
-                        return format!("{}\n{}", es, Command::<P>::help(self));
-                    }
-                };
-
-                if !json_args.is_array() {
-                    return format!("Couldn't parse argument as array\n{}", Command::<P>::help(self));
-                }
-
-                let fee = u64::from(DEFAULT_FEE);
-                let all_zbalance = lightclient.wallet.verified_zbalance(None).await.checked_sub(fee);
-
-                let maybe_send_args = json_args
-                    .members()
-                    .map(|j| {
-                        if !j.has_key("address") || !j.has_key("amount") {
-                            Err(format!("Need 'address' and 'amount'\n"))
-                        } else {
-                            let amount = match j["amount"].as_str() {
-                                Some("entire-verified-zbalance") => all_zbalance,
-                                _ => Some(j["amount"].as_u64().unwrap()),
-                            };
-
-                            match amount {
-                                Some(amt) => Ok((
-                                    j["address"].as_str().unwrap().to_string().clone(),
-                                    amt,
-                                    j["memo"].as_str().map(|s| s.to_string().clone()),
-                                )),
-                                None => Err(format!("Not enough in wallet to pay transaction fee of {}", fee)),
-                            }
-                        }
-                    })
-                    .collect::<Result<Vec<(String, u64, Option<String>)>, String>>();
-
-                match maybe_send_args {
-                    Ok(a) => a.clone(),
-                    Err(s) => {
-                        return format!("Error: {}\n{}", s, Command::<P>::help(self));
-                    }
-                }
-            } else if args.len() == 2 || args.len() == 3 {
-                let address = args[0].to_string();
-
-                // Make sure we can parse the amount
-                let value = match args[1].parse::<u64>() {
-                    Ok(amt) => amt,
-                    Err(e) => {
-                        if args[1] == "entire-verified-zbalance" {
-                            let fee = u64::from(DEFAULT_FEE);
-                            match lightclient.wallet.verified_zbalance(None).await.checked_sub(fee) {
-                                Some(amt) => amt,
-                                None => return format!("Not enough in wallet to pay transaction fee of {}", fee),
-                            }
-                        } else {
-                            return format!("Couldn't parse amount: {}", e);
-                        }
-                    }
-                };
-
-                let memo = if args.len() == 3 {
-                    Some(args[2].to_string())
-                } else {
-                    None
+            let (send_args, fee, inputs, from) =
+                match parse_send_args(args, lightclient, Command::<P>::help(self)).await {
+                    Ok(a) => a,
+                    Err(e) => return e,
                 };
 
-                // Memo has to be None if not sending to a shileded address
-                if memo.is_some() && !Keys::is_shielded_address(&address, &lightclient.config) {
-                    return format!("Can't send a memo to the non-shielded address {}", address);
-                }
-
-                vec![(args[0].to_string(), value, memo)]
-            } else {
-                return Command::<P>::help(self);
-            };
-
             // Convert to the right format. String -> &str.
             let tos = send_args
                 .iter()
                 .map(|(a, v, m)| (a.as_str(), *v, m.clone()))
                 .collect::<Vec<_>>();
-            match lightclient.do_send(tos).await {
+            match lightclient.do_send_with_options(tos, fee, inputs, from).await {
                 Ok(txid) => {
                     object! { "txid" => txid }
                 }
@@ -824,71 +1040,1238 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SendComman
     }
 }
 
-struct SaveCommand {}
+struct ConsolidateCommand {}
 
-impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SaveCommand {
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ConsolidateCommand {
     fn help(&self) -> String {
         let mut h = vec![];
-        h.push("Save the wallet to disk");
+        h.push("Merge your smallest shielded notes, so later sends are smaller and faster to prove");
         h.push("Usage:");
-        h.push("save");
+        h.push("consolidate [dryrun] [optional number of notes]");
+        h.push("OR");
+        h.push("consolidate '{'notes': <optional number of notes>, 'address': <optional z-address>, 'fee': <optional fee>, 'dryrun': <true | false>}'");
+        h.push("");
+        h.push("The smallest spendable notes are merged into notes to your first z-address, or 'address', which must be one of yours.");
+        h.push("All spendable notes are merged unless 'notes' is given. Each transaction spends as many notes as fit in it,");
+        h.push("and pays its own fee. With 'dryrun', the transactions and fees it would take are shown, and nothing is sent.");
+        h.push("Example:");
+        h.push("consolidate dryrun 100");
         h.push("");
-        h.push("The wallet is saved to disk. The wallet is periodically saved to disk (and also saved upon exit)");
-        h.push("but you can use this command to explicitly save it to disk");
 
         h.join("\n")
     }
 
     fn short_help(&self) -> String {
-        "Save wallet file to disk".to_string()
+        "Merge your smallest shielded notes into one".to_string()
     }
-    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let (address, count, fee, dry_run) = if args.len() == 1 && args[0].starts_with('{') {
+            let j = match json::parse(args[0]) {
+                Ok(j) => j,
+                Err(e) => return format!("Couldn't understand JSON: {}\n{}", e, Command::<P>::help(self)),
+            };
+
+            let count = match (j.has_key("notes"), j["notes"].as_usize()) {
+                (false, _) => None,
+                (true, Some(count)) => Some(count),
+                (true, None) => return format!("Couldn't parse number of notes\n{}", Command::<P>::help(self)),
+            };
+            let fee = match (j.has_key("fee"), j["fee"].as_u64()) {
+                (false, _) => None,
+                (true, Some(fee)) => Some(fee),
+                (true, None) => return format!("Couldn't parse fee\n{}", Command::<P>::help(self)),
+            };
+
+            let address = j["address"].as_str().map(|s| s.to_string());
+            (address, count, fee, j["dryrun"].as_bool().unwrap_or(false))
+        } else {
+            let dry_run = args.first() == Some(&"dryrun");
+            let args = if dry_run { &args[1..] } else { args };
+            if args.len() > 1 {
+                return format!("Error: Too many arguments\n{}", Command::<P>::help(self));
+            }
+
+            let count = match args.first().map(|s| s.parse::<usize>()) {
+                None => None,
+                Some(Ok(count)) => Some(count),
+                Some(Err(e)) => return format!("Couldn't parse number of notes: {}", e),
+            };
+
+            (None, count, None, dry_run)
+        };
+
         RT.block_on(async move {
-            match lightclient.do_save(true).await {
-                Ok(_) => {
-                    let r = object! { "result" => "success" };
-                    r.pretty(2)
+            if dry_run {
+                match lightclient.do_consolidation_preview(address, count, fee).await {
+                    Ok(j) => j,
+                    Err(e) => object! { "error" => e },
                 }
-                Err(e) => {
-                    let r = object! {
-                        "result" => "error",
-                        "error" => e
-                    };
-                    r.pretty(2)
+            } else {
+                match lightclient.do_consolidate(address, count, fee).await {
+                    Ok(txids) => object! { "txids" => txids },
+                    Err(e) => object! { "error" => e },
                 }
             }
+            .pretty(2)
         })
     }
 }
 
-struct SeedCommand {}
+struct SendMaxCommand {}
 
-impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SeedCommand {
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SendMaxCommand {
     fn help(&self) -> String {
         let mut h = vec![];
-        h.push("Show the wallet's seed phrase");
+        h.push("Send all the spendable funds to one address. The fee is taken out of the amount sent, so there is no change.");
         h.push("Usage:");
-        h.push("seed");
+        h.push("sendmax <address> \"optional_memo\"");
+        h.push("OR");
+        h.push("sendmax '{'address': <address>, 'memo': <optional memo>, 'from': <optional source>, 'fee': <optional fee in zatoshis>}'");
+        h.push("");
+        h.push("The source is 'all' (the default), 'transparent', 'sapling' or one of this wallet's addresses, to only");
+        h.push("spend the funds in that pool or address. Use 'sendprogress' to see the amount sent and the fee.");
+        h.push("Example:");
+        h.push("sendmax ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d \"Hello from the command line\"");
         h.push("");
-        h.push("Your wallet is entirely recoverable from the seed phrase. Please save it carefully and don't share it with anyone");
 
         h.join("\n")
     }
 
     fn short_help(&self) -> String {
-        "Display the seed phrase".to_string()
+        "Send all the spendable funds to an address, minus the fee".to_string()
     }
-    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
-        RT.block_on(async move {
-            match lightclient.do_seed_phrase().await {
-                Ok(j) => j,
-                Err(e) => object! { "error" => e },
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() < 1 || args.len() > 2 {
+            return Command::<P>::help(self);
+        }
+
+        let (address, memo, source, fee) = match json::parse(args[0]) {
+            Ok(j) if args.len() == 1 && j.is_object() => {
+                let address = match j["address"].as_str() {
+                    Some(a) => a.to_string(),
+                    None => return format!("Need an 'address'\n{}", Command::<P>::help(self)),
+                };
+                let fee = if j.has_key("fee") {
+                    match j["fee"].as_u64() {
+                        Some(f) => Some(f),
+                        None => return format!("Couldn't parse fee\n{}", Command::<P>::help(self)),
+                    }
+                } else {
+                    None
+                };
+                let source = j["from"].as_str().map(|s| s.to_string());
+
+                (address, j["memo"].as_str().map(|m| m.to_string()), source, fee)
+            }
+            _ => (args[0].to_string(), args.get(1).map(|m| m.to_string()), None, None),
+        };
+
+        // Memo has to be None if not sending to a shileded address
+        if memo.is_some() && !Keys::is_shielded_address(&address, &lightclient.config) {
+            return format!("Can't send a memo to the non-shielded address {}", address);
+        }
+
+        RT.block_on(async move {
+            let source = match source {
+                Some(s) => {
+                    let keys = lightclient.wallet.keys();
+                    let keys = keys.read().await;
+                    let addresses = [keys.get_all_zaddresses(), keys.get_all_taddrs()].concat();
+                    match SendMaxSource::parse(&s, &addresses) {
+                        Ok(source) => source,
+                        Err(e) => return object! { "error" => e }.pretty(2),
+                    }
+                }
+                None => SendMaxSource::All,
+            };
+
+            match lightclient.do_send_max(&address, memo, source, fee).await {
+                Ok(txid) => {
+                    object! { "txid" => txid }
+                }
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+// Parse a JSON list of [{txid, output_index}, ...] into outpoints
+fn parse_outpoints(j: &json::JsonValue) -> Result<Vec<(TxId, u32)>, String> {
+    if !j.is_array() {
+        return Err("Inputs should be a list of {'txid', 'output_index'}".to_string());
+    }
+
+    j.members()
+        .map(|o| {
+            let txid = utils::parse_txid(o["txid"].as_str().ok_or("Input is missing a 'txid'".to_string())?)?;
+            let output_index = o["output_index"]
+                .as_u32()
+                .ok_or("Input is missing an 'output_index'".to_string())?;
+
+            Ok((txid, output_index))
+        })
+        .collect()
+}
+
+// Parse the arguments of `send` and `propose` into the recipients, the optional fee override, the optional
+// inputs to spend and the optional source address. On error, returns the message to print.
+async fn parse_send_args<P: consensus::Parameters + Send + Sync + 'static>(
+    args: &[&str],
+    lightclient: &LightClient<P>,
+    help: String,
+) -> Result<
+    (
+        Vec<(String, u64, Option<String>)>,
+        Option<u64>,
+        Option<CoinSelection>,
+        Option<SendFrom>,
+    ),
+    String,
+> {
+    let mut fee = None;
+    let mut inputs = None;
+    let mut from = None;
+
+    // Check for a single argument that can be parsed as JSON
+    let send_args = if args.len() == 1 {
+        let arg_list = args[0];
+
+        let mut json_args = match json::parse(&arg_list) {
+            Ok(j) => j,
+            Err(e) => {
+                let es = format!("Couldn't understand JSON: {}", e);
+                return Err(format!("{}\n{}", es, help));
+            }
+        };
+
+        if json_args.is_object() {
+            if json_args.has_key("fee") {
+                fee = match json_args["fee"].as_u64() {
+                    Some(f) => Some(f),
+                    None => return Err(format!("Couldn't parse fee\n{}", help)),
+                };
+            }
+            if json_args.has_key("notes") || json_args.has_key("utxos") {
+                let mut selection = CoinSelection::default();
+                if json_args.has_key("notes") {
+                    selection.s_notes =
+                        parse_outpoints(&json_args["notes"]).map_err(|e| format!("Error: {}\n{}", e, help))?;
+                }
+                if json_args.has_key("utxos") {
+                    selection.utxos =
+                        parse_outpoints(&json_args["utxos"]).map_err(|e| format!("Error: {}\n{}", e, help))?;
+                }
+                inputs = Some(selection);
+            }
+            if json_args.has_key("from") {
+                let address = match json_args["from"].as_str() {
+                    Some(a) => a.to_string(),
+                    None => return Err(format!("Couldn't parse the source address\n{}", help)),
+                };
+                from = Some(SendFrom {
+                    address,
+                    change_to_source: json_args["change_to_source"].as_bool().unwrap_or(false),
+                });
+            }
+            json_args = json_args["tos"].take();
+        }
+
+        if !json_args.is_array() {
+            return Err(format!("Couldn't parse argument as array\n{}", help));
+        }
+
+        let entire_fee = match fee {
+            Some(f) => f,
+            None => lightclient.wallet.verified_zbalance_fee(json_args.len()).await,
+        };
+        let all_zbalance = lightclient.wallet.spendable_zbalance(None).await.checked_sub(entire_fee);
+
+        let maybe_send_args = json_args
+            .members()
+            .map(|j| {
+                if !j.has_key("address") || !j.has_key("amount") {
+                    Err(format!("Need 'address' and 'amount'\n"))
+                } else {
+                    let amount = match j["amount"].as_str() {
+                        Some("entire-verified-zbalance") => all_zbalance,
+                        _ => Some(j["amount"].as_u64().unwrap()),
+                    };
+
+                    match amount {
+                        Some(amt) => Ok((
+                            j["address"].as_str().unwrap().to_string().clone(),
+                            amt,
+                            j["memo"].as_str().map(|s| s.to_string().clone()),
+                        )),
+                        None => Err(format!("Not enough in wallet to pay transaction fee of {}", entire_fee)),
+                    }
+                }
+            })
+            .collect::<Result<Vec<(String, u64, Option<String>)>, String>>();
+
+        match maybe_send_args {
+            Ok(a) => a.clone(),
+            Err(s) => {
+                return Err(format!("Error: {}\n{}", s, help));
+            }
+        }
+    } else if args.len() == 2 || args.len() == 3 {
+        let address = args[0].to_string();
+
+        // Make sure we can parse the amount
+        let value = match args[1].parse::<u64>() {
+            Ok(amt) => amt,
+            Err(e) => {
+                if args[1] == "entire-verified-zbalance" {
+                    let fee = lightclient.wallet.verified_zbalance_fee(1).await;
+                    match lightclient.wallet.spendable_zbalance(None).await.checked_sub(fee) {
+                        Some(amt) => amt,
+                        None => return Err(format!("Not enough in wallet to pay transaction fee of {}", fee)),
+                    }
+                } else {
+                    return Err(format!("Couldn't parse amount: {}", e));
+                }
+            }
+        };
+
+        let memo = if args.len() == 3 {
+            Some(args[2].to_string())
+        } else {
+            None
+        };
+
+        // Memo has to be None if not sending to a shileded address
+        if memo.is_some() && !Keys::is_shielded_address(&address, &lightclient.config) {
+            return Err(format!("Can't send a memo to the non-shielded address {}", address));
+        }
+
+        vec![(args[0].to_string(), value, memo)]
+    } else {
+        return Err(help);
+    };
+
+    Ok((send_args, fee, inputs, from))
+}
+
+struct ProposeCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ProposeCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Propose a transaction without sending it, to review its fee, inputs and privacy first");
+        h.push("Usage:");
+        h.push("propose <address> <amount in zatoshis || \"entire-verified-zbalance\"> \"optional_memo\"");
+        h.push("OR");
+        h.push("propose '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'");
+        h.push("OR");
+        h.push("propose '{'tos': [{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...], 'fee': <optional fee in zatoshis>,");
+        h.push("          'notes': [{'txid': <txid>, 'output_index': <index>}, ...], 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...],");
+        h.push("          'from': <optional source address>, 'change_to_source': <optional true/false>}'");
+        h.push("");
+        h.push("The proposal's id can be passed to 'confirmproposal' to send it, or to 'discardproposal' to throw it away.");
+        h.push("Example:");
+        h.push("propose ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000 \"Hello from the command line\"");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Propose a transaction, to review before sending it".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() < 1 || args.len() > 3 {
+            return Command::<P>::help(self);
+        }
+
+        RT.block_on(async move {
+            let (send_args, fee, inputs, from) =
+                match parse_send_args(args, lightclient, Command::<P>::help(self)).await {
+                    Ok(a) => a,
+                    Err(e) => return e,
+                };
+
+            let tos = send_args
+                .iter()
+                .map(|(a, v, m)| (a.as_str(), *v, m.clone()))
+                .collect::<Vec<_>>();
+            match lightclient.do_propose(tos, fee, inputs, from).await {
+                Ok(j) => j,
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct ConfirmProposalCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for ConfirmProposalCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Send a transaction proposed with 'propose'");
+        h.push("Usage:");
+        h.push("confirmproposal <proposal id>");
+        h.push("");
+        h.push("The transaction spends exactly the inputs shown in the proposal. If any of them have been spent since,");
+        h.push("the proposal is stale and has to be proposed again.");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Send a proposed transaction".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let id = match args[0].parse::<u32>() {
+            Ok(id) => id,
+            Err(e) => return format!("Couldn't parse proposal id: {}", e),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_send_proposal(id).await {
+                Ok(txid) => {
+                    object! { "txid" => txid }
+                }
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct DiscardProposalCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for DiscardProposalCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Throw away a transaction proposed with 'propose', without sending it");
+        h.push("Usage:");
+        h.push("discardproposal <proposal id>");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Throw away a proposed transaction".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let id = match args[0].parse::<u32>() {
+            Ok(id) => id,
+            Err(e) => return format!("Couldn't parse proposal id: {}", e),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_discard_proposal(id).await {
+                Ok(_) => object! { "result" => "success" },
+                Err(e) => object! {
+                    "result" => "error",
+                    "error" => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+// Parse the arguments of `freeze` and `unfreeze`: either "note|utxo <txid> <output_index>", or a JSON object with
+// lists of "notes" and "utxos"
+fn parse_freeze_args(args: &[&str]) -> Result<CoinSelection, String> {
+    if args.len() == 1 {
+        let j = json::parse(args[0]).map_err(|e| format!("Couldn't understand JSON: {}", e))?;
+        if !j.is_object() {
+            return Err("Expected a JSON object with 'notes' and/or 'utxos'".to_string());
+        }
+
+        let mut selection = CoinSelection::default();
+        if j.has_key("notes") {
+            selection.s_notes = parse_outpoints(&j["notes"])?;
+        }
+        if j.has_key("utxos") {
+            selection.utxos = parse_outpoints(&j["utxos"])?;
+        }
+
+        return Ok(selection);
+    }
+
+    if args.len() != 3 {
+        return Err("Wrong number of arguments".to_string());
+    }
+
+    let outpoint = (
+        utils::parse_txid(args[1])?,
+        args[2]
+            .parse::<u32>()
+            .map_err(|e| format!("Couldn't parse output index: {}", e))?,
+    );
+
+    match args[0] {
+        "note" => Ok(CoinSelection {
+            s_notes: vec![outpoint],
+            utxos: vec![],
+        }),
+        "utxo" => Ok(CoinSelection {
+            s_notes: vec![],
+            utxos: vec![outpoint],
+        }),
+        _ => Err(format!("Expected 'note' or 'utxo', got {}", args[0])),
+    }
+}
+
+struct CreateUnsignedTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for CreateUnsignedTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Create an unsigned transaction spending the notes of imported viewing keys, for an offline signer");
+        h.push("Usage:");
+        h.push("createunsignedtx <file> <address> <amount in zatoshis> \"optional_memo\"");
+        h.push("OR");
+        h.push("createunsignedtx <file> '{'tos': [{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...], 'fee': <optional fee in zatoshis>,");
+        h.push("          'notes': [{'txid': <txid>, 'output_index': <index>}, ...], 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...]}'");
+        h.push("");
+        h.push("The unsigned transaction is written to <file>. Carry it to the offline wallet that has the spending keys, and run");
+        h.push("'signtx' there. Then broadcast the signed transaction from this wallet with 'broadcastsignedtx'.");
+        h.push("Needs 'setoption offline_signing=true' and a rescan, so the witnesses of the viewing key notes are kept.");
+        h.push("Transparent funds are only spent if they are chosen in 'utxos', and the offline wallet must have their keys.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Create an unsigned transaction for an offline signer".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() < 2 || args.len() > 4 {
+            return Command::<P>::help(self);
+        }
+
+        let file = args[0];
+
+        RT.block_on(async move {
+            let (send_args, fee, inputs) = match parse_send_args(&args[1..], lightclient, Command::<P>::help(self)).await
+            {
+                Ok(a) => a,
+                Err(e) => return e,
+            };
+
+            let tos = send_args
+                .iter()
+                .map(|(a, v, m)| (a.as_str(), *v, m.clone()))
+                .collect::<Vec<_>>();
+            let result = match lightclient.do_create_unsigned_tx(tos, fee, inputs).await {
+                Ok(utx) => {
+                    let mut buf = vec![];
+                    utx.write(&mut buf)
+                        .and_then(|_| std::fs::write(file, &buf))
+                        .map(|_| utx.to_json(lightclient.config.hrp_sapling_address()))
+                        .map_err(|e| format!("Couldn't write {}: {}", file, e))
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct SignTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SignTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Sign an unsigned transaction created by a watch-only wallet with 'createunsignedtx'");
+        h.push("Usage:");
+        h.push("signtx <unsigned file>");
+        h.push("signtx <unsigned file> <signed file> <id>");
+        h.push("");
+        h.push("The first form shows the outputs, fee and change of the transaction, and its id. Check them, and then");
+        h.push("pass the id to the second form to sign it. The change has to go back to an address of this wallet.");
+        h.push("The transaction is proved and signed with the spending keys in this wallet, and written to <signed file>.");
+        h.push("It is not broadcast. Carry it back to the watch-only wallet, and run 'broadcastsignedtx' there.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Sign an unsigned transaction offline".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 && args.len() != 3 {
+            return Command::<P>::help(self);
+        }
+
+        let utx = match std::fs::read(args[0]).and_then(|buf| UnsignedTx::read(&buf[..])) {
+            Ok(utx) => utx,
+            Err(e) => return format!("Error: Couldn't read {}: {}", args[0], e),
+        };
+
+        // Show the transaction, so it can be checked before it is signed
+        if args.len() == 1 {
+            return utx.to_json(lightclient.config.hrp_sapling_address()).pretty(2);
+        }
+        if args[2] != utx.id() {
+            let e = format!(
+                "{} is not the id of this transaction. Check it with 'signtx {}'",
+                args[2], args[0]
+            );
+            return object! { "error" => e }.pretty(2);
+        }
+
+        RT.block_on(async move {
+            let result = match lightclient.do_sign_unsigned_tx(&utx).await {
+                Ok((txid, raw_tx)) => std::fs::write(args[1], &raw_tx)
+                    .map(|_| txid)
+                    .map_err(|e| format!("Couldn't write {}: {}", args[1], e)),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(txid) => object! { "txid" => txid },
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct BroadcastSignedTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for BroadcastSignedTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Broadcast a transaction signed offline with 'signtx'");
+        h.push("Usage:");
+        h.push("broadcastsignedtx <signed file>");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Broadcast a transaction signed offline".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let raw_tx = match std::fs::read(args[0]) {
+            Ok(raw_tx) => raw_tx,
+            Err(e) => return format!("Error: Couldn't read {}: {}", args[0], e),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_broadcast_signed_tx(raw_tx).await {
+                Ok(txid) => object! { "txid" => txid },
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct TPubKeyCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for TPubKeyCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Show the public key of one of this wallet's t addresses, to give to the co-signers of a multisig address");
+        h.push("Usage:");
+        h.push("tpubkey [t address]");
+        h.push("");
+        h.push("Without an address, the key of the first t address is shown.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Show the public key of a t address".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() > 1 {
+            return Command::<P>::help(self);
+        }
+
+        let address = args.first().map(|a| a.to_string());
+        RT.block_on(async move {
+            match lightclient.do_tpubkey(address).await {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct NewMultisigCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for NewMultisigCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Add a M-of-N multisig t address, made from the public keys of the co-signers");
+        h.push("Usage:");
+        h.push("newmultisig <signatures needed> <pubkey> <pubkey> ...");
+        h.push("");
+        h.push("Every co-signer has to give the keys in the same order to get the same address. Get this wallet's key");
+        h.push("with 'tpubkey'. The wallet tracks the funds of the address, but they are not part of the balance since they");
+        h.push("need the co-signers to be spent. Rescan to find funds that were sent to it before it was added.");
+        h.push("");
+        h.push("Example:");
+        h.push("newmultisig 2 02a1...c3 03b2...d4 02c3...e5");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Add a multisig t address".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() < 2 {
+            return Command::<P>::help(self);
+        }
+
+        let threshold = match args[0].parse::<u8>() {
+            Ok(t) => t,
+            Err(_) => return format!("Error: Couldn't understand the number of signatures {}", args[0]),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_new_multisig_address(threshold, args[1..].to_vec()).await {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+fn read_multisig_tx<P: consensus::Parameters + Send + Sync + 'static>(
+    file: &str,
+    lightclient: &LightClient<P>,
+) -> Result<PartiallySignedTx, String> {
+    std::fs::read(file)
+        .and_then(|buf| PartiallySignedTx::read(&buf[..], &lightclient.config))
+        .map_err(|e| format!("Couldn't read {}: {}", file, e))
+}
+
+fn write_multisig_tx(file: &str, pstx: &PartiallySignedTx) -> Result<(), String> {
+    let mut buf = vec![];
+    pstx.write(&mut buf)
+        .and_then(|_| std::fs::write(file, &buf))
+        .map_err(|e| format!("Couldn't write {}: {}", file, e))
+}
+
+struct CreateMultisigTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for CreateMultisigTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Create a transaction spending the funds of a multisig address, for the co-signers to sign");
+        h.push("Usage:");
+        h.push("createmultisigtx <file> <multisig address> <t address> <amount in zatoshis>");
+        h.push("OR");
+        h.push("createmultisigtx <file> <multisig address> '{'tos': [{'address': <t address>, 'amount': <amount in zatoshis>}, ...], 'fee': <optional fee in zatoshis>}'");
+        h.push("");
+        h.push("The transaction is written to <file>. Every co-signer signs a copy with 'signmultisigtx', and the copies");
+        h.push("are combined with 'mergemultisigtx'. Once it has enough signatures, send it with 'broadcastmultisigtx'.");
+        h.push("Multisig funds can only be sent to t addresses. The change goes back to the multisig address.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Create a transaction spending multisig funds".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let (tos, fee) = match args.len() {
+            4 => match args[3].parse::<u64>() {
+                Ok(amount) => (vec![(args[2].to_string(), amount)], None),
+                Err(_) => return format!("Error: Couldn't parse amount {}", args[3]),
+            },
+            3 => {
+                let j = match json::parse(args[2]) {
+                    Ok(j) => j,
+                    Err(e) => return format!("Error: Couldn't understand JSON: {}", e),
+                };
+
+                let tos = j["tos"]
+                    .members()
+                    .map(|to| match (to["address"].as_str(), to["amount"].as_u64()) {
+                        (Some(address), Some(amount)) => Ok((address.to_string(), amount)),
+                        _ => Err(format!("Every output needs an 'address' and an 'amount': {}", to)),
+                    })
+                    .collect::<Result<Vec<_>, String>>();
+                match tos {
+                    Ok(tos) => (tos, j["fee"].as_u64()),
+                    Err(e) => return format!("Error: {}", e),
+                }
+            }
+            _ => return Command::<P>::help(self),
+        };
+
+        RT.block_on(async move {
+            let tos = tos.iter().map(|(a, v)| (a.as_str(), *v)).collect::<Vec<_>>();
+            let result = match lightclient.do_create_multisig_tx(args[1], tos, fee).await {
+                Ok(pstx) => write_multisig_tx(args[0], &pstx).map(|_| pstx.to_json()),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct SignMultisigTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SignMultisigTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Sign a multisig transaction created with 'createmultisigtx', with this wallet's keys of the multisig address");
+        h.push("Usage:");
+        h.push("signmultisigtx <file>");
+        h.push("");
+        h.push("The signatures are added to <file>. Check the outputs and fee that are shown before passing it on.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Sign a multisig transaction".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let mut pstx = match read_multisig_tx(args[0], lightclient) {
+            Ok(pstx) => pstx,
+            Err(e) => return format!("Error: {}", e),
+        };
+
+        RT.block_on(async move {
+            let result = match lightclient.do_sign_multisig_tx(&mut pstx).await {
+                Ok(_) => write_multisig_tx(args[0], &pstx).map(|_| pstx.to_json()),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct MergeMultisigTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for MergeMultisigTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Combine the signatures of the co-signers' copies of a multisig transaction");
+        h.push("Usage:");
+        h.push("mergemultisigtx <file> <co-signer file> [<co-signer file> ...]");
+        h.push("");
+        h.push("The signatures in the co-signer files are checked, and added to <file>.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Combine the signatures of a multisig transaction".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() < 2 {
+            return Command::<P>::help(self);
+        }
+
+        let result = read_multisig_tx(args[0], lightclient).and_then(|mut pstx| {
+            for file in args[1..].iter() {
+                let other = read_multisig_tx(file, lightclient)?;
+                lightclient
+                    .do_merge_multisig_tx(&mut pstx, &other)
+                    .map_err(|e| format!("{}: {}", file, e))?;
+            }
+            write_multisig_tx(args[0], &pstx).map(|_| pstx.to_json())
+        });
+
+        match result {
+            Ok(j) => j,
+            Err(e) => object! { "error" => e },
+        }
+        .pretty(2)
+    }
+}
+
+struct BroadcastMultisigTxCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for BroadcastMultisigTxCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Broadcast a multisig transaction that has been signed by enough co-signers");
+        h.push("Usage:");
+        h.push("broadcastmultisigtx <file>");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Broadcast a multisig transaction".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 1 {
+            return Command::<P>::help(self);
+        }
+
+        let pstx = match read_multisig_tx(args[0], lightclient) {
+            Ok(pstx) => pstx,
+            Err(e) => return format!("Error: {}", e),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_broadcast_multisig_tx(&pstx).await {
+                Ok(txid) => object! { "txid" => txid },
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct SetSignerCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SetSignerCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Have outgoing transactions signed by a signer listening on a local socket, instead of the keys in");
+        h.push("this wallet. The signer can be another wallet running 'runsigner', or a bridge to a hardware signer.");
+        h.push("Usage:");
+        h.push("setsigner <address:port> <token>");
+        h.push("OR");
+        h.push("setsigner local");
+        h.push("");
+        h.push("<token> is the secret shared with the signer, at least 16 characters. The signer only signs requests that");
+        h.push("carry it. 'local' goes back to signing with the keys in this wallet. The signer isn't saved in the wallet file.");
+        h.push("");
+        h.push("Example:");
+        h.push("setsigner 127.0.0.1:9068 1f0e7c2b9a3d46e58c71");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Set the signer for outgoing transactions".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let remote = match args {
+            ["local"] => None,
+            [address, token] => Some((*address, *token)),
+            _ => return Command::<P>::help(self),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_set_remote_signer(remote).await {
+                Ok(_) => object! { "result" => "success" },
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct RunSignerCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for RunSignerCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Sign transactions for wallets on this machine that use this wallet as their signer with 'setsigner'.");
+        h.push("The signer runs in the background until it is stopped or the socket fails.");
+        h.push("Usage:");
+        h.push("runsigner <address:port> <token>");
+        h.push("OR");
+        h.push("runsigner stop");
+        h.push("");
+        h.push("Only requests that carry <token>, a secret of at least 16 characters shared with the other wallets, are");
+        h.push("considered. Every request waits until it is approved or rejected with 'signrequests'.");
+        h.push("");
+        h.push("Example:");
+        h.push("runsigner 127.0.0.1:9068 1f0e7c2b9a3d46e58c71");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Sign transactions for other wallets on this machine".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        match args {
+            ["stop"] => match lightclient.do_stop_signer() {
+                true => object! { "result" => "success" },
+                false => object! { "error" => "The signer isn't running" },
+            }
+            .pretty(2),
+            [address, token] => RT.block_on(async move {
+                match lightclient.do_run_signer(address, token).await {
+                    Ok(_) => object! { "result" => "success" },
+                    Err(e) => object! { "error" => e },
+                }
+                .pretty(2)
+            }),
+            _ => Command::<P>::help(self),
+        }
+    }
+}
+
+struct SignRequestsCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SignRequestsCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("List the signing requests waiting for approval by the signer started with 'runsigner', or answer one");
+        h.push("Usage:");
+        h.push("signrequests");
+        h.push("signrequests approve <id>");
+        h.push("signrequests reject <id>");
+        h.push("");
+        h.push("Check the outputs, fee and change of a request before approving it. Requests that aren't answered within");
+        h.push("10 minutes are rejected.");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "List or answer the requests waiting for the signer".to_string()
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let result = match args {
+            [] => lightclient.do_signing_requests(),
+            [answer, id] if *answer == "approve" || *answer == "reject" => lightclient
+                .do_answer_signing_request(id, *answer == "approve")
+                .map(|_| object! { "result" => "success" }),
+            _ => return Command::<P>::help(self),
+        };
+
+        match result {
+            Ok(j) => j,
+            Err(e) => object! { "error" => e },
+        }
+        .pretty(2)
+    }
+}
+
+struct FreezeCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for FreezeCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Freeze sapling notes or utxos, so they are never spent, shielded or counted in the spendable balance");
+        h.push("Usage:");
+        h.push("freeze note <txid> <output_index>");
+        h.push("freeze utxo <txid> <output_index>");
+        h.push("OR");
+        h.push("freeze '{'notes': [{'txid': <txid>, 'output_index': <index>}, ...], 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...]}'");
+        h.push("");
+        h.push("The txid and output_index of each note and utxo are shown by 'notes'. Use 'unfreeze' to spend them again.");
+        h.push("Example:");
+        h.push("freeze utxo 1c52cc5b3e5a5e2c2b6fa0c1f6e3a4d0bf4b0d1e2c3a4b5c6d7e8f9a0b1c2d3e 0");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Keep notes or utxos from being spent".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let selection = match parse_freeze_args(args) {
+            Ok(selection) => selection,
+            Err(e) => return format!("Error: {}\n{}", e, Command::<P>::help(self)),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_freeze(selection, true).await {
+                Ok(_) => object! { "result" => "success" },
+                Err(e) => object! {
+                    "result" => "error",
+                    "error" => e
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct UnfreezeCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for UnfreezeCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Unfreeze sapling notes or utxos that were frozen with 'freeze', so they can be spent again");
+        h.push("Usage:");
+        h.push("unfreeze note <txid> <output_index>");
+        h.push("unfreeze utxo <txid> <output_index>");
+        h.push("OR");
+        h.push("unfreeze '{'notes': [{'txid': <txid>, 'output_index': <index>}, ...], 'utxos': [{'txid': <txid>, 'output_index': <index>}, ...]}'");
+        h.push("");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Allow frozen notes or utxos to be spent again".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        let selection = match parse_freeze_args(args) {
+            Ok(selection) => selection,
+            Err(e) => return format!("Error: {}\n{}", e, Command::<P>::help(self)),
+        };
+
+        RT.block_on(async move {
+            match lightclient.do_freeze(selection, false).await {
+                Ok(_) => object! { "result" => "success" },
+                Err(e) => object! {
+                    "result" => "error",
+                    "error" => e
+                },
             }
             .pretty(2)
         })
     }
 }
 
+struct SaveCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SaveCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Save the wallet to disk");
+        h.push("Usage:");
+        h.push("save");
+        h.push("");
+        h.push("The wallet is saved to disk. The wallet is periodically saved to disk (and also saved upon exit)");
+        h.push("but you can use this command to explicitly save it to disk");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Save wallet file to disk".to_string()
+    }
+    fn exec(&self, _args: &[&str], lightclient: &LightClient<P>) -> String {
+        RT.block_on(async move {
+            match lightclient.do_save(true).await {
+                Ok(_) => {
+                    let r = object! { "result" => "success" };
+                    r.pretty(2)
+                }
+                Err(e) => {
+                    let r = object! {
+                        "result" => "error",
+                        "error" => e
+                    };
+                    r.pretty(2)
+                }
+            }
+        })
+    }
+}
+
+struct SeedCommand {}
+
+impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SeedCommand {
+    fn help(&self) -> String {
+        let mut h = vec![];
+        h.push("Show the wallet's seed phrase");
+        h.push("Usage:");
+        h.push("seed [threshold count]");
+        h.push("");
+        h.push("Your wallet is entirely recoverable from the seed phrase. Please save it carefully and don't share it with anyone");
+        h.push("If you pass a threshold and a count, the seed is instead split into 'count' SLIP-39 shares, any");
+        h.push("'threshold' of which can restore the wallet. Store each share in a different place.");
+        h.push("");
+        h.push("Example:");
+        h.push("seed 2 3");
+
+        h.join("\n")
+    }
+
+    fn short_help(&self) -> String {
+        "Display the seed phrase".to_string()
+    }
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
+        if args.len() != 0 && args.len() != 2 {
+            return Command::<P>::help(self);
+        }
+
+        let shares = if args.len() == 2 {
+            match (args[0].parse::<u8>(), args[1].parse::<u8>()) {
+                (Ok(threshold), Ok(count)) => Some((threshold, count)),
+                _ => return format!("Couldn't parse threshold and count\n\n{}", Command::<P>::help(self)),
+            }
+        } else {
+            None
+        };
+
+        RT.block_on(async move {
+            let r = match shares {
+                Some((threshold, count)) => lightclient.do_seed_shares(threshold, count).await,
+                None => lightclient.do_seed_phrase().await.map_err(|e| e.to_string()),
+            };
+
+            match r {
+                Ok(j) => j.pretty(2),
+                Err(e) => object! { "error" => e }.pretty(2),
+            }
+        })
+    }
+}
+
 struct TransactionsCommand {}
 
 impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for TransactionsCommand {
@@ -939,6 +2322,16 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SetOptionC
         h.push("setoption <optionname>=<optionvalue>");
         h.push("List of available options:");
         h.push("download_memos : none | wallet | all");
+        h.push("spam_filter_threshold : <number of outputs>, or -1 to disable");
+        h.push("auto_lock_timeout : <seconds without a send before an unlocked wallet is locked>, or 0 to disable");
+        h.push("fee_policy : default | fixed:<zats> | zip317 | zip317:<zats per logical action>");
+        h.push("offline_signing : true | false. Keep witnesses for viewing key notes, to create unsigned transactions. Needs a rescan");
+        h.push("privacy_policy : fullyshielded | allowshielding | allowdeshielding | allowrevealedamounts. What sends may reveal on chain");
+        h.push("auto_shield_threshold : <zats>. Shield the confirmed transparent balance after a sync once it reaches this, or 0 to disable");
+        h.push("auto_shield_utxos : <count>. Shield the confirmed transparent balance after a sync once there are this many utxos, or 0 to disable");
+        h.push("auto_shield_address : <z-address> to auto-shield to, or 'default' for the wallet's first z-address");
+        h.push("block_cache_size : <megabytes> of compact blocks to keep in the data directory, so rescans don't download them again, or 0 to disable");
+        h.push("sync_order : oldestfirst | recentfirst | recentfirst:<blocks>. Scan the newest blocks first, so their funds can be spent sooner, then backfill the older ones");
 
         h.join("\n")
     }
@@ -978,6 +2371,48 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for SetOptionC
                     let threshold = option_value.parse::<i64>().unwrap();
                     lightclient.wallet.set_spam_filter_threshold(threshold).await
                 }
+                "auto_lock_timeout" => match option_value.parse::<u64>() {
+                    Ok(timeout) => lightclient.wallet.set_auto_lock_timeout(timeout).await,
+                    Err(_) => return format!("Error: Couldn't understand {} value {}", option_name, option_value),
+                },
+                "fee_policy" => match FeePolicy::parse(option_value) {
+                    Ok(policy) => lightclient.wallet.set_fee_policy(policy).await,
+                    Err(e) => return format!("Error: {}", e),
+                },
+                "offline_signing" => match option_value.parse::<bool>() {
+                    Ok(value) => lightclient.wallet.set_offline_signing(value).await,
+                    Err(_) => return format!("Error: Couldn't understand {} value {}", option_name, option_value),
+                },
+                "privacy_policy" => match PrivacyPolicy::parse(option_value) {
+                    Ok(policy) => lightclient.wallet.set_privacy_policy(policy).await,
+                    Err(e) => return format!("Error: {}", e),
+                },
+                "auto_shield_threshold" => match option_value.parse::<u64>() {
+                    Ok(value) => lightclient.wallet.set_auto_shield_threshold(value).await,
+                    Err(_) => return format!("Error: Couldn't understand {} value {}", option_name, option_value),
+                },
+                "auto_shield_utxos" => match option_value.parse::<u64>() {
+                    Ok(value) => lightclient.wallet.set_auto_shield_utxos(value).await,
+                    Err(_) => return format!("Error: Couldn't understand {} value {}", option_name, option_value),
+                },
+                "auto_shield_address" => match option_value {
+                    "default" => lightclient.wallet.set_auto_shield_address(None).await,
+                    _ if Keys::is_shielded_address(&option_value.to_string(), &lightclient.config) => {
+                        lightclient
+                            .wallet
+                            .set_auto_shield_address(Some(option_value.to_string()))
+                            .await
+                    }
+                    _ => return format!("Error: {} is not a shielded address", option_value),
+                },
+                "block_cache_size" => match option_value.parse::<u64>() {
+                    Ok(value) => lightclient.wallet.set_block_cache_size(value).await,
+                    Err(_) => return format!("Error: Couldn't understand {} value {}", option_name, option_value),
+                },
+                "sync_order" => match SyncOrder::parse(option_value) {
+                    Ok(order) => lightclient.wallet.set_sync_order(order).await,
+                    Err(e) => return format!("Error: {}", e),
+                },
                 _ => return format!("Error: Couldn't understand {}", option_name),
             }
 
@@ -1027,6 +2462,52 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for GetOptionC
                     .await
                     .spam_threshold
                     .to_string(),
+                "auto_lock_timeout" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .auto_lock_timeout
+                    .to_string(),
+                "fee_policy" => lightclient.wallet.fee_policy().await.to_string(),
+                "offline_signing" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .offline_signing
+                    .to_string(),
+                "privacy_policy" => lightclient.wallet.privacy_policy().await.to_string(),
+                "auto_shield_threshold" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .auto_shield_threshold
+                    .to_string(),
+                "auto_shield_utxos" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .auto_shield_utxos
+                    .to_string(),
+                "auto_shield_address" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .auto_shield_address
+                    .clone()
+                    .unwrap_or("default".to_string()),
+                "block_cache_size" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .block_cache_size
+                    .to_string(),
+                "sync_order" => lightclient.wallet.wallet_options.read().await.sync_order.to_string(),
                 _ => return format!("Error: Couldn't understand {}", option_name),
             };
 
@@ -1176,6 +2657,9 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for DefaultFee
         h.push("Usage:");
         h.push("defaultfee <optional_block_height>");
         h.push("");
+        h.push("The fee is for a simple transaction spending one note, under the wallet's fee policy.");
+        h.push("Larger transactions may pay more. Use 'setoption fee_policy=...' to change the policy.");
+        h.push("");
         h.push("Example:");
         h.push("defaultfee");
         h.join("\n")
@@ -1184,13 +2668,18 @@ impl<P: consensus::Parameters + Send + Sync + 'static> Command<P> for DefaultFee
     fn short_help(&self) -> String {
         "Returns the default fee in zats for outgoing transactions".to_string()
     }
-    fn exec(&self, args: &[&str], _client: &LightClient<P>) -> String {
+    fn exec(&self, args: &[&str], lightclient: &LightClient<P>) -> String {
         if args.len() > 1 {
             return format!("Was expecting at most 1 argument\n{}", Command::<P>::help(self));
         }
 
         RT.block_on(async move {
-            let j = object! { "defaultfee" => u64::from(DEFAULT_FEE)};
+            // One sapling spend, paying one recipient and change
+            let policy = lightclient.wallet.fee_policy().await;
+            let j = object! {
+                "defaultfee" => policy.fee(0, 0, 1, 2),
+                "fee_policy" => policy.to_string(),
+            };
             j.pretty(2)
         })
     }
@@ -1299,6 +2788,9 @@ pub fn get_commands<P: consensus::Parameters + Send + Sync + 'static>() -> Box<H
 
     map.insert("sync".to_string(), Box::new(SyncCommand {}));
     map.insert("syncstatus".to_string(), Box::new(SyncStatusCommand {}));
+    map.insert("cancelsync".to_string(), Box::new(CancelSyncCommand {}));
+    map.insert("pausesync".to_string(), Box::new(PauseSyncCommand {}));
+    map.insert("resumesync".to_string(), Box::new(ResumeSyncCommand {}));
     map.insert("encryptionstatus".to_string(), Box::new(EncryptionStatusCommand {}));
     map.insert("encryptmessage".to_string(), Box::new(EncryptMessageCommand {}));
     map.insert("decryptmessage".to_string(), Box::new(DecryptMessageCommand {}));
@@ -1317,7 +2809,26 @@ pub fn get_commands<P: consensus::Parameters + Send + Sync + 'static>() -> Box<H
     map.insert("info".to_string(), Box::new(InfoCommand {}));
     map.insert("zecprice".to_string(), Box::new(ZecPriceCommand {}));
     map.insert("send".to_string(), Box::new(SendCommand {}));
+    map.insert("sendmax".to_string(), Box::new(SendMaxCommand {}));
     map.insert("shield".to_string(), Box::new(ShieldCommand {}));
+    map.insert("consolidate".to_string(), Box::new(ConsolidateCommand {}));
+    map.insert("propose".to_string(), Box::new(ProposeCommand {}));
+    map.insert("confirmproposal".to_string(), Box::new(ConfirmProposalCommand {}));
+    map.insert("discardproposal".to_string(), Box::new(DiscardProposalCommand {}));
+    map.insert("createunsignedtx".to_string(), Box::new(CreateUnsignedTxCommand {}));
+    map.insert("signtx".to_string(), Box::new(SignTxCommand {}));
+    map.insert("broadcastsignedtx".to_string(), Box::new(BroadcastSignedTxCommand {}));
+    map.insert("tpubkey".to_string(), Box::new(TPubKeyCommand {}));
+    map.insert("newmultisig".to_string(), Box::new(NewMultisigCommand {}));
+    map.insert("createmultisigtx".to_string(), Box::new(CreateMultisigTxCommand {}));
+    map.insert("signmultisigtx".to_string(), Box::new(SignMultisigTxCommand {}));
+    map.insert("mergemultisigtx".to_string(), Box::new(MergeMultisigTxCommand {}));
+    map.insert("broadcastmultisigtx".to_string(), Box::new(BroadcastMultisigTxCommand {}));
+    map.insert("setsigner".to_string(), Box::new(SetSignerCommand {}));
+    map.insert("runsigner".to_string(), Box::new(RunSignerCommand {}));
+    map.insert("signrequests".to_string(), Box::new(SignRequestsCommand {}));
+    map.insert("freeze".to_string(), Box::new(FreezeCommand {}));
+    map.insert("unfreeze".to_string(), Box::new(UnfreezeCommand {}));
     map.insert("save".to_string(), Box::new(SaveCommand {}));
     map.insert("quit".to_string(), Box::new(QuitCommand {}));
     map.insert("list".to_string(), Box::new(TransactionsCommand {}));
@@ -1327,6 +2838,9 @@ pub fn get_commands<P: consensus::Parameters + Send + Sync + 'static>() -> Box<H
     map.insert("seed".to_string(), Box::new(SeedCommand {}));
     map.insert("encrypt".to_string(), Box::new(EncryptCommand {}));
     map.insert("decrypt".to_string(), Box::new(DecryptCommand {}));
+    map.insert("changepassword".to_string(), Box::new(ChangePasswordCommand {}));
+    map.insert("encryptfile".to_string(), Box::new(EncryptFileCommand {}));
+    map.insert("decryptfile".to_string(), Box::new(DecryptFileCommand {}));
     map.insert("unlock".to_string(), Box::new(UnlockCommand {}));
     map.insert("lock".to_string(), Box::new(LockCommand {}));
 
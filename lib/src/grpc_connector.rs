@@ -12,7 +12,7 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use log::{warn, info, error};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio::sync::mpsc::{Sender, UnboundedReceiver};
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 
@@ -173,8 +173,7 @@ impl GrpcConnector {
         start_height: u64,
         end_height: u64,
         spam_filter_threshold: i64,
-        receivers: &[Sender<CompactBlock>; 2],
-    ) -> Result<(), String> {
+    ) -> Result<Vec<CompactBlock>, String> {
         let mut client = self.get_client().await.map_err(|e| format!("{}", e))?;
 
         let bs = BlockId {
@@ -198,24 +197,17 @@ impl GrpcConnector {
             .map_err(|e| format!("{}", e))?
             .into_inner();
 
-        // First download all blocks and save them locally, so we don't timeout
-        let mut block_cache = Vec::new();
+        // Download all blocks before returning them, so we don't timeout
+        let mut blocks = Vec::new();
 
         while let Some(block) = response.message().await.map_err(|e| {
             // println!("first error");
             format!("{}", e)
         })? {
-            block_cache.push(block);
-        }
-
-        // Send all the blocks to the recievers
-        for block in block_cache {
-            //println!("grpc connector Sent {}", block.height);
-            receivers[0].send(block.clone()).await.map_err(|e| format!("{}", e))?;
-            receivers[1].send(block).await.map_err(|e| format!("{}", e))?;
+            blocks.push(block);
         }
 
-        Ok(())
+        Ok(blocks)
     }
 
     async fn get_full_tx<P: consensus::Parameters + Send + Sync + 'static>(
@@ -1,14 +1,33 @@
 use self::lightclient_config::LightClientConfig;
 use crate::{
     blaze::{
-        block_witness_data::BlockAndWitnessData, fetch_compact_blocks::FetchCompactBlocks,
-        fetch_full_tx::FetchFullTxns, fetch_taddr_txns::FetchTaddrTxns, sync_status::SyncStatus,
-        syncdata::BlazeSyncData, trial_decryptions::TrialDecryptions, update_notes::UpdateNotes,
+        block_cache::{CompactBlockCache, CACHE_MIN_DEPTH},
+        block_witness_data::BlockAndWitnessData,
+        fetch_compact_blocks::FetchCompactBlocks,
+        fetch_full_tx::FetchFullTxns,
+        fetch_taddr_txns::FetchTaddrTxns,
+        sync_status::{SyncControl, SyncStatus},
+        syncdata::BlazeSyncData,
+        trial_decryptions::TrialDecryptions,
+        update_notes::UpdateNotes,
     },
     compact_formats::RawTransaction,
     grpc_connector::GrpcConnector,
     lightclient::lightclient_config::MAX_REORG,
-    lightwallet::{self, data::WalletTx, message::Message, now, LightWallet, MAX_CHECKPOINTS, MERKLE_DEPTH},
+    lightwallet::{
+        self,
+        data::{CoinSelection, SendFrom, SendMaxSource, WalletTx},
+        events::WalletEvent,
+        keys::Keys,
+        message::Message,
+        now,
+        multisig::{MultisigAddress, PartiallySignedTx},
+        offline::UnsignedTx,
+        secret::SecretJson,
+        signer::{self, KeysSigner, PendingApprovals, RunningSigner, Signer, SocketSigner},
+        sync_order::Backfill,
+        LightWallet, MAX_CHECKPOINTS, MERKLE_DEPTH,
+    },
 };
 use futures::{stream::FuturesUnordered, StreamExt};
 use incrementalmerkletree::bridgetree::BridgeTree;
@@ -20,14 +39,19 @@ use std::{
     collections::HashSet,
     fs::File,
     io::{self, BufReader, Error, ErrorKind, Read, Write},
+    net::SocketAddr,
     path::Path,
     sync::Arc,
     time::Duration,
 };
 use tokio::{
     join,
+    net::TcpListener,
     runtime::Runtime,
-    sync::{mpsc::unbounded_channel, oneshot, Mutex, RwLock},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        oneshot, Mutex, RwLock,
+    },
     task::yield_now,
     time::sleep,
 };
@@ -37,8 +61,9 @@ use zcash_primitives::{
     consensus::{self, BlockHeight, BranchId},
     memo::{Memo, MemoBytes},
     merkle_tree::CommitmentTree,
-    transaction::{components::amount::DEFAULT_FEE, Transaction, TxId},
+    transaction::{Transaction, TxId},
 };
+use zcash_primitives::sapling::prover::TxProver;
 use zcash_proofs::prover::LocalTxProver;
 
 pub(crate) mod checkpoints;
@@ -61,13 +86,109 @@ impl WalletStatus {
     }
 }
 
+/// How long an encrypted wallet stays unlocked after `unlock`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnlockDuration {
+    // Stay unlocked until explicitly locked, or until the idle timeout kicks in. Without an idle timeout, the next
+    // save locks the wallet.
+    UntilLocked,
+    // Lock again this many seconds after unlocking
+    Seconds(u64),
+    // Lock again as soon as the next send or shield finishes
+    OneSend,
+}
+
+#[derive(Clone, Debug)]
+pub struct AutoLockState {
+    pub duration: UnlockDuration,
+    pub unlocked_at: u64,
+    pub last_activity: u64,
+}
+
+impl AutoLockState {
+    pub fn new() -> Self {
+        AutoLockState {
+            duration: UnlockDuration::UntilLocked,
+            unlocked_at: now(),
+            last_activity: now(),
+        }
+    }
+
+    /// The time at which an unlocked wallet should be locked again, if there is one
+    pub fn lock_at(&self, idle_timeout: u64) -> Option<u64> {
+        let idle = if idle_timeout > 0 {
+            Some(self.last_activity + idle_timeout)
+        } else {
+            None
+        };
+
+        let timed = match self.duration {
+            UnlockDuration::Seconds(s) => Some(self.unlocked_at + s),
+            _ => None,
+        };
+
+        match (idle, timed) {
+            (Some(a), Some(b)) => Some(cmp::min(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Lock the wallet's `keys` if they have been unlocked for longer than `state` allows. Returns true if they were
+    /// locked.
+    pub async fn lock_if_expired<P>(state: &RwLock<AutoLockState>, keys: &RwLock<Keys<P>>, idle_timeout: u64) -> bool {
+        {
+            let keys = keys.read().await;
+            if !keys.is_encrypted() || !keys.is_unlocked_for_spending() {
+                return false;
+            }
+        }
+
+        match state.read().await.lock_at(idle_timeout) {
+            Some(t) if t <= now() => {}
+            _ => return false,
+        };
+
+        match keys.write().await.lock() {
+            Ok(_) => {
+                info!("Auto-locked wallet");
+                true
+            }
+            Err(e) => {
+                warn!("Couldn't auto-lock wallet: {}", e);
+                false
+            }
+        }
+    }
+}
+
+// The blocks a sync batch scans, and where the wallet keeps what it learns from them
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SyncPass {
+    // The blocks after the wallet's
+    InOrder,
+
+    // The newest blocks of a recent-first sync, from `first` on. The wallet goes on from them, and the older blocks
+    // it skipped are saved as a backfill.
+    Recent { first: u64 },
+
+    // The older blocks a recent-first sync skipped, after the backfill's own blocks
+    Backfill,
+}
+
 pub struct LightClient<P> {
     pub(crate) config: LightClientConfig<P>,
     pub(crate) wallet: LightWallet<P>,
 
     mempool_monitor: std::sync::RwLock<Option<std::thread::JoinHandle<()>>>,
+    auto_lock_monitor: std::sync::RwLock<Option<std::thread::JoinHandle<()>>>,
+
+    auto_lock: Arc<RwLock<AutoLockState>>,
 
     sync_lock: Mutex<()>,
+    sync_control: RwLock<SyncControl>,
+
+    // The signer this wallet runs for other wallets on this machine, if it was started
+    signer: std::sync::Mutex<Option<RunningSigner>>,
 
     bsync_data: Arc<RwLock<BlazeSyncData>>,
 }
@@ -84,11 +205,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         }
 
         let l = LightClient {
-            wallet: LightWallet::new(config.clone(), seed_phrase, height, 1, 1)?,
+            wallet: LightWallet::new(config.clone(), seed_phrase, "".to_string(), height, 1, 1)?,
             config: config.clone(),
             mempool_monitor: std::sync::RwLock::new(None),
+            auto_lock_monitor: std::sync::RwLock::new(None),
+            auto_lock: Arc::new(RwLock::new(AutoLockState::new())),
             bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
             sync_lock: Mutex::new(()),
+            sync_control: RwLock::new(SyncControl::default()),
+            signer: std::sync::Mutex::new(None),
         };
 
         l.set_wallet_initial_state(height).await;
@@ -214,20 +339,26 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
 
     fn new_wallet(
         config: &LightClientConfig<P>,
+        passphrase: String,
         latest_block: u64,
         num_zaddrs: u32,
         num_oaddrs: u32,
     ) -> io::Result<Self> {
         Runtime::new().unwrap().block_on(async move {
             let l = LightClient {
-                wallet: LightWallet::new(config.clone(), None, latest_block, num_zaddrs, num_oaddrs)?,
+                wallet: LightWallet::new(config.clone(), None, passphrase, latest_block, num_zaddrs, num_oaddrs)?,
                 config: config.clone(),
                 mempool_monitor: std::sync::RwLock::new(None),
+                auto_lock_monitor: std::sync::RwLock::new(None),
+                auto_lock: Arc::new(RwLock::new(AutoLockState::new())),
                 sync_lock: Mutex::new(()),
+                sync_control: RwLock::new(SyncControl::default()),
+                signer: std::sync::Mutex::new(None),
                 bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
             };
 
             l.set_wallet_initial_state(latest_block).await;
+            l.start_auto_lock_monitor();
 
             info!("Created new wallet with a new seed!");
             info!("Created LightClient to {}", &config.server);
@@ -244,6 +375,16 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
     /// Create a brand new wallet with a new seed phrase. Will fail if a wallet file
     /// already exists on disk
     pub fn new(config: &LightClientConfig<P>, latest_block: u64) -> io::Result<Self> {
+        Self::new_with_passphrase(config, latest_block, "".to_string())
+    }
+
+    /// Create a brand new wallet with a new seed phrase, which is used together with the BIP39 `passphrase`.
+    /// Will fail if a wallet file already exists on disk
+    pub fn new_with_passphrase(
+        config: &LightClientConfig<P>,
+        latest_block: u64,
+        passphrase: String,
+    ) -> io::Result<Self> {
         #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
         {
             if config.wallet_exists() {
@@ -254,7 +395,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             }
         }
 
-        Self::new_wallet(config, latest_block, 1, 1)
+        Self::new_wallet(config, passphrase, latest_block, 1, 1)
     }
 
     pub fn new_from_phrase(
@@ -262,6 +403,32 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         config: &LightClientConfig<P>,
         birthday: u64,
         overwrite: bool,
+    ) -> io::Result<Self> {
+        Self::new_from_phrase_with_passphrase(seed_phrase, "".to_string(), config, birthday, overwrite)
+    }
+
+    /// Restore a wallet from a set of SLIP-39 shares of its seed, as exported by `do_seed_shares`
+    pub fn new_from_shares(
+        shares: Vec<String>,
+        passphrase: String,
+        config: &LightClientConfig<P>,
+        birthday: u64,
+        overwrite: bool,
+    ) -> io::Result<Self> {
+        let seed_phrase =
+            lightwallet::slip39::combine_shares(&shares).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        Self::new_from_phrase_with_passphrase(seed_phrase.to_string(), passphrase, config, birthday, overwrite)
+    }
+
+    /// Restore a wallet from a seed phrase that was used together with a BIP39 `passphrase`. The passphrase
+    /// is ignored when restoring from a spending or viewing key.
+    pub fn new_from_phrase_with_passphrase(
+        seed_phrase: String,
+        passphrase: String,
+        config: &LightClientConfig<P>,
+        birthday: u64,
+        overwrite: bool,
     ) -> io::Result<Self> {
         #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
         {
@@ -276,7 +443,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         let lr = if seed_phrase.starts_with(config.hrp_sapling_private_key())
             || seed_phrase.starts_with(config.hrp_sapling_viewing_key())
         {
-            let lc = Self::new_wallet(config, birthday, 0, 0)?;
+            let lc = Self::new_wallet(config, "".to_string(), birthday, 0, 0)?;
             Runtime::new().unwrap().block_on(async move {
                 lc.do_import_key(seed_phrase, birthday)
                     .await
@@ -289,14 +456,19 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         } else {
             Runtime::new().unwrap().block_on(async move {
                 let l = LightClient {
-                    wallet: LightWallet::new(config.clone(), Some(seed_phrase), birthday, 1, 1)?,
+                    wallet: LightWallet::new(config.clone(), Some(seed_phrase), passphrase, birthday, 1, 1)?,
                     config: config.clone(),
                     mempool_monitor: std::sync::RwLock::new(None),
+                    auto_lock_monitor: std::sync::RwLock::new(None),
+                    auto_lock: Arc::new(RwLock::new(AutoLockState::new())),
                     sync_lock: Mutex::new(()),
+                    sync_control: RwLock::new(SyncControl::default()),
+                    signer: std::sync::Mutex::new(None),
                     bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
                 };
 
                 l.set_wallet_initial_state(birthday).await;
+                l.start_auto_lock_monitor();
                 l.do_save(true)
                     .await
                     .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
@@ -320,10 +492,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                 wallet,
                 config: config.clone(),
                 mempool_monitor: std::sync::RwLock::new(None),
+                auto_lock_monitor: std::sync::RwLock::new(None),
+                auto_lock: Arc::new(RwLock::new(AutoLockState::new())),
                 sync_lock: Mutex::new(()),
+                sync_control: RwLock::new(SyncControl::default()),
+                signer: std::sync::Mutex::new(None),
                 bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
             };
 
+            lc.start_auto_lock_monitor();
             info!("Read wallet with birthday {}", lc.wallet.get_birthday().await);
             info!("Created LightClient to {}", &config.server);
 
@@ -352,10 +529,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                 wallet: wallet,
                 config: config.clone(),
                 mempool_monitor: std::sync::RwLock::new(None),
+                auto_lock_monitor: std::sync::RwLock::new(None),
+                auto_lock: Arc::new(RwLock::new(AutoLockState::new())),
                 sync_lock: Mutex::new(()),
+                sync_control: RwLock::new(SyncControl::default()),
+                signer: std::sync::Mutex::new(None),
                 bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
             };
 
+            lc.start_auto_lock_monitor();
             info!("Read wallet with birthday {}", lc.wallet.get_birthday().await);
             info!("Created LightClient to {}", &config.server);
 
@@ -437,10 +619,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         // Collect t addresses
         let t_addresses = self.wallet.keys().read().await.get_all_taddrs();
 
+        // Collect multisig addresses
+        let multisig_addresses = self.wallet.keys().read().await.get_all_multisig_addresses();
+
         object! {
             "ua_addresses" => uas,
             "z_addresses" => z_addresses,
             "t_addresses" => t_addresses,
+            "multisig_addresses" => multisig_addresses,
         }
     }
 
@@ -468,7 +654,8 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                 "zbalance" =>self.wallet.zbalance(Some(zaddress.clone())).await,
                 "verified_zbalance"  =>self.wallet.verified_zbalance(Some(zaddress.clone())).await,
                 "spendable_zbalance" =>self.wallet.spendable_zbalance(Some(zaddress.clone())).await,
-                "unverified_zbalance"   => self.wallet.unverified_zbalance(Some(zaddress.clone())).await
+                "unverified_zbalance"   => self.wallet.unverified_zbalance(Some(zaddress.clone())).await,
+                "frozen_zbalance"    => self.wallet.frozen_zbalance(Some(zaddress.clone())).await,
             });
         }
 
@@ -477,42 +664,66 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         for taddress in self.wallet.keys().read().await.get_all_taddrs() {
             // Get the balance for this address
             let balance = self.wallet.tbalance(Some(taddress.clone())).await;
+            let frozen_balance = self.wallet.frozen_tbalance(Some(taddress.clone())).await;
 
             t_addresses.push(object! {
                 "address" => taddress,
                 "balance" => balance,
+                "frozen_balance" => frozen_balance,
             });
         }
 
-        object! {
+        // Collect multisig addresses. Their funds aren't part of the tbalance, since they need the co-signers
+        let mut multisig_addresses = vec![];
+        let multisigs = self.wallet.keys().read().await.multisigs.clone();
+        for multisig in multisigs {
+            let balance = self
+                .wallet
+                .get_multisig_utxos(&multisig.address)
+                .await
+                .iter()
+                .map(|u| u.value)
+                .sum::<u64>();
+
+            multisig_addresses.push(object! {
+                "address" => multisig.address,
+                "threshold" => multisig.threshold,
+                "keys" => multisig.pubkeys.len(),
+                "balance" => balance,
+            });
+        }
+
+        let mut balance = object! {
             "uabalance" => self.wallet.uabalance(None).await,
             "zbalance"           => self.wallet.zbalance(None).await,
             "verified_zbalance"  => self.wallet.verified_zbalance(None).await,
             "spendable_zbalance" => self.wallet.spendable_zbalance(None).await,
             "unverified_zbalance"   => self.wallet.unverified_zbalance(None).await,
             "tbalance"           => self.wallet.tbalance(None).await,
+            "frozen_zbalance"    => self.wallet.frozen_zbalance(None).await,
+            "frozen_tbalance"    => self.wallet.frozen_tbalance(None).await,
             "ua_addresses" => ua_addresses,
             "z_addresses"        => z_addresses,
             "t_addresses"        => t_addresses,
+            "multisig_addresses" => multisig_addresses,
+        };
+
+        // Until a recent-first sync has backfilled the older blocks, the balances above can still change. What it
+        // found in the newest blocks is reported on its own.
+        let provisional = self.wallet.provisional_funds().await;
+        balance["provisional"] = provisional.is_some().into();
+        if let Some((received, spent)) = provisional {
+            balance["provisional_received"] = received.into();
+            balance["provisional_spent"] = spent.into();
         }
+
+        balance
     }
 
     pub async fn do_save(&self, grab_lock: bool) -> Result<(), String> {
         // On mobile platforms, disable the save, because the saves will be handled by the native layer, and not in rust
         if cfg!(all(not(target_os = "ios"), not(target_os = "android"))) {
-            // If the wallet is encrypted but unlocked, lock it again.
-            {
-                if self.wallet.is_encrypted().await && self.wallet.is_unlocked_for_spending().await {
-                    match self.wallet.lock().await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            let err = format!("ERR: {}", e);
-                            error!("{}", err);
-                            return Err(e.to_string());
-                        }
-                    }
-                }
-            }
+            self.lock_for_save().await?;
 
             {
                 // Prevent any overlapping syncs during save, and don't save in the middle of a sync
@@ -549,19 +760,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
     }
 
     pub async fn do_save_to_buffer(&self) -> Result<Vec<u8>, String> {
-        // If the wallet is encrypted but unlocked, lock it again.
-        {
-            if self.wallet.is_encrypted().await && self.wallet.is_unlocked_for_spending().await {
-                match self.wallet.lock().await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        let err = format!("ERR: {}", e);
-                        error!("{}", err);
-                        return Err(e.to_string());
-                    }
-                }
-            }
-        }
+        self.lock_for_save().await?;
 
         let mut buffer: Vec<u8> = vec![];
         match self.wallet.write(&mut buffer).await {
@@ -632,25 +831,61 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             "total" => progress.total,
             "txid" => progress.last_txid,
             "error" => progress.last_error,
+            "amount" => progress.last_amount,
+            "fee" => progress.last_fee,
+            "transactions" => progress.tx_count,
+            "txids" => progress.txids,
         })
     }
 
-    pub fn do_seed_phrase_sync(&self) -> Result<JsonValue, &str> {
+    pub fn do_seed_phrase_sync(&self) -> Result<SecretJson, &str> {
         Runtime::new()
             .unwrap()
             .block_on(async move { self.do_seed_phrase().await })
     }
 
-    pub async fn do_seed_phrase(&self) -> Result<JsonValue, &str> {
+    /// The seed phrase and birthday of the wallet. The secrets are wiped from memory when the result is dropped.
+    pub async fn do_seed_phrase(&self) -> Result<SecretJson, &str> {
         if !self.wallet.is_unlocked_for_spending().await {
             error!("Wallet is locked");
             return Err("Wallet is locked");
         }
 
-        Ok(object! {
-            "seed"     => self.wallet.keys().read().await.get_seed_phrase(),
+        let mut seed = SecretJson::new(object! {
+            "seed"     => JsonValue::String(self.wallet.keys().read().await.get_seed_phrase().to_string()),
             "birthday" => self.wallet.get_birthday().await
-        })
+        });
+
+        // The passphrase is needed along with the seed phrase to restore the wallet
+        let passphrase = self.wallet.keys().read().await.get_passphrase();
+        if !passphrase.is_empty() {
+            seed["passphrase"] = JsonValue::String(passphrase.to_string());
+        }
+
+        Ok(seed)
+    }
+
+    /// Export the seed as `count` SLIP-39 shares, any `threshold` of which can restore the wallet
+    pub async fn do_seed_shares(&self, threshold: u8, count: u8) -> Result<SecretJson, String> {
+        if !self.wallet.is_unlocked_for_spending().await {
+            error!("Wallet is locked");
+            return Err("Wallet is locked".to_string());
+        }
+
+        let shares = self.wallet.keys().read().await.get_seed_shares(threshold, count)?;
+        let mut seed = SecretJson::new(object! {
+            "shares"    => shares.iter().map(|s| JsonValue::String(s.to_string())).collect::<Vec<_>>(),
+            "threshold" => threshold,
+            "birthday"  => self.wallet.get_birthday().await
+        });
+
+        // The passphrase isn't part of the shares, so it has to be kept along with them
+        let passphrase = self.wallet.keys().read().await.get_passphrase();
+        if !passphrase.is_empty() {
+            seed["passphrase"] = JsonValue::String(passphrase.to_string());
+        }
+
+        Ok(seed)
     }
 
     // Return a list of all notes, spent and unspent
@@ -732,18 +967,20 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                             let address = LightWallet::<P>::sapling_note_address(self.config.hrp_sapling_address(), nd);
                             let spendable = address.is_some() &&
                                                     spendable_address.contains(&address.clone().unwrap()) &&
-                                                    wtx.block <= anchor_height && nd.spent.is_none() && nd.unconfirmed_spent.is_none();
+                                                    wtx.block <= anchor_height && nd.spent.is_none() && nd.unconfirmed_spent.is_none() && !nd.frozen;
 
                             let created_block:u32 = wtx.block.into();
                             Some(object!{
                                 "created_in_block"   => created_block,
                                 "datetime"           => wtx.datetime,
                                 "created_in_txid"    => format!("{}", txid),
+                                "output_index"       => nd.output_index,
                                 "value"              => nd.note.value,
                                 "unconfirmed"        => wtx.unconfirmed,
                                 "is_change"          => nd.is_change,
                                 "address"            => address,
                                 "spendable"          => spendable,
+                                "frozen"             => nd.frozen,
                                 "spent"              => nd.spent.map(|(spent_txid, _)| format!("{}", spent_txid)),
                                 "spent_at_height"    => nd.spent.map(|(_, h)| h),
                                 "unconfirmed_spent"  => nd.unconfirmed_spent.map(|(spent_txid, _)| format!("{}", spent_txid)),
@@ -779,10 +1016,12 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                                 "created_in_block"   => created_block,
                                 "datetime"           => wtx.datetime,
                                 "created_in_txid"    => format!("{}", txid),
+                                "output_index"       => utxo.output_index,
                                 "value"              => utxo.value,
                                 "scriptkey"          => hex::encode(utxo.script.clone()),
                                 "is_change"          => false, // TODO: Identify notes as change if we send change to our own taddrs
                                 "address"            => utxo.address.clone(),
+                                "frozen"             => utxo.frozen,
                                 "spent_at_height"    => utxo.spent_at_height,
                                 "spent"              => utxo.spent.map(|spent_txid| format!("{}", spent_txid)),
                                 "unconfirmed_spent"  => utxo.unconfirmed_spent.map(|(spent_txid, _)| format!("{}", spent_txid)),
@@ -854,12 +1093,121 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
     }
 
     pub async fn do_encryption_status(&self) -> JsonValue {
-        object! {
-            "encrypted" => self.wallet.is_encrypted().await,
-            "locked"    => !self.wallet.is_unlocked_for_spending().await
+        self.check_auto_lock().await;
+
+        let encrypted = self.wallet.is_encrypted().await;
+        let locked = !self.wallet.is_unlocked_for_spending().await;
+        let timeout = self.wallet.wallet_options.read().await.auto_lock_timeout;
+
+        let mut status = object! {
+            "encrypted"         => encrypted,
+            "locked"            => locked,
+            "file_encrypted"    => self.wallet.is_file_encrypted().await,
+            "auto_lock_timeout" => timeout,
+        };
+
+        if encrypted && !locked {
+            let state = self.auto_lock.read().await;
+            status["unlock_mode"] = match state.duration {
+                UnlockDuration::UntilLocked => "until_locked",
+                UnlockDuration::Seconds(_) => "timed",
+                UnlockDuration::OneSend => "one_send",
+            }
+            .into();
+
+            if let Some(t) = state.lock_at(timeout) {
+                status["locks_in"] = t.saturating_sub(now()).into();
+            }
+        }
+
+        status
+    }
+
+    /// Turn encryption of the whole wallet file on or off, and save it. The wallet has to be encrypted.
+    pub async fn do_set_file_encryption(&self, passwd: String, enabled: bool) -> Result<JsonValue, String> {
+        self.wallet
+            .set_file_encryption(passwd, enabled)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.do_save(true).await?;
+
+        Ok(object! { "result" => "success" })
+    }
+
+    /// Unlock the wallet for spending. Depending on `duration`, the wallet will be locked again after a
+    /// number of seconds or after the next send. The idle auto-lock timeout applies in every mode.
+    pub async fn do_unlock(&self, passwd: String, duration: UnlockDuration) -> Result<JsonValue, String> {
+        self.wallet.unlock(passwd).await.map_err(|e| e.to_string())?;
+
+        *self.auto_lock.write().await = AutoLockState {
+            duration,
+            unlocked_at: now(),
+            last_activity: now(),
+        };
+
+        Ok(object! { "result" => "success" })
+    }
+
+    /// Lock the wallet if it has been unlocked for longer than allowed. Returns true if the wallet was locked.
+    pub async fn check_auto_lock(&self) -> bool {
+        let timeout = self.wallet.wallet_options.read().await.auto_lock_timeout;
+        AutoLockState::lock_if_expired(&self.auto_lock, &self.wallet.keys(), timeout).await
+    }
+
+    // Called before every save. The wallet is always written locked, and an unlocked wallet stays unlocked for as long
+    // as it was unlocked for. Without an idle timeout, a wallet unlocked until it's locked has nothing that would lock
+    // it, so the save locks it, as saves always did.
+    async fn lock_for_save(&self) -> Result<(), String> {
+        if self.check_auto_lock().await {
+            return Ok(());
+        }
+
+        let timeout = self.wallet.wallet_options.read().await.auto_lock_timeout;
+        let until_locked = self.auto_lock.read().await.duration == UnlockDuration::UntilLocked;
+        if timeout == 0
+            && until_locked
+            && self.wallet.is_encrypted().await
+            && self.wallet.is_unlocked_for_spending().await
+        {
+            if let Err(e) = self.wallet.lock().await {
+                let err = format!("ERR: {}", e);
+                error!("{}", err);
+                return Err(e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Called before every spend. Locks the wallet if it has expired, and resets the idle timer otherwise
+    async fn touch_auto_lock(&self) {
+        self.check_auto_lock().await;
+        self.auto_lock.write().await.last_activity = now();
+    }
+
+    // Called after every spend, to relock wallets that were only unlocked for a single send
+    async fn finish_spend(&self) {
+        let one_send = self.auto_lock.read().await.duration == UnlockDuration::OneSend;
+        if one_send && self.wallet.is_encrypted().await && self.wallet.is_unlocked_for_spending().await {
+            if let Err(e) = self.wallet.lock().await {
+                warn!("Couldn't lock wallet after send: {}", e);
+            }
         }
     }
 
+    /// Change the wallet's password and save it. Only the re-encrypted wallet is ever written to disk.
+    pub async fn do_change_password(&self, old_passwd: String, new_passwd: String) -> Result<JsonValue, String> {
+        self.wallet
+            .change_password(old_passwd, new_passwd)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.do_save(true).await?;
+
+        Ok(object! { "result" => "success" })
+    }
+
     pub async fn do_list_transactions(&self, include_memo_hex: bool) -> JsonValue {
         // Create a list of TransactionItems from wallet txns
         let mut tx_list = self
@@ -1232,6 +1580,74 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         self.bsync_data.read().await.sync_status.read().await.clone()
     }
 
+    /// Get the wallet's events as they happen, instead of polling for sync status, transactions and send progress
+    pub fn subscribe(&self) -> UnboundedReceiver<WalletEvent> {
+        self.wallet.events.subscribe()
+    }
+
+    // The wallet's transactions that are still in the mempool
+    async fn mempool_txids(&self) -> Vec<TxId> {
+        self.wallet
+            .txns
+            .read()
+            .await
+            .current
+            .values()
+            .filter(|wtx| wtx.unconfirmed)
+            .map(|wtx| wtx.txid.clone())
+            .collect()
+    }
+
+    // Tell the event subscribers which of the `mempool_txids` have been mined, or have expired
+    async fn emit_mempool_updates(&self, mempool_txids: Vec<TxId>) {
+        let txns = self.wallet.txns.read().await;
+        for txid in mempool_txids {
+            match txns.current.get(&txid) {
+                Some(wtx) if !wtx.unconfirmed => self.wallet.events.emit(WalletEvent::TxConfirmed {
+                    txid: txid.to_string(),
+                    height: u64::from(wtx.block),
+                }),
+                Some(_) => {}
+                None => self
+                    .wallet
+                    .events
+                    .emit(WalletEvent::TxExpired { txid: txid.to_string() }),
+            }
+        }
+    }
+
+    /// Stop the running sync once its current batch is done. The batches synced so far are kept.
+    pub async fn cancel_sync(&self) -> Result<(), String> {
+        if !self.do_sync_status().await.in_progress {
+            return Err("No sync is running".to_string());
+        }
+
+        *self.sync_control.write().await = SyncControl::Cancel;
+        Ok(())
+    }
+
+    /// Stop the running sync once its current batch is done, and don't sync again until `resume_sync`
+    pub async fn pause_sync(&self) {
+        *self.sync_control.write().await = SyncControl::Pause;
+    }
+
+    /// Allow syncing again after `pause_sync`, and sync the rest of the blocks
+    pub async fn resume_sync(&self) -> Result<JsonValue, String> {
+        {
+            let mut control = self.sync_control.write().await;
+            if *control != SyncControl::Pause {
+                return Err("Sync isn't paused".to_string());
+            }
+            *control = SyncControl::Run;
+        }
+
+        self.do_sync(false).await
+    }
+
+    pub async fn is_sync_paused(&self) -> bool {
+        *self.sync_control.read().await == SyncControl::Pause
+    }
+
     pub fn start_mempool_monitor(lc: Arc<LightClient<P>>) {
         if !lc.config.monitor_mempool {
             return;
@@ -1259,6 +1675,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                     let keys = lc1.wallet.keys();
                     let wallet_txns = lc1.wallet.txns.clone();
                     let price = lc1.wallet.price.clone();
+                    let events = lc1.wallet.events.clone();
 
                     while let Some(rtx) = mempool_rx.recv().await {
                         if let Ok(tx) = Transaction::read(
@@ -1268,6 +1685,9 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                             let price = price.read().await.clone();
                             //info!("Mempool attempting to scan {}", tx.txid());
 
+                            let txid = tx.txid();
+                            let is_new = !wallet_txns.read().await.current.contains_key(&txid);
+
                             FetchFullTxns::<P>::scan_full_tx(
                                 config.clone(),
                                 tx,
@@ -1279,6 +1699,10 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                                 WalletTx::get_price(now(), &price),
                             )
                             .await;
+
+                            if is_new && wallet_txns.read().await.current.contains_key(&txid) {
+                                events.emit(WalletEvent::MempoolTx { txid: txid.to_string() });
+                            }
                         }
                     }
                 });
@@ -1304,7 +1728,55 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         *lc.mempool_monitor.write().unwrap() = Some(h);
     }
 
+    // Start a thread that locks an unlocked, encrypted wallet once its unlock duration or idle timeout expires. It
+    // only holds on to the wallet weakly, and stops once the LightClient is dropped.
+    fn start_auto_lock_monitor(&self) {
+        if self.auto_lock_monitor.read().unwrap().is_some() {
+            return;
+        }
+
+        let state = Arc::downgrade(&self.auto_lock);
+        let keys = Arc::downgrade(&self.wallet.keys());
+        let wallet_options = Arc::downgrade(&self.wallet.wallet_options);
+        let h = std::thread::spawn(move || {
+            Runtime::new().unwrap().block_on(async move {
+                loop {
+                    match (state.upgrade(), keys.upgrade(), wallet_options.upgrade()) {
+                        (Some(state), Some(keys), Some(wallet_options)) => {
+                            let timeout = wallet_options.read().await.auto_lock_timeout;
+                            AutoLockState::lock_if_expired(&state, &keys, timeout).await;
+                        }
+                        _ => return,
+                    }
+
+                    sleep(Duration::from_secs(1)).await;
+                }
+            });
+        });
+
+        *self.auto_lock_monitor.write().unwrap() = Some(h);
+    }
+
     pub async fn do_sync(&self, print_updates: bool) -> Result<JsonValue, String> {
+        self.sync_and_auto_shield(print_updates, || {
+            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+            Ok(LocalTxProver::from_bytes(&sapling_spend, &sapling_output))
+        })
+        .await
+    }
+
+    // Sync, and then shield the transparent funds that came in with a prover from `get_prover`, if the wallet is set
+    // up to. The wallet is saved during the sync, which leaves an unlocked wallet unlocked for the shielding.
+    async fn sync_and_auto_shield<F, PR>(&self, print_updates: bool, get_prover: F) -> Result<JsonValue, String>
+    where
+        F: FnOnce() -> Result<PR, String>,
+        PR: TxProver + Send + Sync,
+    {
+        // The wallet's keys and history aren't loaded until an encrypted wallet file is unlocked
+        if self.wallet.is_sealed().await {
+            return Err("Wallet file is encrypted. Unlock the wallet before syncing".to_string());
+        }
+
         // Remember the previous sync id first
         let prev_sync_id = self.bsync_data.read().await.sync_status.read().await.sync_id;
 
@@ -1312,7 +1784,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         let r_fut = self.start_sync();
 
         // If printing updates, start a new task to print updates every 2 seconds.
-        let sync_result = if print_updates {
+        let mut sync_result = if print_updates {
             let sync_status = self.bsync_data.read().await.sync_status.clone();
             let (tx, mut rx) = oneshot::channel::<i32>();
 
@@ -1347,15 +1819,146 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         // Mark the sync data as finished, which should clear everything
         self.bsync_data.read().await.finish().await;
 
+        // Shield the transparent funds that came in, if the wallet is set up to
+        if let Ok(json) = sync_result.as_mut() {
+            if let Some(r) = self.auto_shield(get_prover).await {
+                json["auto_shield"] = r;
+            }
+        }
+
         sync_result
     }
 
+    // The compact block cache in the data directory, if it is turned on
+    async fn block_cache(&self) -> Option<CompactBlockCache> {
+        let size = self.wallet.wallet_options.read().await.block_cache_size;
+        if size == 0 {
+            return None;
+        }
+
+        match CompactBlockCache::new(&self.config.get_block_cache_path(), size * 1_000_000) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                warn!("Couldn't open the block cache: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Shield the confirmed transparent funds once they reach the wallet's auto-shield threshold or utxo count.
+    /// Returns what happened, to be reported with the sync result, or None if there was nothing to shield.
+    async fn auto_shield<F, PR>(&self, get_prover: F) -> Option<JsonValue>
+    where
+        F: FnOnce() -> Result<PR, String>,
+        PR: TxProver + Send + Sync,
+    {
+        let options = self.wallet.wallet_options.read().await.clone();
+        if !options.auto_shield_enabled() {
+            return None;
+        }
+
+        let utxos = self.wallet.get_shieldable_utxos().await;
+        let value = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+        let count = utxos.len();
+
+        let over_threshold = options.auto_shield_threshold > 0 && value >= options.auto_shield_threshold;
+        let over_count = options.auto_shield_utxos > 0 && count as u64 >= options.auto_shield_utxos;
+        if !over_threshold && !over_count {
+            return None;
+        }
+
+        let report = |result: &str| {
+            object! {
+                "result" => result,
+                "value"  => value,
+                "utxos"  => count,
+            }
+        };
+
+        // An encrypted wallet is never unlocked for this. A wallet that was unlocked for a single send is left
+        // for the user's own send.
+        self.check_auto_lock().await;
+        let one_send = self.auto_lock.read().await.duration == UnlockDuration::OneSend;
+        if self.wallet.is_encrypted().await && (one_send || !self.wallet.is_unlocked_for_spending().await) {
+            let mut r = report("skipped");
+            r["reason"] = "Wallet is locked".into();
+            return Some(r);
+        }
+
+        let address = match options.auto_shield_address {
+            Some(address) => Some(address),
+            None => self.wallet.keys().read().await.get_all_zaddresses().get(0).cloned(),
+        };
+
+        let result = match (address.as_ref(), get_prover()) {
+            (None, _) => Err("No z-address to shield to".to_string()),
+            (_, Err(e)) => Err(e),
+            (Some(address), Ok(prover)) => {
+                let _lock = self.sync_lock.lock().await;
+
+                self.wallet
+                    .shield(prover, address, utxos, None, |txbytes| {
+                        GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                    })
+                    .await
+            }
+        };
+
+        match result {
+            Ok((txid, _)) => {
+                info!("Auto-shielded {} zats in {} utxos, txid {}", value, count, txid);
+                let mut r = report("success");
+                r["address"] = address.into();
+                r["txid"] = txid.into();
+                Some(r)
+            }
+            Err(e) => {
+                warn!("Couldn't auto-shield: {}", e);
+                let mut r = report("error");
+                r["error"] = e.into();
+                Some(r)
+            }
+        }
+    }
+
     /// Start syncing in batches with the max size, so we don't consume memory more than
     // wha twe can handle.
     async fn start_sync(&self) -> Result<JsonValue, String> {
         // We can only do one sync at a time because we sync blocks in serial order
         // If we allow multiple syncs, they'll all get jumbled up.
-        let _lock = self.sync_lock.lock().await;
+        let mut _lock = self.sync_lock.lock().await;
+
+        // A cancel only stops the sync that was running when it was asked for
+        {
+            let mut control = self.sync_control.write().await;
+            if *control == SyncControl::Cancel {
+                *control = SyncControl::Run;
+            }
+        }
+
+        // A recent-first sync that didn't finish scanning the older blocks does that first. The wallet's blocks are
+        // already past them, so the sync then goes on from there to the tip.
+        let backfill_batches = match self.wallet.backfill.read().await.as_ref() {
+            Some(backfill) => Some(Self::sync_batches(backfill.scanned_height, backfill.last_height())),
+            None => None,
+        };
+        let backfilled = backfill_batches.is_some();
+        if let Some(batches) = backfill_batches {
+            self.bsync_data
+                .read()
+                .await
+                .sync_status
+                .write()
+                .await
+                .start_new(batches.len());
+            drop(_lock);
+
+            let res = self.backfill(batches, 0).await?;
+            if res["result"] != "success" {
+                return Ok(res);
+            }
+            _lock = self.sync_lock.lock().await;
+        }
 
         // The top of the wallet
         let last_scanned_height = self.wallet.last_scanned_height().await;
@@ -1385,25 +1988,68 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                     self.wallet.orchard_witnesses.clone(),
                 )
                 .await;
+                self.wallet.events.emit(WalletEvent::ReorgRolledBack {
+                    height: last_scanned_height - 1,
+                });
             }
         }
 
         // Re-read the last scanned height
         let last_scanned_height = self.wallet.last_scanned_height().await;
 
-        let mut latest_block_batches = vec![];
-        let mut prev = last_scanned_height;
-        while latest_block_batches.is_empty() || prev != latest_blockid.height {
-            let mut batch_size = 50_000;
-            if prev + batch_size > 1_700_000 {
-                batch_size = 1_000;
+        // In recent-first order, the newest blocks are scanned into the wallet first, so the notes received in them
+        // can be spent right away. The older blocks are backfilled after that, with the sync lock let go of between
+        // batches. A paused sync doesn't start it.
+        let sync_order = self.wallet.wallet_options.read().await.sync_order;
+        let recent_range = sync_order.recent_range(last_scanned_height, latest_blockid.height);
+        let control = *self.sync_control.read().await;
+        if let (Some((first, last)), SyncControl::Run) = (recent_range, control) {
+            let backfill_batches = Self::sync_batches(last_scanned_height, first - 1);
+            let batch_total = backfill_batches.len() + 1;
+            self.bsync_data
+                .read()
+                .await
+                .sync_status
+                .write()
+                .await
+                .start_new(batch_total);
+
+            let mempool_txids = self.mempool_txids().await;
+            self.wallet.events.emit(WalletEvent::SyncBatchStarted {
+                batch_num: 0,
+                batch_total,
+                first_block: first,
+                last_block: last,
+            });
+
+            let res = self.start_sync_batch(last, 0, last, SyncPass::Recent { first }).await;
+            if res.is_err() {
+                info!("Sync failed, not saving: {:?}", res.as_ref().err());
+                return res;
             }
+            self.do_save(false).await?;
 
-            let batch = cmp::min(latest_blockid.height, prev + batch_size);
-            prev = batch;
-            latest_block_batches.push(batch);
+            self.bsync_data
+                .read()
+                .await
+                .sync_status
+                .write()
+                .await
+                .add_done_range(first, last);
+            self.wallet.events.emit(WalletEvent::SyncBatchFinished {
+                batch_num: 0,
+                batch_total,
+                latest_block: last,
+            });
+            self.emit_mempool_updates(mempool_txids).await;
+            self.wallet.events.emit(WalletEvent::HeightAdvanced { height: last });
+
+            drop(_lock);
+            return self.backfill(backfill_batches, 1).await;
         }
 
+        let latest_block_batches = Self::sync_batches(last_scanned_height, latest_blockid.height);
+
         // println!("Batches are {:?}", latest_block_batches);
 
         // Increment the sync ID so the caller can determine when it is over
@@ -1414,33 +2060,323 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             let mut l2 = l1.sync_status.write().await;
             // println!("l2");
 
+            // The ranges of a backfill done first are still part of this sync
+            let done_ranges = std::mem::take(&mut l2.done_ranges);
             l2.start_new(latest_block_batches.len());
+            if backfilled {
+                l2.done_ranges = done_ranges;
+            }
         }
+        let batch_total = latest_block_batches.len();
         // println!("Started new sync");
 
         let mut res = Err("No batches were run!".to_string());
         for (batch_num, batch_latest_block) in latest_block_batches.into_iter().enumerate() {
+            // Pausing and cancelling take effect between batches, when all the blaze tasks of the previous batch are
+            // done and it has been saved, so the wallet is left consistent
+            let control = *self.sync_control.read().await;
+            if control != SyncControl::Run {
+                let result = if control == SyncControl::Cancel {
+                    *self.sync_control.write().await = SyncControl::Run;
+                    "cancelled"
+                } else {
+                    "paused"
+                };
+                info!("Sync {} before batch {}", result, batch_num);
+
+                let synced_height = self.wallet.last_scanned_height().await;
+                return Ok(object! {
+                    "result" => result,
+                    "latest_block" => synced_height,
+                    "total_blocks_synced" => synced_height - last_scanned_height,
+                });
+            }
+
             // println!("Starting batch {}", batch_num);
-            res = self.start_sync_batch(batch_latest_block, batch_num).await;
+            let batch_start = self.wallet.last_scanned_height().await;
+            let has_blocks = batch_latest_block > batch_start;
+            let mempool_txids = self.mempool_txids().await;
+            if has_blocks {
+                self.wallet.events.emit(WalletEvent::SyncBatchStarted {
+                    batch_num,
+                    batch_total,
+                    first_block: batch_start + 1,
+                    last_block: batch_latest_block,
+                });
+            }
+
+            res = self
+                .start_sync_batch(batch_latest_block, batch_num, latest_blockid.height, SyncPass::InOrder)
+                .await;
             if res.is_err() {
                 info!("Sync failed, not saving: {:?}", res.as_ref().err());
                 return res;
             } else {
                 self.do_save(false).await?;
             }
+
+            if has_blocks {
+                let latest_block = self.wallet.last_scanned_height().await;
+                self.bsync_data
+                    .read()
+                    .await
+                    .sync_status
+                    .write()
+                    .await
+                    .add_done_range(batch_start + 1, latest_block);
+                self.wallet.events.emit(WalletEvent::SyncBatchFinished {
+                    batch_num,
+                    batch_total,
+                    latest_block,
+                });
+                self.emit_mempool_updates(mempool_txids).await;
+                if latest_block != batch_start {
+                    self.wallet
+                        .events
+                        .emit(WalletEvent::HeightAdvanced { height: latest_block });
+                }
+            }
         }
 
         res
     }
 
+    // The heights each batch of a sync from `last_scanned_height` up to `latest_block` goes up to. There is always at
+    // least one.
+    fn sync_batches(last_scanned_height: u64, latest_block: u64) -> Vec<u64> {
+        let mut batches = vec![];
+        let mut prev = last_scanned_height;
+        while batches.is_empty() || prev != latest_block {
+            let mut batch_size = 50_000;
+            if prev + batch_size > 1_700_000 {
+                batch_size = 1_000;
+            }
+
+            let batch = cmp::min(latest_block, prev + batch_size);
+            prev = batch;
+            batches.push(batch);
+        }
+
+        batches
+    }
+
+    // Scan the older blocks a recent-first sync skipped, in `batches` numbered from `first_batch_num`, and then look
+    // at the newest blocks again for the notes found in them. The sync lock is taken for one batch at a time, so the
+    // notes from the newest blocks can be spent in between.
+    async fn backfill(&self, batches: Vec<u64>, first_batch_num: usize) -> Result<JsonValue, String> {
+        let wallet_height = self.wallet.last_scanned_height().await;
+        let batch_total = first_batch_num + batches.len();
+        let mut total_blocks_synced = 0;
+
+        for (i, batch_latest_block) in batches.into_iter().enumerate() {
+            let batch_num = first_batch_num + i;
+            let control = *self.sync_control.read().await;
+            if control != SyncControl::Run {
+                let result = if control == SyncControl::Cancel {
+                    *self.sync_control.write().await = SyncControl::Run;
+                    "cancelled"
+                } else {
+                    "paused"
+                };
+                info!("Backfill {} before batch {}", result, batch_num);
+
+                return Ok(object! {
+                    "result" => result,
+                    "latest_block" => wallet_height,
+                    "total_blocks_synced" => total_blocks_synced,
+                });
+            }
+
+            let _lock = self.sync_lock.lock().await;
+
+            // Another sync may have done this batch while the lock was let go of, or a rescan thrown the backfill away
+            let batch_start = match self.wallet.backfill.read().await.as_ref() {
+                Some(backfill) => backfill.scanned_height,
+                None => break,
+            };
+            if batch_latest_block <= batch_start {
+                continue;
+            }
+
+            self.wallet.events.emit(WalletEvent::SyncBatchStarted {
+                batch_num,
+                batch_total,
+                first_block: batch_start + 1,
+                last_block: batch_latest_block,
+            });
+
+            let res = self
+                .start_sync_batch(batch_latest_block, batch_num, wallet_height, SyncPass::Backfill)
+                .await;
+            if res.is_err() {
+                info!("Backfill failed, not saving: {:?}", res.as_ref().err());
+                return res;
+            }
+            self.do_save(false).await?;
+            total_blocks_synced += batch_latest_block - batch_start;
+
+            self.bsync_data
+                .read()
+                .await
+                .sync_status
+                .write()
+                .await
+                .add_done_range(batch_start + 1, batch_latest_block);
+            self.wallet.events.emit(WalletEvent::SyncBatchFinished {
+                batch_num,
+                batch_total,
+                latest_block: batch_latest_block,
+            });
+        }
+
+        {
+            let _lock = self.sync_lock.lock().await;
+            let done = self.wallet.backfill.read().await.as_ref().map(|b| b.is_done());
+            if done == Some(true) {
+                self.finish_backfill(batch_total - 1).await?;
+                self.do_save(false).await?;
+            }
+        }
+
+        Ok(object! {
+            "result" => "success",
+            "latest_block" => wallet_height,
+            "total_blocks_synced" => total_blocks_synced,
+        })
+    }
+
+    // Once the backfill reaches the newest blocks, look for the spends of the older notes in them, and bring their
+    // witnesses and the orchard tree up to the wallet's height. The newest blocks were saved with the backfill for
+    // this, so they aren't downloaded again.
+    async fn finish_backfill(&self, batch_num: usize) -> Result<(), String> {
+        let recent_blocks = match self.wallet.backfill.read().await.as_ref() {
+            Some(backfill) => backfill.recent_blocks.clone(),
+            None => return Ok(()),
+        };
+        let (start_block, end_block) = match (recent_blocks.first(), recent_blocks.last()) {
+            (Some(top), Some(bottom)) => (top.height, bottom.height),
+            _ => {
+                self.wallet.backfill.write().await.take();
+                return Ok(());
+            }
+        };
+
+        let bsync_data = self.bsync_data.clone();
+        bsync_data
+            .write()
+            .await
+            .setup_for_sync(
+                start_block,
+                end_block,
+                batch_num,
+                vec![],
+                None,
+                self.wallet.orchard_witnesses.clone(),
+                self.wallet.wallet_options.read().await.clone(),
+            )
+            .await;
+
+        // The orchard notes found in the newest blocks get their place in the orchard tree now
+        let orchard_notes = self
+            .wallet
+            .txns
+            .read()
+            .await
+            .current
+            .values()
+            .flat_map(|wtx| wtx.o_notes.iter())
+            .filter(|nd| nd.witness_position.is_none() && nd.created_at.0 >= end_block)
+            .map(|nd| nd.created_at)
+            .collect::<Vec<_>>();
+        for (height, tx_num, output_num) in orchard_notes {
+            bsync_data
+                .read()
+                .await
+                .block_data
+                .track_orchard_note(height, tx_num, output_num)
+                .await;
+        }
+
+        let grpc_connector = GrpcConnector::new(self.config.server.clone());
+        let (fulltx_fetcher_handle, fulltx_fetcher_tx) =
+            grpc_connector.start_fulltx_fetcher(self.config.get_params()).await;
+        let fetch_full_tx_processor = FetchFullTxns::new(&self.config, self.wallet.keys(), self.wallet.txns());
+        let (fetch_full_txns_handle, scan_full_txn_tx, _) = fetch_full_tx_processor
+            .start(fulltx_fetcher_tx, bsync_data.clone())
+            .await;
+
+        // The notes from before the newest blocks are the ones that are updated
+        let update_notes_processor = UpdateNotes::new(self.wallet.txns());
+        let (update_notes_handle, blocks_done_tx, _) = update_notes_processor
+            .start(bsync_data.clone(), scan_full_txn_tx.clone())
+            .await;
+
+        let (reorg_tx, _reorg_rx) = unbounded_channel();
+        let (block_and_witness_handle, block_and_witness_data_tx) = bsync_data
+            .read()
+            .await
+            .block_data
+            .start(start_block, end_block, self.wallet.txns(), reorg_tx)
+            .await;
+        for block in recent_blocks {
+            block_and_witness_data_tx
+                .send(block.cb())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        drop(block_and_witness_data_tx);
+
+        let earliest_block = block_and_witness_handle.await.map_err(|e| e.to_string())??;
+        blocks_done_tx.send(earliest_block).unwrap();
+        update_notes_handle.await.map_err(|e| e.to_string())??;
+
+        bsync_data
+            .read()
+            .await
+            .block_data
+            .update_orchard_spends_and_witnesses(self.wallet.txns.clone(), scan_full_txn_tx)
+            .await;
+
+        let mut tasks = FuturesUnordered::new();
+        tasks.push(fulltx_fetcher_handle);
+        tasks.push(fetch_full_txns_handle);
+        while let Some(r) = tasks.next().await {
+            match r {
+                Ok(Ok(_)) => (),
+                Ok(Err(s)) => return Err(s),
+                Err(e) => return Err(e.to_string()),
+            };
+        }
+
+        // The blocks have to be taken out of the sync data, which keeps them until the next sync otherwise
+        bsync_data.read().await.block_data.finish_get_blocks(0).await;
+        self.wallet.backfill.write().await.take();
+        info!("Backfill finished, the wallet's balance is up to date");
+
+        Ok(())
+    }
+
     /// start_sync will start synchronizing the blockchain from the wallet's last height. This function will return immediately after starting the sync
     /// Use the `sync_status` command to get the status of the sync
-    async fn start_sync_batch(&self, latest_block: u64, batch_num: usize) -> Result<JsonValue, String> {
+    async fn start_sync_batch(
+        &self,
+        latest_block: u64,
+        batch_num: usize,
+        chain_tip: u64,
+        pass: SyncPass,
+    ) -> Result<JsonValue, String> {
         let uri = self.config.server.clone();
 
-        // The top of the wallet
+        // The top of the wallet, or of the blocks this pass goes on from
         // println!("Trying to get last scanned height");
-        let last_scanned_height = self.wallet.last_scanned_height().await;
+        let (last_scanned_height, existing_blocks) = match pass {
+            SyncPass::InOrder => (self.wallet.last_scanned_height().await, self.wallet.get_blocks().await),
+            SyncPass::Recent { first } => (first - 1, vec![]),
+            SyncPass::Backfill => match self.wallet.backfill.read().await.as_ref() {
+                Some(backfill) => (backfill.scanned_height, backfill.blocks.clone()),
+                None => return Err("The wallet has no blocks to backfill".to_string()),
+            },
+        };
         // println!("Got last scanned height : {}", last_scanned_height);
 
         info!(
@@ -1459,8 +2395,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         let start_block = latest_block;
         let end_block = last_scanned_height + 1;
 
-        // Make sure that the wallet has an orchard tree first
-        {
+        // Make sure that the wallet has an orchard tree first. The newest blocks can't be added to it before the older
+        // ones, so a recent pass leaves them until the backfill is done.
+        let recent = matches!(pass, SyncPass::Recent { .. });
+        let orchard_witnesses = if recent {
+            Arc::new(RwLock::new(None))
+        } else {
+            self.wallet.orchard_witnesses.clone()
+        };
+        if !recent {
             let mut orchard_witnesses = self.wallet.orchard_witnesses.write().await;
 
             if orchard_witnesses.is_none() {
@@ -1492,6 +2435,11 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
 
         // Before we start, we need to do a few things
         // 1. Pre-populate the last 100 blocks, in case of reorgs
+        let verified_tree = if pass == SyncPass::InOrder {
+            self.wallet.verified_tree.read().await.clone()
+        } else {
+            None
+        };
         bsync_data
             .write()
             .await
@@ -1499,12 +2447,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
                 start_block,
                 end_block,
                 batch_num,
-                self.wallet.get_blocks().await,
-                self.wallet.verified_tree.read().await.clone(),
-                self.wallet.orchard_witnesses.clone(),
-                *self.wallet.wallet_options.read().await,
+                existing_blocks,
+                verified_tree,
+                orchard_witnesses,
+                self.wallet.wallet_options.read().await.clone(),
             )
             .await;
+        if let SyncPass::Recent { first } = pass {
+            bsync_data.write().await.block_data.set_recent_start(first);
+        }
 
         // 2. Update the current price
         self.update_current_price().await;
@@ -1549,7 +2500,11 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             .await;
 
         // Fetch Compact blocks and send them to nullifier cache, node-and-witness cache and the trial-decryption processor
-        let fetch_compact_blocks = Arc::new(FetchCompactBlocks::new(&self.config));
+        let fetch_compact_blocks = Arc::new(FetchCompactBlocks::new(
+            &self.config,
+            self.block_cache().await,
+            chain_tip.saturating_sub(CACHE_MIN_DEPTH),
+        ));
         let fetch_compact_blocks_handle = tokio::spawn(async move {
             fetch_compact_blocks
                 .start(
@@ -1565,6 +2520,11 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         // We wait first for the node's to be updated. This is where reorgs will be handled, so all the steps done after this phase will
         // assume that the reorgs are done.
         let earliest_block = block_and_witness_handle.await.unwrap().unwrap();
+        if earliest_block < end_block {
+            self.wallet.events.emit(WalletEvent::ReorgRolledBack {
+                height: earliest_block - 1,
+            });
+        }
         let params = self.config.get_params();
 
         // 1. Fetch the transparent txns only after reorgs are done.
@@ -1633,22 +2593,50 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         }
 
         // 2. Get the last 100 blocks and store it into the wallet, needed for future re-orgs
-        let blocks = bsync_data.read().await.block_data.finish_get_blocks(MAX_REORG).await;
-        self.wallet.set_blocks(blocks).await;
+        match pass {
+            SyncPass::InOrder => {
+                let blocks = bsync_data.read().await.block_data.finish_get_blocks(MAX_REORG).await;
+                self.wallet.set_blocks(blocks).await;
+            }
+            SyncPass::Recent { .. } => {
+                // The wallet goes on from the newest blocks, which are all kept for the end of the backfill. The
+                // backfill goes on from where the wallet was.
+                let recent_blocks = bsync_data.read().await.block_data.finish_get_blocks(usize::MAX).await;
+                let backfill = Backfill::new(
+                    self.wallet.last_scanned_height().await,
+                    self.wallet.get_blocks().await,
+                    recent_blocks.clone(),
+                );
+                self.wallet
+                    .set_blocks(recent_blocks.into_iter().take(MAX_REORG).collect())
+                    .await;
+                *self.wallet.backfill.write().await = Some(backfill);
+            }
+            SyncPass::Backfill => {
+                let blocks = bsync_data.read().await.block_data.finish_get_blocks(MAX_REORG).await;
+                if let Some(backfill) = self.wallet.backfill.write().await.as_mut() {
+                    backfill.scanned_height = latest_block;
+                    backfill.blocks = blocks;
+                }
+            }
+        }
 
         // 3. If sync was successfull, also try to get historical prices
         self.update_historical_prices().await;
 
-        // 4. Remove the witnesses for spent notes more than 100 blocks old, since now there
-        // is no risk of reorg
-        self.wallet.txns().write().await.clear_old_witnesses(latest_block);
+        // The rest is for the top of the wallet, which a backfill is below
+        if pass != SyncPass::Backfill {
+            // 4. Remove the witnesses for spent notes more than 100 blocks old, since now there
+            // is no risk of reorg
+            self.wallet.txns().write().await.clear_old_witnesses(latest_block);
 
-        // 5. Remove expired mempool transactions, if any
-        self.wallet.txns().write().await.clear_expired_mempool(latest_block);
+            // 5. Remove expired mempool transactions, if any
+            self.wallet.txns().write().await.clear_expired_mempool(latest_block);
 
-        // 6. Set the heighest verified tree
-        if heighest_tree.is_some() {
-            *self.wallet.verified_tree.write().await = heighest_tree;
+            // 6. Set the heighest verified tree
+            if heighest_tree.is_some() {
+                *self.wallet.verified_tree.write().await = heighest_tree;
+            }
         }
 
         Ok(object! {
@@ -1658,17 +2646,24 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
         })
     }
 
-    pub async fn do_shield(&self, address: Option<String>) -> Result<String, String> {
-        let fee = u64::from(DEFAULT_FEE);
-        let tbal = self.wallet.tbalance(None).await;
-
-        // Make sure there is a balance, and it is greated than the amount
-        if tbal <= fee {
-            return Err(format!(
-                "Not enough transparent balance to shield. Have {} zats, need more than {} zats to cover tx fee",
-                tbal, fee
-            ));
-        }
+    /// Shield all transparent funds, or only the `utxos` outpoints if they are given. The fee comes from the
+    /// wallet's fee policy, unless `fee` is given. If there are too many utxos for one transaction, they are
+    /// shielded in several, each paying its own fee.
+    pub async fn do_shield(
+        &self,
+        address: Option<String>,
+        fee: Option<u64>,
+        utxos: Option<Vec<(TxId, u32)>>,
+    ) -> Result<String, String> {
+        self.touch_auto_lock().await;
+
+        let selection = utxos.map(|utxos| CoinSelection { s_notes: vec![], utxos });
+
+        // Shielding spends the utxos into a single sapling output, with no change
+        let utxos = match selection.as_ref() {
+            Some(selection) => self.wallet.chosen_inputs(selection, false).await?.1,
+            None => self.wallet.get_spendable_utxos().await,
+        };
 
         let addr = address
             .or(self
@@ -1688,17 +2683,32 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
 
             self.wallet
-                .send_to_address(prover, true, vec![(&addr, tbal - fee, None)], |txbytes| {
+                .shield(prover, &addr, utxos, fee, |txbytes| {
                     GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
                 })
                 .await
         };
+        self.finish_spend().await;
 
         result.map(|(txid, _)| txid)
     }
 
     pub async fn do_send(&self, addrs: Vec<(&str, u64, Option<String>)>) -> Result<String, String> {
+        self.do_send_with_options(addrs, None, None, None).await
+    }
+
+    /// Send to `addrs`. If `fee` is given, it is paid instead of the fee computed from the wallet's fee policy.
+    /// If `inputs` are given, exactly those notes and utxos are spent. If `from` is given, only the funds received
+    /// at that address are spent.
+    pub async fn do_send_with_options(
+        &self,
+        addrs: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<CoinSelection>,
+        from: Option<SendFrom>,
+    ) -> Result<String, String> {
         info!("Creating transaction");
+        self.touch_auto_lock().await;
 
         // println!("BranchID {:x}", branch_id);
 
@@ -1709,11 +2719,371 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
             let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
 
             self.wallet
-                .send_to_address(prover, false, addrs, |txbytes| {
+                .send_to_address(prover, false, addrs, fee, inputs.as_ref(), from.as_ref(), |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    /// Send everything spendable from `source` to `to`, with the fee taken out of the amount sent
+    pub async fn do_send_max(
+        &self,
+        to: &str,
+        memo: Option<String>,
+        source: SendMaxSource,
+        fee: Option<u64>,
+    ) -> Result<String, String> {
+        info!("Creating transaction sending the entire balance");
+        self.touch_auto_lock().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+
+            let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+
+            self.wallet
+                .send_max(prover, to, memo, &source, fee, |txbytes| {
                     GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
                 })
                 .await
         };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    /// Merge the `count` smallest spendable sapling notes, or all of them, into notes to `address`, which must be
+    /// one of this wallet's z-addresses. Returns the txids of the transactions it took.
+    pub async fn do_consolidate(
+        &self,
+        address: Option<String>,
+        count: Option<usize>,
+        fee: Option<u64>,
+    ) -> Result<Vec<String>, String> {
+        info!("Consolidating notes");
+        self.touch_auto_lock().await;
+
+        let address = self.consolidation_address(address).await?;
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+
+            let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+
+            self.wallet
+                .consolidate(prover, &address, count, fee, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result?;
+        Ok(self.wallet.get_send_progress().await.txids)
+    }
+
+    /// How many transactions and how much in fees `do_consolidate` would take, without sending anything
+    pub async fn do_consolidation_preview(
+        &self,
+        address: Option<String>,
+        count: Option<usize>,
+        fee: Option<u64>,
+    ) -> Result<JsonValue, String> {
+        let address = self.consolidation_address(address).await?;
+        let proposals = self.wallet.consolidation_proposals(&address, count, fee).await?;
+
+        let transactions = proposals
+            .iter()
+            .map(|p| {
+                object! {
+                    "notes"          => p.s_notes.len(),
+                    "value"          => p.selected_value(),
+                    "fee"            => p.fee,
+                    "estimated_size" => p.estimated_size(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(object! {
+            "address"      => address,
+            "notes"        => proposals.iter().map(|p| p.s_notes.len()).sum::<usize>(),
+            "value"        => proposals.iter().map(|p| p.selected_value()).sum::<u64>(),
+            "fee"          => proposals.iter().map(|p| p.fee).sum::<u64>(),
+            "transactions" => JsonValue::Array(transactions),
+        })
+    }
+
+    async fn consolidation_address(&self, address: Option<String>) -> Result<String, String> {
+        let zaddrs = self.wallet.keys().read().await.get_all_zaddresses();
+        match address {
+            Some(address) if zaddrs.contains(&address) => Ok(address),
+            Some(address) => Err(format!("{} is not a z-address of this wallet", address)),
+            None => zaddrs
+                .get(0)
+                .cloned()
+                .ok_or_else(|| "No z-address to consolidate to".to_string()),
+        }
+    }
+
+    /// Select the inputs and compute the fee for a send, without proving or broadcasting it. The returned
+    /// proposal can be sent with `do_send_proposal` or thrown away with `do_discard_proposal`.
+    pub async fn do_propose(
+        &self,
+        addrs: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<CoinSelection>,
+        from: Option<SendFrom>,
+    ) -> Result<JsonValue, String> {
+        self.touch_auto_lock().await;
+
+        let proposal = self
+            .wallet
+            .propose(false, addrs, fee, inputs.as_ref(), from.as_ref())
+            .await?;
+        Ok(proposal.to_json())
+    }
+
+    pub async fn do_send_proposal(&self, id: u32) -> Result<String, String> {
+        info!("Sending transaction proposal {}", id);
+        self.touch_auto_lock().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+
+            let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+
+            self.wallet
+                .send_proposal(prover, id, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    /// Create an unsigned transaction spending the notes of viewing keys in this watch-only wallet, to be signed by
+    /// an offline wallet with `do_sign_unsigned_tx`
+    pub async fn do_create_unsigned_tx(
+        &self,
+        addrs: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<CoinSelection>,
+    ) -> Result<UnsignedTx, String> {
+        self.wallet.create_unsigned_tx(addrs, fee, inputs.as_ref()).await
+    }
+
+    /// Prove and sign an unsigned transaction with the keys in this wallet. Returns the txid and the raw
+    /// transaction, which is broadcast by the watch-only wallet with `do_broadcast_signed_tx`
+    pub async fn do_sign_unsigned_tx(&self, utx: &UnsignedTx) -> Result<(String, Vec<u8>), String> {
+        self.touch_auto_lock().await;
+
+        let result = {
+            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+            let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+
+            self.wallet.sign_unsigned_tx(prover, utx).await
+        };
+        self.finish_spend().await;
+
+        result
+    }
+
+    pub async fn do_broadcast_signed_tx(&self, raw_tx: Vec<u8>) -> Result<String, String> {
+        self.wallet
+            .broadcast_signed_tx(raw_tx, |txbytes| {
+                GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+            })
+            .await
+    }
+
+    /// Have outgoing transactions signed by the signer listening at `address` on this machine, instead of with the
+    /// keys in this wallet. `remote` is the address and the token shared with the signer. With `None`, the wallet's
+    /// own keys sign again.
+    pub async fn do_set_remote_signer(&self, remote: Option<(&str, &str)>) -> Result<(), String> {
+        let signer: Option<Arc<dyn Signer>> = match remote {
+            Some((address, token)) => Some(Arc::new(SocketSigner::new(address, token)?)),
+            None => None,
+        };
+        self.wallet.set_signer(signer).await;
+
+        Ok(())
+    }
+
+    /// Sign the transactions of wallets that use this one as their remote signer, with the keys in this wallet.
+    /// The signer runs in the background, replacing any that was running. It only answers requests from this machine
+    /// that carry `token`, and every request waits until it is approved or rejected with `do_answer_signing_request`.
+    pub async fn do_run_signer(&self, address: &str, token: &str) -> Result<(), String> {
+        let address: SocketAddr = address
+            .parse()
+            .map_err(|e| format!("Invalid signer address '{}': {}", address, e))?;
+        if !address.ip().is_loopback() {
+            return Err(format!("The signer address {} is not a local address", address));
+        }
+        signer::check_token(token)?;
+
+        let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+        let prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+        let signer = KeysSigner::new(self.config.clone(), self.wallet.keys(), prover);
+
+        // Stop the old signer first, in case it is listening on the same address
+        self.do_stop_signer();
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| format!("Couldn't listen on {}: {}", address, e))?;
+        info!("Signer listening on {}", address);
+
+        let approvals = Arc::new(PendingApprovals::new(
+            self.config.hrp_sapling_address(),
+            self.wallet.events.clone(),
+        ));
+        let running = RunningSigner::start(listener, token.to_string(), signer, approvals);
+        *self.signer.lock().unwrap() = Some(running);
+
+        Ok(())
+    }
+
+    /// Stop the signer started with `do_run_signer`. Returns false if it wasn't running.
+    pub fn do_stop_signer(&self) -> bool {
+        self.signer.lock().unwrap().take().is_some()
+    }
+
+    /// The signing requests waiting to be approved, with their outputs, fee and change
+    pub fn do_signing_requests(&self) -> Result<JsonValue, String> {
+        match self.signer.lock().unwrap().as_ref() {
+            Some(running) => Ok(running.approvals.list()),
+            None => Err("The signer isn't running".to_string()),
+        }
+    }
+
+    /// Approve or reject the signing request with `id`
+    pub fn do_answer_signing_request(&self, id: &str, approve: bool) -> Result<(), String> {
+        match self.signer.lock().unwrap().as_ref() {
+            Some(running) => running.approvals.answer(id, approve),
+            None => Err("The signer isn't running".to_string()),
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_sign_unsigned_tx(&self, utx: &UnsignedTx) -> Result<(String, Vec<u8>), String> {
+        self.touch_auto_lock().await;
+
+        let prover = crate::blaze::test_utils::FakeTxProver {};
+        let result = self.wallet.sign_unsigned_tx(prover, utx).await;
+        self.finish_spend().await;
+
+        result
+    }
+
+    /// The public key of one of this wallet's t addresses (the first one by default), to give to the co-signers
+    /// of a multisig address
+    pub async fn do_tpubkey(&self, address: Option<String>) -> Result<JsonValue, String> {
+        if !self.wallet.is_unlocked_for_spending().await {
+            return Err("Wallet is locked".to_string());
+        }
+
+        let keys = self.wallet.keys();
+        let keys = keys.read().await;
+        let address = match address {
+            Some(address) => address,
+            None => keys
+                .get_all_taddrs()
+                .first()
+                .cloned()
+                .ok_or("The wallet has no t addresses".to_string())?,
+        };
+
+        let pubkey = keys
+            .get_taddr_pubkey(&address)
+            .ok_or(format!("{} is not a t address in this wallet", address))?;
+
+        Ok(object! {
+            "address" => address,
+            "pubkey" => hex::encode(pubkey.serialize()),
+        })
+    }
+
+    /// Track the M-of-N multisig address made from the co-signers' public keys, in the order given. The wallet
+    /// has to be rescanned to find funds sent to it before it was added.
+    pub async fn do_new_multisig_address(&self, threshold: u8, pubkeys: Vec<&str>) -> Result<JsonValue, String> {
+        let pubkeys = MultisigAddress::parse_pubkeys(&pubkeys)?;
+        let address = self
+            .wallet
+            .keys()
+            .write()
+            .await
+            .add_multisig_address(threshold, pubkeys)?;
+
+        self.do_save(true).await?;
+
+        let multisig = self.wallet.keys().read().await.get_multisig(&address).cloned().unwrap();
+        Ok(multisig.to_json())
+    }
+
+    pub async fn do_create_multisig_tx(
+        &self,
+        from: &str,
+        tos: Vec<(&str, u64)>,
+        fee: Option<u64>,
+    ) -> Result<PartiallySignedTx, String> {
+        self.wallet.create_multisig_tx(from, tos, fee).await
+    }
+
+    pub async fn do_sign_multisig_tx(&self, pstx: &mut PartiallySignedTx) -> Result<usize, String> {
+        self.touch_auto_lock().await;
+        self.wallet.sign_multisig_tx(pstx).await
+    }
+
+    /// Add the signatures in a co-signer's copy of the multisig transaction to `pstx`
+    pub fn do_merge_multisig_tx(&self, pstx: &mut PartiallySignedTx, other: &PartiallySignedTx) -> Result<(), String> {
+        pstx.merge(&self.config.get_params(), other)
+    }
+
+    pub async fn do_broadcast_multisig_tx(&self, pstx: &PartiallySignedTx) -> Result<String, String> {
+        self.wallet
+            .broadcast_multisig_tx(pstx, |txbytes| {
+                GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+            })
+            .await
+    }
+
+    /// Freeze (or unfreeze) the given notes and utxos, so they are never spent or shielded
+    pub async fn do_freeze(&self, selection: CoinSelection, frozen: bool) -> Result<(), String> {
+        self.wallet.set_frozen(&selection, frozen).await?;
+
+        self.do_save(true).await
+    }
+
+    pub async fn do_discard_proposal(&self, id: u32) -> Result<(), String> {
+        if self.wallet.discard_proposal(id).await {
+            Ok(())
+        } else {
+            Err(format!("No transaction proposal with id {}", id))
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_send_proposal(&self, id: u32) -> Result<String, String> {
+        self.touch_auto_lock().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let prover = crate::blaze::test_utils::FakeTxProver {};
+
+            self.wallet
+                .send_proposal(prover, id, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
 
         result.map(|(txid, _)| txid)
     }
@@ -1721,17 +3091,119 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightClient<P> {
     #[cfg(test)]
     pub async fn test_do_send(&self, addrs: Vec<(&str, u64, Option<String>)>) -> Result<String, String> {
         info!("Creating transaction");
+        self.touch_auto_lock().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let prover = crate::blaze::test_utils::FakeTxProver {};
+
+            self.wallet
+                .send_to_address(prover, false, addrs, None, None, None, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_consolidate(&self, count: Option<usize>) -> Result<Vec<String>, String> {
+        self.touch_auto_lock().await;
+
+        let address = self.consolidation_address(None).await?;
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let prover = crate::blaze::test_utils::FakeTxProver {};
+
+            self.wallet
+                .consolidate(prover, &address, count, None, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result?;
+        Ok(self.wallet.get_send_progress().await.txids)
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_auto_shield(&self) -> Option<JsonValue> {
+        self.auto_shield(|| Ok(crate::blaze::test_utils::FakeTxProver {})).await
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_sync(&self, print_updates: bool) -> Result<JsonValue, String> {
+        self.sync_and_auto_shield(print_updates, || Ok(crate::blaze::test_utils::FakeTxProver {}))
+            .await
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_shield(&self, address: &str) -> Result<String, String> {
+        self.touch_auto_lock().await;
+
+        let utxos = self.wallet.get_spendable_utxos().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let prover = crate::blaze::test_utils::FakeTxProver {};
+
+            self.wallet
+                .shield(prover, address, utxos, None, |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_send_from(
+        &self,
+        addrs: Vec<(&str, u64, Option<String>)>,
+        from: SendFrom,
+    ) -> Result<String, String> {
+        self.touch_auto_lock().await;
+
+        let result = {
+            let _lock = self.sync_lock.lock().await;
+            let prover = crate::blaze::test_utils::FakeTxProver {};
+
+            self.wallet
+                .send_to_address(prover, false, addrs, None, None, Some(&from), |txbytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
+                })
+                .await
+        };
+        self.finish_spend().await;
+
+        result.map(|(txid, _)| txid)
+    }
+
+    #[cfg(test)]
+    pub async fn test_do_send_max(
+        &self,
+        to: &str,
+        source: SendMaxSource,
+        fee: Option<u64>,
+    ) -> Result<String, String> {
+        self.touch_auto_lock().await;
 
         let result = {
             let _lock = self.sync_lock.lock().await;
             let prover = crate::blaze::test_utils::FakeTxProver {};
 
             self.wallet
-                .send_to_address(prover, false, addrs, |txbytes| {
+                .send_max(prover, to, None, &source, fee, |txbytes| {
                     GrpcConnector::send_transaction(self.get_server_uri(), txbytes)
                 })
                 .await
         };
+        self.finish_spend().await;
 
         result.map(|(txid, _)| txid)
     }
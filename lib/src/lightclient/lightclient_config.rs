@@ -26,6 +26,7 @@ use crate::{grpc_connector::GrpcConnector, lightclient::checkpoints};
 pub const DEFAULT_SERVER: &str = "http://localhost:9067";
 pub const WALLET_NAME: &str = "bitcoinz-light-wallet.dat";
 pub const LOGFILE_NAME: &str = "bitcoinz-light-wallet.debug.log";
+pub const BLOCK_CACHE_DIR: &str = "blockcache";
 pub const DEFAULT_ANCHOR_OFFSET: u32 = 1;
 pub const MAX_REORG: usize = 100;
 pub const GAP_RULE_UNUSED_ADDRESSES: usize = if cfg!(any(target_os = "ios", target_os = "android")) {
@@ -294,6 +295,13 @@ impl<P: consensus::Parameters> LightClientConfig<P> {
         Ok(backup_file_str)
     }
 
+    pub fn get_block_cache_path(&self) -> Box<Path> {
+        let mut cache_path = self.get_zcash_data_path().into_path_buf();
+        cache_path.push(BLOCK_CACHE_DIR);
+
+        cache_path.into_boxed_path()
+    }
+
     pub fn get_log_path(&self) -> Box<Path> {
         let mut log_path = self.get_zcash_data_path().into_path_buf();
         log_path.push(LOGFILE_NAME);
@@ -1,19 +1,25 @@
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use ff::{Field, PrimeField};
+use futures::future::BoxFuture;
 use group::GroupEncoding;
 use json::JsonValue;
 use rand::rngs::OsRng;
 use rand::RngCore;
 use tempdir::TempDir;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::runtime::Runtime;
+use tokio::sync::mpsc::UnboundedReceiver;
 use tonic::transport::Channel;
 use tonic::Request;
 
 use zcash_client_backend::address::RecipientAddress;
 use zcash_client_backend::encoding::{
-    encode_extended_full_viewing_key, encode_extended_spending_key, encode_payment_address,
+    decode_extended_spending_key, encode_extended_full_viewing_key, encode_extended_spending_key,
+    encode_payment_address,
 };
 use zcash_note_encryption::{EphemeralKeyBytes, NoteEncryption};
 use zcash_primitives::consensus::{BlockHeight, BranchId, TEST_NETWORK};
@@ -30,15 +36,26 @@ use zcash_primitives::transaction::components::{OutputDescription, GROTH_PROOF_S
 use zcash_primitives::transaction::{Transaction, TransactionData};
 use zcash_primitives::zip32::{ExtendedFullViewingKey, ExtendedSpendingKey};
 
+use crate::blaze::block_cache::{CompactBlockCache, CACHE_MIN_DEPTH};
 use crate::blaze::fetch_full_tx::FetchFullTxns;
-use crate::blaze::test_utils::{FakeCompactBlockList, FakeTransaction};
+use crate::blaze::sync_status::SyncControl;
+use crate::blaze::test_utils::{FakeCompactBlockList, FakeTransaction, FakeTxProver};
 use crate::compact_formats::compact_tx_streamer_client::CompactTxStreamerClient;
 
 use crate::compact_formats::{CompactSaplingOutput, CompactTx, Empty};
 use crate::lightclient::faketx::new_transactiondata;
 use crate::lightclient::test_server::{create_test_server, mine_pending_blocks, mine_random_blocks};
-use crate::lightclient::LightClient;
-use crate::lightwallet::data::WalletTx;
+use crate::lightclient::{LightClient, SyncPass, UnlockDuration};
+use crate::lightwallet::data::{CoinSelection, SendFrom, SendMaxSource, WalletTx};
+use crate::lightwallet::events::{WalletEvent, WalletEvents};
+use crate::lightwallet::fee::FeePolicy;
+use crate::lightwallet::multisig::PartiallySignedTx;
+use crate::lightwallet::offline::UnsignedTx;
+use crate::lightwallet::privacy::PrivacyPolicy;
+use crate::lightwallet::proposal::MAX_TX_SIZE;
+use crate::lightwallet::signer::{self, Approver, KeysSigner, PendingApprovals};
+use crate::lightwallet::sync_order::SyncOrder;
+use crate::lightwallet::{slip39, LightWallet, WalletOptions};
 
 use super::checkpoints;
 use super::lightclient_config::{LightClientConfig, UnitTestNetwork};
@@ -717,7 +734,7 @@ async fn t_incoming_t_outgoing() {
     // 2. Get an incoming tx to a t address
     let sk = lc.wallet.keys().read().await.tkeys[0].clone();
     let pk = sk.pubkey().unwrap();
-    let taddr = sk.address;
+    let taddr = sk.address.clone();
     let value = 100_000;
 
     let mut ftx = FakeTransaction::new();
@@ -826,7 +843,7 @@ async fn mixed_txn() {
     // 3. Send an incoming t-address txn
     let sk = lc.wallet.keys().read().await.tkeys[0].clone();
     let pk = sk.pubkey().unwrap();
-    let taddr = sk.address;
+    let taddr = sk.address.clone();
     let tvalue = 200_000;
 
     let mut ftx = FakeTransaction::new();
@@ -928,7 +945,7 @@ async fn aborted_resync() {
     // 3. Send an incoming t-address txn
     let sk = lc.wallet.keys().read().await.tkeys[0].clone();
     let pk = sk.pubkey().unwrap();
-    let taddr = sk.address;
+    let taddr = sk.address.clone();
     let tvalue = 200_000;
 
     let mut ftx = FakeTransaction::new();
@@ -1043,7 +1060,7 @@ async fn no_change() {
     // 3. Send an incoming t-address txn
     let sk = lc.wallet.keys().read().await.tkeys[0].clone();
     let pk = sk.pubkey().unwrap();
-    let taddr = sk.address;
+    let taddr = sk.address.clone();
     let tvalue = 200_000;
 
     let mut ftx = FakeTransaction::new();
@@ -1435,6 +1452,1889 @@ async fn mempool_and_balance() {
     h1.await.unwrap();
 }
 
+#[tokio::test]
+async fn encrypted_wallet_roundtrip() {
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+    let lc = LightClient::test_new(&config, Some(TEST_SEED.to_string()), 0).await.unwrap();
+
+    let seed = lc.do_seed_phrase().await.unwrap()["seed"].as_str().unwrap().to_string();
+
+    // 1. Encrypting locks the wallet, and the wrong password can't unlock it
+    lc.wallet.encrypt("password".to_string()).await.unwrap();
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+    assert!(lc.wallet.unlock("wrong password".to_string()).await.is_err());
+
+    // 2. The KDF params are saved with the wallet, so a re-read wallet can be unlocked
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    assert!(wallet.is_encrypted().await);
+    assert!(!wallet.is_unlocked_for_spending().await);
+
+    wallet.unlock("password".to_string()).await.unwrap();
+    assert_eq!(*wallet.keys().read().await.get_seed_phrase(), seed);
+}
+
+#[tokio::test]
+async fn change_password_never_writes_plaintext() {
+    let temp_dir = TempDir::new("test").unwrap();
+    let data_dir = temp_dir
+        .into_path()
+        .canonicalize()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, Some(data_dir));
+    let lc = LightClient::test_new(&config, Some(TEST_SEED.to_string()), 0).await.unwrap();
+
+    // Import a spending key, so we also check imported keys, which are stored encrypted
+    let sk = "secret-extended-key-main1q0p44m9zqqqqpqyxfvy5w2vq6ahvxyrwsk2w4h2zleun4cft4llmnsjlv77lhuuknv6x9jgu5g2clf3xq0wz9axxxq8klvv462r5pa32gjuj5uhxnvps6wsrdg6xll05unwks8qpgp4psmvy5e428uxaggn4l29duk82k3sv3njktaaj453fdmfmj2fup8rls4egqxqtj2p5a3yt4070khn99vzxj5ag5qjngc4v2kq0ctl9q2rpc2phu4p3e26egu9w88mchjf83sqgh3cev";
+    lc.do_import_key(sk.to_string(), 0).await.unwrap();
+
+    let seed = lc.do_seed_phrase().await.unwrap()["seed"].as_str().unwrap().to_string();
+    let entropy = bip39::Mnemonic::from_phrase(TEST_SEED, bip39::Language::English)
+        .unwrap()
+        .entropy()
+        .to_vec();
+    let mut sk_bytes = vec![];
+    decode_extended_spending_key(config.hrp_sapling_private_key(), sk)
+        .unwrap()
+        .unwrap()
+        .write(&mut sk_bytes)
+        .unwrap();
+
+    let has_secrets = |bytes: &[u8]| {
+        bytes.windows(entropy.len()).any(|w| w == &entropy[..])
+            || bytes.windows(sk_bytes.len()).any(|w| w == &sk_bytes[..])
+    };
+
+    // 1. Encrypt and save the wallet
+    lc.wallet.encrypt("old password".to_string()).await.unwrap();
+    lc.do_save(true).await.unwrap();
+    let encrypted_bytes = fs::read(config.get_wallet_path()).unwrap();
+    assert!(!has_secrets(&encrypted_bytes));
+
+    // 2. The wrong password doesn't change anything
+    assert!(lc
+        .do_change_password("wrong password".to_string(), "new password".to_string())
+        .await
+        .is_err());
+    assert_eq!(fs::read(config.get_wallet_path()).unwrap(), encrypted_bytes);
+
+    // 3. Change the password of the locked wallet. The saved wallet is never decrypted.
+    lc.do_change_password("old password".to_string(), "new password".to_string())
+        .await
+        .unwrap();
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+
+    let wallet_bytes = fs::read(config.get_wallet_path()).unwrap();
+    assert_ne!(wallet_bytes, encrypted_bytes);
+    assert!(!has_secrets(&wallet_bytes));
+
+    // 4. Only the new password unlocks the saved wallet, and all the keys are intact
+    let wallet = LightWallet::read(&wallet_bytes[..], &config).await.unwrap();
+    assert!(wallet.unlock("old password".to_string()).await.is_err());
+    wallet.unlock("new password".to_string()).await.unwrap();
+    assert_eq!(*wallet.keys().read().await.get_seed_phrase(), seed);
+    assert_eq!(wallet.keys().read().await.get_z_private_keys()[1].1, sk.to_string());
+
+    // 5. An unlocked wallet can change its password too, and is written locked when it is saved
+    lc.wallet.unlock("new password".to_string()).await.unwrap();
+    lc.do_change_password("new password".to_string(), "newer password".to_string())
+        .await
+        .unwrap();
+
+    let wallet_bytes = fs::read(config.get_wallet_path()).unwrap();
+    assert!(!has_secrets(&wallet_bytes));
+    let wallet = LightWallet::read(&wallet_bytes[..], &config).await.unwrap();
+    wallet.unlock("newer password".to_string()).await.unwrap();
+}
+
+#[tokio::test]
+async fn auto_lock() {
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+    let lc = LightClient::test_new(&config, Some(TEST_SEED.to_string()), 0).await.unwrap();
+    lc.wallet.encrypt("password".to_string()).await.unwrap();
+
+    // 1. Unlocking until explicitly locked never auto-locks without an idle timeout
+    lc.do_unlock("password".to_string(), UnlockDuration::UntilLocked).await.unwrap();
+    lc.auto_lock.write().await.last_activity -= 1000;
+    assert!(!lc.check_auto_lock().await);
+    assert!(lc.wallet.is_unlocked_for_spending().await);
+
+    // 2. ...but does once the wallet has been idle for longer than the timeout
+    lc.wallet.set_auto_lock_timeout(60).await;
+    assert!(lc.check_auto_lock().await);
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+    lc.wallet.set_auto_lock_timeout(0).await;
+
+    // 3. A timed unlock expires by itself
+    lc.do_unlock("password".to_string(), UnlockDuration::Seconds(30)).await.unwrap();
+    assert!(!lc.check_auto_lock().await);
+    assert_eq!(lc.do_encryption_status().await["unlock_mode"], "timed");
+    lc.auto_lock.write().await.unlocked_at -= 31;
+    assert!(lc.check_auto_lock().await);
+    assert_eq!(lc.do_encryption_status().await["locked"], true);
+
+    // 4. A one-send unlock is locked again after a send, even a failed one
+    lc.do_unlock("password".to_string(), UnlockDuration::OneSend).await.unwrap();
+    assert!(lc.test_do_send(vec![(EXT_ZADDR, 1000, None)]).await.is_err());
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+
+    // 5. The wrong password doesn't unlock
+    assert!(lc.do_unlock("wrong password".to_string(), UnlockDuration::OneSend).await.is_err());
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+
+    // 6. Without an idle timeout, nothing would lock a wallet unlocked until it's locked, so saving locks it
+    lc.do_unlock("password".to_string(), UnlockDuration::UntilLocked).await.unwrap();
+    lc.do_save_to_buffer().await.unwrap();
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+
+    // With one, saving leaves the wallet unlocked for as long as it was unlocked for, but always writes it locked
+    let entropy = bip39::Mnemonic::from_phrase(TEST_SEED, bip39::Language::English)
+        .unwrap()
+        .entropy()
+        .to_vec();
+    lc.wallet.set_auto_lock_timeout(600).await;
+    lc.do_unlock("password".to_string(), UnlockDuration::UntilLocked).await.unwrap();
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    assert!(lc.wallet.is_unlocked_for_spending().await);
+    assert!(!buf.windows(entropy.len()).any(|w| w == &entropy[..]));
+
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    assert!(!wallet.is_unlocked_for_spending().await);
+    wallet.unlock("password".to_string()).await.unwrap();
+
+    // ...and locks it once a timed unlock has expired
+    lc.wallet.lock().await.unwrap();
+    lc.do_unlock("password".to_string(), UnlockDuration::Seconds(30)).await.unwrap();
+    lc.auto_lock.write().await.unlocked_at -= 31;
+    lc.do_save_to_buffer().await.unwrap();
+    assert!(!lc.wallet.is_unlocked_for_spending().await);
+}
+
+#[tokio::test]
+async fn file_encryption() {
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+    let lc = LightClient::test_new(&config, Some(TEST_SEED.to_string()), 0).await.unwrap();
+
+    let seed = lc.do_seed_phrase().await.unwrap()["seed"].as_str().unwrap().to_string();
+    let taddr = lc.wallet.keys().read().await.tkeys[0].address.clone();
+    let contains_taddr = |buf: &[u8]| buf.windows(taddr.len()).any(|w| w == taddr.as_bytes());
+
+    // 1. The file can only be encrypted once the wallet is, and with the wallet's password
+    assert!(lc.wallet.set_file_encryption("password".to_string(), true).await.is_err());
+    lc.wallet.encrypt("password".to_string()).await.unwrap();
+    assert!(contains_taddr(&lc.do_save_to_buffer().await.unwrap()));
+    assert!(lc.wallet.set_file_encryption("wrong".to_string(), true).await.is_err());
+
+    // 2. Once enabled, even the addresses are no longer readable from the file
+    lc.wallet.set_file_encryption("password".to_string(), true).await.unwrap();
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    assert!(!contains_taddr(&buf));
+
+    // 3. The wallet reads back sealed, without keys, and is written back unchanged
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    assert!(wallet.is_sealed().await);
+    assert!(wallet.is_encrypted().await);
+    assert!(!wallet.is_unlocked_for_spending().await);
+    assert_eq!(wallet.keys().read().await.tkeys.len(), 0);
+
+    let mut buf2 = vec![];
+    wallet.write(&mut buf2).await.unwrap();
+    assert_eq!(buf, buf2);
+
+    // 4. Unlocking loads the keys and history
+    assert!(wallet.unlock("wrong".to_string()).await.is_err());
+    assert!(wallet.is_sealed().await);
+    wallet.unlock("password".to_string()).await.unwrap();
+    assert!(!wallet.is_sealed().await);
+    assert!(wallet.is_file_encrypted().await);
+    assert_eq!(wallet.keys().read().await.tkeys[0].address, taddr);
+    assert_eq!(*wallet.keys().read().await.get_seed_phrase(), seed);
+
+    // 5. Changing the password re-seals the file under the new password
+    wallet.lock().await.unwrap();
+    wallet
+        .change_password("password".to_string(), "new password".to_string())
+        .await
+        .unwrap();
+    let mut buf3 = vec![];
+    wallet.write(&mut buf3).await.unwrap();
+    assert!(!contains_taddr(&buf3));
+
+    let wallet = LightWallet::read(&buf3[..], &config).await.unwrap();
+    assert!(wallet.unlock("password".to_string()).await.is_err());
+    wallet.unlock("new password".to_string()).await.unwrap();
+
+    // 6. Turning it off writes the history in the clear again, with the keys still encrypted
+    wallet.lock().await.unwrap();
+    wallet.set_file_encryption("new password".to_string(), false).await.unwrap();
+    let mut buf4 = vec![];
+    wallet.write(&mut buf4).await.unwrap();
+    assert!(contains_taddr(&buf4));
+
+    let wallet = LightWallet::read(&buf4[..], &config).await.unwrap();
+    assert!(!wallet.is_sealed().await);
+    assert!(wallet.is_encrypted().await);
+}
+
+#[tokio::test]
+async fn seed_passphrase() {
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+    let new_wallet = |passphrase: &str| {
+        LightWallet::new(config.clone(), Some(TEST_SEED.to_string()), passphrase.to_string(), 0, 1, 1).unwrap()
+    };
+
+    let plain = new_wallet("");
+    let wallet = new_wallet("passphrase");
+
+    // 1. The same seed phrase with a passphrase derives a different wallet
+    let taddrs = wallet.keys().read().await.get_all_taddrs();
+    let zaddrs = wallet.keys().read().await.get_all_zaddresses();
+    assert_ne!(plain.keys().read().await.get_all_taddrs(), taddrs);
+    assert_ne!(plain.keys().read().await.get_all_zaddresses(), zaddrs);
+
+    // 2. Restoring with the same passphrase derives the same addresses, including new ones
+    let restored = new_wallet("passphrase");
+    assert_eq!(restored.keys().read().await.get_all_zaddresses(), zaddrs);
+    assert_eq!(
+        wallet.keys().write().await.add_zaddr(),
+        restored.keys().write().await.add_zaddr()
+    );
+    let zaddrs = wallet.keys().read().await.get_all_zaddresses();
+
+    // 3. The passphrase is saved with the wallet, encrypted along with the seed
+    wallet.encrypt("password".to_string()).await.unwrap();
+    assert_eq!(*wallet.keys().read().await.get_passphrase(), "");
+
+    let mut buf = vec![];
+    wallet.write(&mut buf).await.unwrap();
+    assert!(!buf.windows(10).any(|w| w == b"passphrase"));
+
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    wallet.unlock("password".to_string()).await.unwrap();
+    assert_eq!(*wallet.keys().read().await.get_passphrase(), "passphrase");
+    assert_eq!(wallet.keys().read().await.get_all_zaddresses(), zaddrs);
+    assert_eq!(wallet.keys().write().await.add_taddr(), restored.keys().write().await.add_taddr());
+}
+
+#[tokio::test]
+async fn seed_shares() {
+    let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+    let lc = LightClient::test_new(&config, Some(TEST_SEED.to_string()), 0).await.unwrap();
+
+    let shares = lc.do_seed_shares(2, 3).await.unwrap();
+    assert_eq!(shares["threshold"].as_u8().unwrap(), 2);
+    let shares = shares["shares"]
+        .members()
+        .map(|s| s.as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(shares.len(), 3);
+
+    // Any 2 shares give back the seed phrase, and so the same addresses
+    let phrase = slip39::combine_shares(&[shares[2].clone(), shares[0].clone()]).unwrap();
+    assert_eq!(*phrase, TEST_SEED);
+
+    let restored = LightWallet::new(config.clone(), Some(phrase.to_string()), "".to_string(), 0, 1, 1).unwrap();
+    assert_eq!(
+        restored.keys().read().await.get_all_zaddresses(),
+        lc.wallet.keys().read().await.get_all_zaddresses()
+    );
+    assert_eq!(
+        restored.keys().read().await.get_all_taddrs(),
+        lc.wallet.keys().read().await.get_all_taddrs()
+    );
+
+    // Shares can't be exported from a locked wallet
+    lc.wallet.encrypt("password".to_string()).await.unwrap();
+    assert!(lc.do_seed_shares(2, 3).await.is_err());
+}
+
+#[tokio::test]
+async fn fee_policy() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Mine 10 blocks and receive some funds, and then confirm them
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let value = 100_000;
+    fcbl.add_tx_paying(&extfvk1, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // 2. Under a ZIP-317 policy, one spend paying one recipient and change is charged for the 2 grace actions
+    lc.wallet.set_fee_policy(FeePolicy::zip317()).await;
+    assert_eq!(lc.wallet.verified_zbalance_fee(1).await, 10_000);
+
+    let sent_value = 2000;
+    let sent_txid = lc.test_do_send(vec![(EXT_ZADDR, sent_value, None)]).await.unwrap();
+
+    let list = lc.do_list_transactions(false).await;
+    let jv = list.members().find(|jv| jv["txid"] == sent_txid).unwrap();
+    assert_eq!(jv["amount"].as_i64().unwrap(), -(sent_value as i64 + 10_000));
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    assert_eq!(lc.wallet.verified_zbalance(None).await, value - sent_value - 10_000);
+
+    // 3. A fixed fee policy is used as is, and survives a save and load of the options
+    lc.wallet.set_fee_policy(FeePolicy::Fixed(2_000)).await;
+    let mut options = vec![];
+    lc.wallet.wallet_options.read().await.write(&mut options).unwrap();
+    assert_eq!(WalletOptions::read(&options[..]).unwrap().fee_policy, FeePolicy::Fixed(2_000));
+
+    let sent_txid = lc.test_do_send(vec![(EXT_ZADDR, sent_value, None)]).await.unwrap();
+    let list = lc.do_list_transactions(false).await;
+    let jv = list.members().find(|jv| jv["txid"] == sent_txid).unwrap();
+    assert_eq!(jv["amount"].as_i64().unwrap(), -(sent_value as i64 + 2_000));
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn propose_then_send() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive some funds and confirm them
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let value = 100_000;
+    let (tx, _height, _) = fcbl.add_tx_paying(&extfvk1, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // 2. Propose two sends. Nothing is spent yet, and both select the only note
+    let sent_value = 2000;
+    let p1 = lc.do_propose(vec![(EXT_ZADDR, sent_value, None)], None, None, None).await.unwrap();
+    let p2 = lc.do_propose(vec![(EXT_TADDR, sent_value, None)], None, None, None).await.unwrap();
+    assert_eq!(lc.wallet.verified_zbalance(None).await, value);
+
+    assert_eq!(p1["fee"].as_u64().unwrap(), u64::from(DEFAULT_FEE));
+    assert_eq!(p1["total"].as_u64().unwrap(), sent_value);
+    assert_eq!(p1["change"].as_u64().unwrap(), value - sent_value - u64::from(DEFAULT_FEE));
+    assert_eq!(p1["inputs"]["sapling"][0]["txid"], tx.txid().to_string());
+    assert_eq!(p1["inputs"]["transparent"].len(), 0);
+    assert_eq!(p1["privacy"], "shielded");
+    assert_eq!(p2["privacy"], "deshielding");
+    assert_eq!(p2["pools_received"].len(), 2);
+
+    // Discarded proposals can't be sent
+    let p3 = lc.do_propose(vec![(EXT_ZADDR, 1, None)], None, None, None).await.unwrap();
+    let id3 = p3["id"].as_u32().unwrap();
+    lc.do_discard_proposal(id3).await.unwrap();
+    assert!(lc.do_discard_proposal(id3).await.is_err());
+    assert!(lc.test_do_send_proposal(id3).await.is_err());
+
+    // 3. Send the first proposal. It can only be sent once
+    let id1 = p1["id"].as_u32().unwrap();
+    let sent_txid = lc.test_do_send_proposal(id1).await.unwrap();
+    assert!(lc.test_do_send_proposal(id1).await.is_err());
+
+    let list = lc.do_list_transactions(false).await;
+    let jv = list.members().find(|jv| jv["txid"] == sent_txid).unwrap();
+    assert_eq!(jv["amount"].as_i64().unwrap(), -(sent_value as i64 + i64::from(DEFAULT_FEE)));
+
+    // 4. The second proposal's note has been spent, so it is stale
+    let id2 = p2["id"].as_u32().unwrap();
+    assert!(lc.test_do_send_proposal(id2).await.is_err());
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn coin_control() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive two notes in separate txs, and confirm them
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let (tx1, _, _) = fcbl.add_tx_paying(&extfvk1, 50_000);
+    let (tx2, _, _) = fcbl.add_tx_paying(&extfvk1, 30_000);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["unspent_notes"].len(), 2);
+    for note in notes["unspent_notes"].members() {
+        assert_eq!(note["output_index"].as_u32().unwrap(), 0);
+    }
+
+    // 2. Without coin control, the bigger note is picked. Choosing the smaller one spends exactly it
+    let p = lc.do_propose(vec![(EXT_ZADDR, 10_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["inputs"]["sapling"][0]["txid"], tx1.txid().to_string());
+
+    let chosen = CoinSelection {
+        s_notes: vec![(tx2.txid(), 0)],
+        utxos: vec![],
+    };
+    let p = lc
+        .do_propose(vec![(EXT_ZADDR, 10_000, None)], None, Some(chosen.clone()), None)
+        .await
+        .unwrap();
+    assert_eq!(p["inputs"]["sapling"].len(), 1);
+    assert_eq!(p["inputs"]["sapling"][0]["txid"], tx2.txid().to_string());
+
+    // 3. Bad choices are rejected
+    let missing = CoinSelection {
+        s_notes: vec![(tx2.txid(), 5)],
+        utxos: vec![],
+    };
+    assert!(lc.do_propose(vec![(EXT_ZADDR, 10_000, None)], None, Some(missing), None).await.is_err());
+
+    let twice = CoinSelection {
+        s_notes: vec![(tx2.txid(), 0), (tx2.txid(), 0)],
+        utxos: vec![],
+    };
+    assert!(lc.do_propose(vec![(EXT_ZADDR, 10_000, None)], None, Some(twice), None).await.is_err());
+
+    // A note saved by an older version of the wallet, without its output index, can't be chosen until a rescan
+    let txns = lc.wallet.txns();
+    txns.write().await.current.get_mut(&tx1.txid()).unwrap().s_notes[0].output_index = None;
+    let old = CoinSelection {
+        s_notes: vec![(tx1.txid(), 0)],
+        utxos: vec![],
+    };
+    let e = lc
+        .do_propose(vec![(EXT_ZADDR, 10_000, None)], None, Some(old), None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("rescan"));
+    txns.write().await.current.get_mut(&tx1.txid()).unwrap().s_notes[0].output_index = Some(0);
+
+    // The chosen note alone doesn't cover this
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 40_000, None)], None, Some(chosen.clone()), None)
+        .await
+        .is_err());
+
+    // 4. Send it. The chosen note is spent and the other one isn't
+    let sent_txid = lc.test_do_send_proposal(p["id"].as_u32().unwrap()).await.unwrap();
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["pending_notes"].len(), 1);
+    assert_eq!(notes["pending_notes"][0]["created_in_txid"], tx2.txid().to_string());
+    assert_eq!(notes["pending_notes"][0]["unconfirmed_spent"], sent_txid);
+
+    // And it can't be chosen again
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 1_000, None)], None, Some(chosen), None)
+        .await
+        .is_err());
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn freeze_notes_and_utxos() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive two notes and a utxo
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let (tx1, _, _) = fcbl.add_tx_paying(&extfvk1, 50_000);
+    let (tx2, _, _) = fcbl.add_tx_paying(&extfvk1, 30_000);
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let mut ftx = FakeTransaction::new();
+    ftx.add_t_output(&pk, sk.address.clone(), 100_000);
+    let (ttx, _) = fcbl.add_ftx(ftx);
+
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // 2. Freeze the bigger note and the utxo
+    let frozen = CoinSelection {
+        s_notes: vec![(tx1.txid(), 0)],
+        utxos: vec![(ttx.txid(), 0)],
+    };
+    lc.do_freeze(frozen.clone(), true).await.unwrap();
+
+    // Nothing is frozen if any of the inputs isn't in the wallet
+    let missing = CoinSelection {
+        s_notes: vec![(tx2.txid(), 0), (tx2.txid(), 5)],
+        utxos: vec![],
+    };
+    assert!(lc.do_freeze(missing, true).await.is_err());
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), 80_000);
+    assert_eq!(balance["verified_zbalance"].as_u64().unwrap(), 30_000);
+    assert_eq!(balance["spendable_zbalance"].as_u64().unwrap(), 30_000);
+    assert_eq!(balance["frozen_zbalance"].as_u64().unwrap(), 50_000);
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 0);
+    assert_eq!(balance["frozen_tbalance"].as_u64().unwrap(), 100_000);
+
+    let notes = lc.do_list_notes(false).await;
+    for note in notes["unspent_notes"].members() {
+        let is_frozen = note["created_in_txid"] == tx1.txid().to_string();
+        assert_eq!(note["frozen"].as_bool().unwrap(), is_frozen);
+        assert_eq!(note["spendable"].as_bool().unwrap(), !is_frozen);
+    }
+    assert_eq!(notes["utxos"][0]["frozen"].as_bool().unwrap(), true);
+
+    // 3. Frozen funds are never selected, shielded or chosen explicitly
+    let p = lc.do_propose(vec![(EXT_ZADDR, 10_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 0);
+    assert_eq!(p["inputs"]["sapling"].len(), 1);
+    assert_eq!(p["inputs"]["sapling"][0]["txid"], tx2.txid().to_string());
+
+    assert!(lc.do_propose(vec![(EXT_ZADDR, 40_000, None)], None, None, None).await.is_err());
+    assert!(lc.do_shield(None, None, None).await.is_err());
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 10_000, None)], None, Some(frozen.clone()), None)
+        .await
+        .is_err());
+
+    // A proposal whose inputs are frozen afterwards can't be sent
+    let note2 = CoinSelection {
+        s_notes: vec![(tx2.txid(), 0)],
+        utxos: vec![],
+    };
+    lc.do_freeze(note2.clone(), true).await.unwrap();
+    assert!(lc.test_do_send_proposal(p["id"].as_u32().unwrap()).await.is_err());
+    lc.do_freeze(note2, false).await.unwrap();
+
+    // 4. The flag is saved with the wallet
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    assert_eq!(wallet.frozen_zbalance(None).await, 50_000);
+    assert_eq!(wallet.frozen_tbalance(None).await, 100_000);
+
+    // 5. Unfrozen funds can be spent again
+    lc.do_freeze(frozen, false).await.unwrap();
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["verified_zbalance"].as_u64().unwrap(), 80_000);
+    assert_eq!(balance["frozen_zbalance"].as_u64().unwrap(), 0);
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 100_000);
+    assert_eq!(balance["frozen_tbalance"].as_u64().unwrap(), 0);
+
+    let p = lc.do_propose(vec![(EXT_ZADDR, 10_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 1);
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn offline_signing() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    // The online wallet only has the viewing key of the cold wallet
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    let cold_extsk = ExtendedSpendingKey::master(&[2u8; 32]);
+    let cold_extfvk = ExtendedFullViewingKey::from(&cold_extsk);
+    lc.do_import_vk(
+        encode_extended_full_viewing_key(config.hrp_sapling_viewing_key(), &cold_extfvk),
+        1,
+    )
+    .await
+    .unwrap();
+
+    // 1. Unsigned transactions need the witnesses of the viewing key notes
+    assert!(lc
+        .do_create_unsigned_tx(vec![(EXT_ZADDR, 1_000, None)], None, None)
+        .await
+        .is_err());
+    lc.wallet.set_offline_signing(true).await;
+
+    let value = 100_000;
+    let (tx, _, _) = fcbl.add_tx_paying(&cold_extfvk, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // The funds can't be spent by the online wallet itself, but the note's witnesses are kept
+    {
+        let txns = lc.wallet.txns();
+        let txns = txns.read().await;
+        let nd = &txns.current.get(&tx.txid()).unwrap().s_notes[0];
+        assert!(!nd.have_spending_key);
+        assert!(nd.witnesses.len() > 0);
+    }
+    assert_eq!(lc.do_balance().await["zbalance"].as_u64().unwrap(), value);
+    assert_eq!(lc.do_balance().await["spendable_zbalance"].as_u64().unwrap(), 0);
+    assert!(lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.is_err());
+
+    // 2. Create the unsigned transaction, and carry it over as bytes
+    let sent_value = 20_000;
+    let utx = lc
+        .do_create_unsigned_tx(vec![(EXT_ZADDR, sent_value, Some("From cold".to_string()))], None, None)
+        .await
+        .unwrap();
+    assert_eq!(utx.s_spends.len(), 1);
+    assert_eq!(utx.s_spends[0].txid, tx.txid());
+    assert_eq!(utx.fee, u64::from(DEFAULT_FEE));
+    assert_eq!(utx.change, value - sent_value - u64::from(DEFAULT_FEE));
+    assert_eq!(utx.change_address, cold_extfvk.default_address().1);
+
+    let mut buf = vec![];
+    utx.write(&mut buf).unwrap();
+    let id = utx.id();
+    let utx = UnsignedTx::read(&buf[..]).unwrap();
+    assert_eq!(utx.s_spends[0].note.value, value);
+    assert_eq!(utx.id(), id);
+
+    // 3. Sign it offline. A wallet without the spending key can't
+    let offline = LightClient::test_new(&config, None, 0).await.unwrap();
+    assert!(offline.test_do_sign_unsigned_tx(&utx).await.is_err());
+
+    offline
+        .do_import_sk(
+            encode_extended_spending_key(config.hrp_sapling_private_key(), &cold_extsk),
+            1,
+        )
+        .await
+        .unwrap();
+
+    // The change and the outgoing metadata have to go back to the signer's keys
+    let other_extfvk = ExtendedFullViewingKey::from(&ExtendedSpendingKey::master(&[3u8; 32]));
+    let mut bad_change = UnsignedTx::read(&buf[..]).unwrap();
+    bad_change.change_address = other_extfvk.default_address().1;
+    assert!(offline.test_do_sign_unsigned_tx(&bad_change).await.is_err());
+
+    let mut bad_ovk = UnsignedTx::read(&buf[..]).unwrap();
+    bad_ovk.ovk = other_extfvk.fvk.ovk;
+    assert!(offline.test_do_sign_unsigned_tx(&bad_ovk).await.is_err());
+
+    let (signed_txid, raw_tx) = offline.test_do_sign_unsigned_tx(&utx).await.unwrap();
+
+    // 4. Broadcast it from the online wallet, which marks the note as spent
+    let sent_txid = lc.do_broadcast_signed_tx(raw_tx).await.unwrap();
+    assert_eq!(sent_txid, signed_txid);
+
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["pending_notes"][0]["created_in_txid"], tx.txid().to_string());
+    assert_eq!(notes["pending_notes"][0]["unconfirmed_spent"], sent_txid);
+
+    // 5. Mine it. The change comes back to the cold wallet's address
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let list = lc.do_list_transactions(false).await;
+    assert_eq!(list[1]["txid"], sent_txid);
+    assert_eq!(list[1]["outgoing_metadata"][0]["address"], EXT_ZADDR.to_string());
+    assert_eq!(list[1]["outgoing_metadata"][0]["value"].as_u64().unwrap(), sent_value);
+    assert_eq!(
+        lc.do_balance().await["zbalance"].as_u64().unwrap(),
+        value - sent_value - u64::from(DEFAULT_FEE)
+    );
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+const SIGNER_TOKEN: &str = "0123456789abcdef0123";
+
+// Approves every signing request, for signers whose requests don't need checking in a test
+struct ApproveAll;
+
+impl Approver for ApproveAll {
+    fn approve<'a>(&'a self, _utx: &'a UnsignedTx) -> BoxFuture<'a, bool> {
+        Box::pin(async { true })
+    }
+}
+
+// Wait for the signer to get a request, and answer it. Returns the request, as it was shown.
+async fn answer_signing_request(approvals: Arc<PendingApprovals>, approve: bool) -> JsonValue {
+    loop {
+        let requests = approvals.list();
+        if let Some(request) = requests.members().next() {
+            approvals.answer(request["id"].as_str().unwrap(), approve).unwrap();
+            return request.clone();
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn remote_signer() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let value = 100_000;
+    let (tx, _, _) = fcbl.add_tx_paying(&extfvk1, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // Signers have to be on this machine, and need a token that is hard to guess
+    assert!(lc.do_set_remote_signer(Some(("8.8.8.8:9068", SIGNER_TOKEN))).await.is_err());
+    assert!(lc.do_set_remote_signer(Some(("127.0.0.1:9068", "short"))).await.is_err());
+
+    // 1. Once a signer is set, it signs instead of the wallet's own keys. This one doesn't have them.
+    let stranger = LightClient::test_new(&config, None, 0).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let stranger_signer = KeysSigner::new(config.clone(), stranger.wallet.keys(), FakeTxProver {});
+    let h2 = tokio::spawn(async move { signer::serve(listener, SIGNER_TOKEN, &stranger_signer, &ApproveAll).await });
+
+    lc.do_set_remote_signer(Some((&address, SIGNER_TOKEN))).await.unwrap();
+    let e = lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.unwrap_err();
+    assert!(e.contains("refused"));
+    h2.abort();
+
+    // 2. A signer with the spending key signs the transaction, and this wallet broadcasts it
+    let hw = LightClient::test_new(&config, None, 0).await.unwrap();
+    let extsk1 = lc.wallet.keys().read().await.get_extsk_for_extfvk(&extfvk1).unwrap().clone();
+    hw.do_import_sk(encode_extended_spending_key(config.hrp_sapling_private_key(), &extsk1), 1)
+        .await
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let hw_signer = KeysSigner::new(config.clone(), hw.wallet.keys(), FakeTxProver {});
+    let approvals = Arc::new(PendingApprovals::new(config.hrp_sapling_address(), WalletEvents::default()));
+    let hw_approvals = approvals.clone();
+    let h3 = tokio::spawn(async move { signer::serve(listener, SIGNER_TOKEN, &hw_signer, &*hw_approvals).await });
+
+    // A connection that never sends its request doesn't hold up the others
+    let _idle = TcpStream::connect(&address).await.unwrap();
+
+    // Requests without the right token aren't even shown
+    lc.do_set_remote_signer(Some((&address, "not the right token"))).await.unwrap();
+    let e = lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.unwrap_err();
+    assert!(e.contains("token"));
+    assert_eq!(approvals.list().len(), 0);
+
+    // A request that the user rejects isn't signed
+    lc.do_set_remote_signer(Some((&address, SIGNER_TOKEN))).await.unwrap();
+    let answer = tokio::spawn(answer_signing_request(approvals.clone(), false));
+    let e = lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.unwrap_err();
+    assert!(e.contains("rejected"));
+    answer.await.unwrap();
+
+    // The user sees the outputs, fee and change before approving
+    let answer = tokio::spawn(answer_signing_request(approvals.clone(), true));
+    let sent_value = 20_000;
+    let sent_txid = lc
+        .test_do_send(vec![(EXT_ZADDR, sent_value, Some("Signed remotely".to_string()))])
+        .await
+        .unwrap();
+
+    let request = answer.await.unwrap();
+    assert_eq!(request["outputs"][0]["address"], EXT_ZADDR.to_string());
+    assert_eq!(request["outputs"][0]["amount"].as_u64().unwrap(), sent_value);
+    assert_eq!(request["fee"].as_u64().unwrap(), u64::from(DEFAULT_FEE));
+    assert_eq!(request["change"].as_u64().unwrap(), value - sent_value - u64::from(DEFAULT_FEE));
+
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["pending_notes"][0]["created_in_txid"], tx.txid().to_string());
+    assert_eq!(notes["pending_notes"][0]["unconfirmed_spent"], sent_txid);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let list = lc.do_list_transactions(false).await;
+    assert_eq!(list[1]["txid"], sent_txid);
+    assert_eq!(list[1]["outgoing_metadata"][0]["address"], EXT_ZADDR.to_string());
+    assert_eq!(list[1]["outgoing_metadata"][0]["value"].as_u64().unwrap(), sent_value);
+    assert_eq!(
+        lc.do_balance().await["zbalance"].as_u64().unwrap(),
+        value - sent_value - u64::from(DEFAULT_FEE)
+    );
+
+    // 3. Back to signing with the wallet's keys
+    h3.abort();
+    lc.do_set_remote_signer(None).await.unwrap();
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.unwrap();
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn multisig() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    // 1. Both co-signers make the same 2-of-2 address from their keys
+    let cosigner = LightClient::test_new(&config, None, 0).await.unwrap();
+    let pk1 = lc.do_tpubkey(None).await.unwrap()["pubkey"].as_str().unwrap().to_string();
+    let pk2 = cosigner.do_tpubkey(None).await.unwrap()["pubkey"].as_str().unwrap().to_string();
+
+    let address = lc.do_new_multisig_address(2, vec![&pk1, &pk2]).await.unwrap()["address"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        cosigner.do_new_multisig_address(2, vec![&pk1, &pk2]).await.unwrap()["address"],
+        address
+    );
+    assert!(lc.do_new_multisig_address(3, vec![&pk1, &pk2]).await.is_err());
+
+    // 2. Fund it. The funds are tracked, but they aren't part of the wallet's own balance
+    let multisig = lc.wallet.keys().read().await.get_multisig(&address).cloned().unwrap();
+    let value = 100_000;
+    let mut ftx = FakeTransaction::new();
+    ftx.add_t_script_output(multisig.script_pubkey(), address.clone(), value);
+    let (tx, _) = fcbl.add_ftx(ftx);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 0);
+    assert_eq!(balance["multisig_addresses"][0]["address"], address);
+    assert_eq!(balance["multisig_addresses"][0]["balance"].as_u64().unwrap(), value);
+    assert!(lc.test_do_send(vec![(EXT_TADDR, 1_000, None)]).await.is_err());
+
+    // 3. Create the spend. Multisig funds can only go to t addresses
+    assert!(lc
+        .do_create_multisig_tx(&address, vec![(EXT_ZADDR, 1_000)], None)
+        .await
+        .is_err());
+
+    // Everything about a multisig spend is public, so a stricter privacy policy doesn't allow it
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowDeshielding).await;
+    let e = lc
+        .do_create_multisig_tx(&address, vec![(EXT_TADDR, 1_000)], None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("privacy_policy=allowrevealedamounts"));
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowRevealedAmounts).await;
+
+    let sent_value = 20_000;
+    let pstx = lc
+        .do_create_multisig_tx(&address, vec![(EXT_TADDR, sent_value)], None)
+        .await
+        .unwrap();
+    assert_eq!(pstx.inputs.len(), 1);
+    assert_eq!(pstx.inputs[0].txid, tx.txid());
+    assert_eq!(pstx.fee, u64::from(DEFAULT_FEE));
+    assert_eq!(pstx.change, value - sent_value - u64::from(DEFAULT_FEE));
+    assert!(lc.do_broadcast_multisig_tx(&pstx).await.is_err());
+
+    let mut buf = vec![];
+    pstx.write(&mut buf).unwrap();
+
+    // 4. Each co-signer signs their own copy. A wallet without any of the keys can't
+    let stranger = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut copy = PartiallySignedTx::read(&buf[..], &config).unwrap();
+    assert!(stranger.do_sign_multisig_tx(&mut copy).await.is_err());
+
+    let mut ours = PartiallySignedTx::read(&buf[..], &config).unwrap();
+    let mut theirs = PartiallySignedTx::read(&buf[..], &config).unwrap();
+    assert_eq!(lc.do_sign_multisig_tx(&mut ours).await.unwrap(), 1);
+    assert_eq!(cosigner.do_sign_multisig_tx(&mut theirs).await.unwrap(), 1);
+    assert_eq!(ours.signature_count(), 1);
+    assert!(lc.do_broadcast_multisig_tx(&ours).await.is_err());
+
+    // A signature made with the wrong key is rejected
+    let mut forged = PartiallySignedTx::read(&buf[..], &config).unwrap();
+    forged.signatures[0][1] = ours.signatures[0][0].clone();
+    assert!(lc.do_merge_multisig_tx(&mut ours, &forged).is_err());
+
+    // 5. Merge the signatures and broadcast
+    lc.do_merge_multisig_tx(&mut ours, &theirs).unwrap();
+    assert!(ours.is_complete());
+
+    let sent_txid = lc.do_broadcast_multisig_tx(&ours).await.unwrap();
+    let notes = lc.do_list_notes(true).await;
+    assert_eq!(notes["pending_utxos"][0]["created_in_txid"], tx.txid().to_string());
+    assert_eq!(notes["pending_utxos"][0]["unconfirmed_spent"], sent_txid);
+
+    // 6. Mine it. The change comes back to the multisig address
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let balance = lc.do_balance().await;
+    assert_eq!(
+        balance["multisig_addresses"][0]["balance"].as_u64().unwrap(),
+        value - sent_value - u64::from(DEFAULT_FEE)
+    );
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 0);
+
+    let list = lc.do_list_transactions(false).await;
+    let sent = list.members().find(|t| t["txid"] == sent_txid).unwrap();
+    assert_eq!(sent["outgoing_metadata"][0]["address"], EXT_TADDR);
+    assert_eq!(sent["outgoing_metadata"][0]["value"].as_u64().unwrap(), sent_value);
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn send_max() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive two notes and a utxo
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let (tx1, _, _) = fcbl.add_tx_paying(&extfvk1, 50_000);
+    fcbl.add_tx_paying(&extfvk1, 30_000);
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let mut ftx = FakeTransaction::new();
+    ftx.add_t_output(&pk, sk.address.clone(), 100_000);
+    fcbl.add_ftx(ftx);
+
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    // 2. The amount is everything in the source, minus the fee
+    let fee = u64::from(DEFAULT_FEE);
+    let (amount, max_fee, selection) = lc
+        .wallet
+        .max_send_amount(EXT_TADDR, &SendMaxSource::Transparent, None)
+        .await
+        .unwrap();
+    assert_eq!((amount, max_fee), (100_000 - fee, fee));
+    assert_eq!((selection.s_notes.len(), selection.utxos.len()), (0, 1));
+
+    let (amount, _, selection) = lc
+        .wallet
+        .max_send_amount(EXT_TADDR, &SendMaxSource::Sapling, None)
+        .await
+        .unwrap();
+    assert_eq!(amount, 80_000 - fee);
+    assert_eq!((selection.s_notes.len(), selection.utxos.len()), (2, 0));
+
+    let (amount, _, _) = lc
+        .wallet
+        .max_send_amount(EXT_TADDR, &SendMaxSource::Address(sk.address.clone()), None)
+        .await
+        .unwrap();
+    assert_eq!(amount, 100_000 - fee);
+    assert!(lc
+        .wallet
+        .max_send_amount(EXT_TADDR, &SendMaxSource::Address(EXT_ZADDR2.to_string()), None)
+        .await
+        .is_err());
+
+    // A mistyped source isn't taken for an address
+    let addresses = vec![sk.address.clone()];
+    assert_eq!(
+        SendMaxSource::parse(&sk.address, &addresses).unwrap(),
+        SendMaxSource::Address(sk.address.clone())
+    );
+    assert_eq!(
+        SendMaxSource::parse("sapling", &addresses).unwrap(),
+        SendMaxSource::Sapling
+    );
+    assert!(SendMaxSource::parse("transparnt", &addresses).is_err());
+
+    // A note saved by an older version of the wallet, without its output index, isn't silently left out
+    let txns = lc.wallet.txns();
+    txns.write().await.current.get_mut(&tx1.txid()).unwrap().s_notes[0].output_index = None;
+    let e = lc
+        .wallet
+        .max_send_amount(EXT_TADDR, &SendMaxSource::Sapling, None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("rescan"));
+    txns.write().await.current.get_mut(&tx1.txid()).unwrap().s_notes[0].output_index = Some(0);
+
+    // The fee policy is charged for every input, but there is no change output
+    lc.wallet.set_fee_policy(FeePolicy::zip317()).await;
+    let (amount, max_fee, _) = lc
+        .wallet
+        .max_send_amount(EXT_ZADDR, &SendMaxSource::All, None)
+        .await
+        .unwrap();
+    assert_eq!(max_fee, 15_000);
+    assert_eq!(amount, 180_000 - 15_000);
+    lc.wallet.set_fee_policy(FeePolicy::default()).await;
+
+    // 3. Sweep the sapling notes. The amount and fee show up in the send progress
+    let sent_txid = lc
+        .test_do_send_max(EXT_ZADDR, SendMaxSource::Sapling, None)
+        .await
+        .unwrap();
+    let progress = lc.do_send_progress().await.unwrap();
+    assert_eq!(progress["txid"], sent_txid);
+    assert_eq!(progress["amount"].as_u64().unwrap(), 80_000 - fee);
+    assert_eq!(progress["fee"].as_u64().unwrap(), fee);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), 0);
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 100_000);
+
+    let list = lc.do_list_transactions(false).await;
+    let sent = list.members().find(|t| t["txid"] == sent_txid).unwrap();
+    assert_eq!(sent["outgoing_metadata"][0]["address"], EXT_ZADDR);
+    assert_eq!(sent["outgoing_metadata"][0]["value"].as_u64().unwrap(), 80_000 - fee);
+
+    // 4. Sweep the rest with an explicit fee
+    let sent_txid = lc
+        .test_do_send_max(EXT_TADDR, SendMaxSource::All, Some(500))
+        .await
+        .unwrap();
+    assert_eq!(lc.do_send_progress().await.unwrap()["amount"].as_u64().unwrap(), 99_500);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), 0);
+    assert_eq!(balance["tbalance"].as_u64().unwrap(), 0);
+
+    let list = lc.do_list_transactions(false).await;
+    let sent = list.members().find(|t| t["txid"] == sent_txid).unwrap();
+    assert_eq!(sent["outgoing_metadata"][0]["value"].as_u64().unwrap(), 99_500);
+
+    // Nothing is left to send
+    let e = lc.test_do_send_max(EXT_TADDR, SendMaxSource::All, None).await.unwrap_err();
+    assert_eq!(lc.do_send_progress().await.unwrap()["error"], e);
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn send_from_address() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive a note at each of two z addresses, and a utxo
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let zaddr2 = lc.wallet.keys().write().await.add_zaddr();
+    let zaddr1 = lc.wallet.keys().read().await.get_all_zaddresses()[0].clone();
+    let extfvks = lc.wallet.keys().read().await.get_all_extfvks();
+    let (tx1, _, _) = fcbl.add_tx_paying(&extfvks[0], 50_000);
+    fcbl.add_tx_paying(&extfvks[1], 70_000);
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let taddr = sk.address.clone();
+    let mut ftx = FakeTransaction::new();
+    ftx.add_t_output(&pk, taddr.clone(), 100_000);
+    fcbl.add_ftx(ftx);
+
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let from = |address: &str, change_to_source| SendFrom {
+        address: address.to_string(),
+        change_to_source,
+    };
+    let fee = u64::from(DEFAULT_FEE);
+
+    // 2. Only the funds received at the source address are selected
+    let p = lc
+        .do_propose(vec![(EXT_ZADDR, 20_000, None)], None, None, Some(from(&zaddr1, false)))
+        .await
+        .unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 0);
+    assert_eq!(p["inputs"]["sapling"].len(), 1);
+    assert_eq!(p["inputs"]["sapling"][0]["txid"], tx1.txid().to_string());
+    assert!(p["change_address"].is_null());
+
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 60_000, None)], None, None, Some(from(&zaddr1, false)))
+        .await
+        .is_err());
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 1_000, None)], None, None, Some(from(EXT_ZADDR2, false)))
+        .await
+        .is_err());
+
+    // Chosen inputs have to come from the source address too
+    let note1 = CoinSelection {
+        s_notes: vec![(tx1.txid(), 0)],
+        utxos: vec![],
+    };
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 1_000, None)], None, Some(note1), Some(from(&zaddr2, false)))
+        .await
+        .is_err());
+
+    // Change to a t address is paid as a transparent output
+    let p = lc
+        .do_propose(vec![(EXT_ZADDR, 20_000, None)], None, None, Some(from(&taddr, true)))
+        .await
+        .unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 1);
+    assert_eq!(p["inputs"]["sapling"].len(), 0);
+    assert_eq!(p["change_address"], taddr);
+    assert!(p["pools_received"].contains("transparent"));
+
+    // 3. Send from the second z address, with the change going back to it
+    let sent_txid = lc
+        .test_do_send_from(vec![(EXT_ZADDR, 20_000, None)], from(&zaddr2, true))
+        .await
+        .unwrap();
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let notes = lc.do_list_notes(false).await;
+    let change = notes["unspent_notes"]
+        .members()
+        .find(|n| n["created_in_txid"] == sent_txid)
+        .unwrap();
+    assert_eq!(change["address"], zaddr2);
+    assert_eq!(change["value"].as_u64().unwrap(), 70_000 - 20_000 - fee);
+    assert_eq!(lc.wallet.zbalance(Some(zaddr1.clone())).await, 50_000);
+
+    // 4. Send from the t address, with the change going back to it
+    let sent_txid = lc
+        .test_do_send_from(vec![(EXT_TADDR, 20_000, None)], from(&taddr, true))
+        .await
+        .unwrap();
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["utxos"].len(), 1);
+    assert_eq!(notes["utxos"][0]["created_in_txid"], sent_txid);
+    assert_eq!(notes["utxos"][0]["address"], taddr);
+    assert_eq!(notes["utxos"][0]["value"].as_u64().unwrap(), 100_000 - 20_000 - fee);
+    assert_eq!(lc.wallet.zbalance(None).await, 50_000 + 70_000 - 20_000 - fee);
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn split_large_sends() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let taddr = sk.address.clone();
+    let zaddr = lc.wallet.keys().read().await.get_all_zaddresses()[0].clone();
+    let fee = u64::from(DEFAULT_FEE);
+
+    // Lots of small mining payouts, more than fit in one transaction
+    let count = 700;
+    let payout = 10_000;
+    let receive_payouts = |fcbl: &mut FakeCompactBlockList| {
+        let mut ftx = FakeTransaction::new();
+        for _ in 0..count {
+            ftx.add_t_output(&pk, taddr.clone(), payout);
+        }
+        fcbl.add_ftx(ftx);
+    };
+
+    // 1. Shielding them takes two transactions
+    receive_payouts(&mut fcbl);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let txid = lc.test_do_shield(&zaddr).await.unwrap();
+    let progress = lc.do_send_progress().await.unwrap();
+    assert_eq!(progress["transactions"].as_u32().unwrap(), 2);
+    assert_eq!(progress["txids"].len(), 2);
+    assert_eq!(progress["txids"][1], txid);
+    assert_eq!(progress["fee"].as_u64().unwrap(), 2 * fee);
+    assert_eq!(progress["amount"].as_u64().unwrap(), count * payout - 2 * fee);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    assert_eq!(lc.wallet.tbalance(None).await, 0);
+    assert_eq!(lc.wallet.zbalance(None).await, count * payout - 2 * fee);
+
+    // 2. A small payment still fits in one transaction, using only the inputs it needs
+    receive_payouts(&mut fcbl);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let from = SendFrom {
+        address: taddr.clone(),
+        change_to_source: false,
+    };
+    let p = lc
+        .do_propose(vec![(EXT_TADDR, 50_000, None)], None, None, Some(from.clone()))
+        .await
+        .unwrap();
+    assert!(p["inputs"]["transparent"].len() < 10);
+    assert!(p["estimated_size"].as_usize().unwrap() <= MAX_TX_SIZE);
+
+    // A large one has to be split, so it can't be proposed
+    let amount = 6_800_000;
+    assert!(lc
+        .do_propose(vec![(EXT_TADDR, amount, None)], None, None, Some(from.clone()))
+        .await
+        .is_err());
+
+    // 3. Sending it pays the recipient in two parts
+    let txid = lc
+        .test_do_send_from(vec![(EXT_TADDR, amount, None)], from.clone())
+        .await
+        .unwrap();
+    let progress = lc.do_send_progress().await.unwrap();
+    assert_eq!(progress["transactions"].as_u32().unwrap(), 2);
+    assert_eq!(progress["txids"][1], txid);
+    assert_eq!(progress["amount"].as_u64().unwrap(), amount);
+    assert_eq!(progress["fee"].as_u64().unwrap(), 2 * fee);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let list = lc.do_list_transactions(false).await;
+    let paid = progress["txids"]
+        .members()
+        .map(|txid| {
+            let sent = list.members().find(|t| t["txid"] == *txid).unwrap();
+            assert_eq!(sent["outgoing_metadata"][0]["address"], EXT_TADDR);
+            sent["outgoing_metadata"][0]["value"].as_u64().unwrap()
+        })
+        .sum::<u64>();
+    assert_eq!(paid, amount);
+    assert_eq!(
+        lc.wallet.tbalance(None).await + lc.wallet.zbalance(None).await,
+        2 * (count * payout) - 2 * fee - amount - 2 * fee
+    );
+
+    // 4. With a fee per action, only the last transaction pays for the sapling change output
+    receive_payouts(&mut fcbl);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    let marginal_fee = 10;
+    lc.wallet
+        .set_fee_policy(FeePolicy::PerAction {
+            marginal_fee,
+            grace_actions: 2,
+        })
+        .await;
+
+    let tbalance = lc.wallet.tbalance(None).await;
+    let zbalance = lc.wallet.zbalance(None).await;
+    lc.test_do_send_from(vec![(EXT_TADDR, amount, None)], from)
+        .await
+        .unwrap();
+    let progress = lc.do_send_progress().await.unwrap();
+    assert_eq!(progress["transactions"].as_u32().unwrap(), 2);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let inputs = (tbalance - lc.wallet.tbalance(None).await) / payout;
+    let fees = progress["fee"].as_u64().unwrap();
+    assert_eq!(fees, marginal_fee * (inputs + 1));
+    assert_eq!(
+        lc.wallet.zbalance(None).await - zbalance,
+        inputs * payout - amount - fees
+    );
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn auto_shield() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let taddr = sk.address.clone();
+    let zaddr = lc.wallet.keys().read().await.get_all_zaddresses()[0].clone();
+    let fee = u64::from(DEFAULT_FEE);
+
+    // Three mining payouts
+    let mut ftx = FakeTransaction::new();
+    for _ in 0..3 {
+        ftx.add_t_output(&pk, taddr.clone(), 100_000);
+    }
+    fcbl.add_ftx(ftx);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    // 1. Nothing is shielded while auto-shielding is off, or below both the threshold and the utxo count
+    assert!(lc.do_sync(true).await.unwrap()["auto_shield"].is_null());
+    lc.wallet.set_auto_shield_threshold(500_000).await;
+    lc.wallet.set_auto_shield_utxos(4).await;
+    assert!(lc.do_sync(true).await.unwrap()["auto_shield"].is_null());
+    assert!(lc.test_do_auto_shield().await.is_none());
+
+    // 2. The options are saved with the wallet
+    lc.wallet.set_auto_shield_utxos(3).await;
+    lc.wallet.set_auto_shield_address(Some(zaddr.clone())).await;
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    let options = wallet.wallet_options.read().await.clone();
+    assert_eq!(options.auto_shield_threshold, 500_000);
+    assert_eq!(options.auto_shield_utxos, 3);
+    assert_eq!(options.auto_shield_address, Some(zaddr.clone()));
+
+    // 3. A locked wallet isn't unlocked to shield, and neither is a wallet unlocked for one send
+    lc.wallet.encrypt("password".to_string()).await.unwrap();
+    let r = lc.test_do_auto_shield().await.unwrap();
+    assert_eq!(r["result"], "skipped");
+    assert_eq!(r["value"].as_u64().unwrap(), 300_000);
+    assert_eq!(r["utxos"].as_usize().unwrap(), 3);
+
+    lc.do_unlock("password".to_string(), UnlockDuration::OneSend).await.unwrap();
+    assert_eq!(lc.test_do_auto_shield().await.unwrap()["result"], "skipped");
+    assert!(lc.wallet.is_unlocked_for_spending().await);
+
+    // 4. Once unlocked, the utxo count triggers the shielding
+    lc.do_unlock("password".to_string(), UnlockDuration::UntilLocked).await.unwrap();
+    let r = lc.test_do_auto_shield().await.unwrap();
+    assert_eq!(r["result"], "success");
+    assert_eq!(r["address"], zaddr);
+    assert_eq!(lc.do_send_progress().await.unwrap()["txids"][0], r["txid"]);
+
+    // The utxos being shielded aren't shielded again
+    assert!(lc.test_do_auto_shield().await.is_none());
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    assert_eq!(lc.wallet.tbalance(None).await, 0);
+    assert_eq!(lc.wallet.zbalance(None).await, 300_000 - fee);
+    assert!(lc.do_sync(true).await.unwrap()["auto_shield"].is_null());
+
+    // 5. An encrypted wallet that was unlocked is still unlocked after the sync saves it, and shields what came in
+    lc.wallet.lock().await.unwrap();
+    lc.wallet.set_file_encryption("password".to_string(), true).await.unwrap();
+    lc.do_unlock("password".to_string(), UnlockDuration::UntilLocked).await.unwrap();
+
+    let mut ftx = FakeTransaction::new();
+    for _ in 0..3 {
+        ftx.add_t_output(&pk, taddr.clone(), 100_000);
+    }
+    fcbl.add_ftx(ftx);
+    data.write().await.add_blocks(fcbl.into_compact_blocks());
+    data.write().await.add_txns(fcbl.into_txns());
+
+    let r = lc.test_do_sync(true).await.unwrap();
+    assert_eq!(r["auto_shield"]["result"], "success");
+    assert_eq!(r["auto_shield"]["utxos"].as_usize().unwrap(), 3);
+    assert!(lc.wallet.is_unlocked_for_spending().await);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    assert_eq!(lc.wallet.tbalance(None).await, 0);
+    assert_eq!(lc.wallet.zbalance(None).await, 2 * (300_000 - fee));
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn consolidate_notes() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive one large note and five small ones
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    fcbl.add_tx_paying(&extfvk1, 1_000_000);
+    for value in [10_000, 20_000, 30_000, 40_000, 50_000] {
+        fcbl.add_tx_paying(&extfvk1, value);
+    }
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let fee = u64::from(DEFAULT_FEE);
+    let zaddr = lc.wallet.keys().read().await.get_all_zaddresses()[0].clone();
+
+    // 2. A dry run merges the smallest notes first, and sends nothing
+    let preview = lc.do_consolidation_preview(None, Some(5), None).await.unwrap();
+    assert_eq!(preview["address"], zaddr);
+    assert_eq!(preview["notes"].as_usize().unwrap(), 5);
+    assert_eq!(preview["value"].as_u64().unwrap(), 150_000);
+    assert_eq!(preview["fee"].as_u64().unwrap(), fee);
+    assert_eq!(preview["transactions"].len(), 1);
+
+    let preview = lc.do_consolidation_preview(None, None, None).await.unwrap();
+    assert_eq!(preview["notes"].as_usize().unwrap(), 6);
+
+    // Only the wallet's own z-addresses can be consolidated to, and one note can't be merged
+    assert!(lc
+        .do_consolidation_preview(Some(EXT_ZADDR.to_string()), None, None)
+        .await
+        .is_err());
+    assert!(lc.do_consolidation_preview(None, Some(1), None).await.is_err());
+
+    // 3. Merge the five small notes
+    let txids = lc.test_do_consolidate(Some(5)).await.unwrap();
+    assert_eq!(txids.len(), 1);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+
+    let notes = lc.do_list_notes(false).await;
+    assert_eq!(notes["unspent_notes"].len(), 2);
+    assert_eq!(lc.wallet.zbalance(None).await, 1_150_000 - fee);
+    assert!(notes["unspent_notes"]
+        .members()
+        .any(|n| n["value"].as_u64().unwrap() == 150_000 - fee));
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn privacy_policy() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive a note and a utxo
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    fcbl.add_tx_paying(&extfvk1, 100_000);
+
+    let sk = lc.wallet.keys().read().await.tkeys[0].clone();
+    let pk = sk.pubkey().unwrap();
+    let mut ftx = FakeTransaction::new();
+    ftx.add_t_output(&pk, sk.address.clone(), 100_000);
+    fcbl.add_ftx(ftx);
+
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let utxo = lc.wallet.get_utxos().await[0].clone();
+    let utxo_only = CoinSelection {
+        s_notes: vec![],
+        utxos: vec![(utxo.txid, utxo.output_index as u32)],
+    };
+    let zaddr = lc.wallet.keys().read().await.get_all_zaddresses()[0].clone();
+
+    // 2. Fully shielded: the utxo isn't selected, and nothing can be deshielded or shielded
+    lc.wallet.set_privacy_policy(PrivacyPolicy::FullyShielded).await;
+    let p = lc.do_propose(vec![(EXT_ZADDR, 50_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 0);
+    assert_eq!(p["privacy"], "shielded");
+
+    let e = lc
+        .do_propose(vec![(EXT_TADDR, 50_000, None)], None, None, None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("'fullyshielded'") && e.contains("privacy_policy=allowdeshielding"));
+
+    let e = lc.test_do_shield(&zaddr).await.unwrap_err();
+    assert!(e.contains("privacy_policy=allowshielding"));
+
+    // 3. Shielding is allowed, but the utxo still can't go to a t-address
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowShielding).await;
+    assert!(lc
+        .do_propose(vec![(EXT_ZADDR, 50_000, None)], None, Some(utxo_only.clone()), None)
+        .await
+        .is_ok());
+    assert!(lc
+        .do_propose(vec![(EXT_TADDR, 50_000, None)], None, Some(utxo_only.clone()), None)
+        .await
+        .is_err());
+
+    // Spending the utxo together with the note isn't shielding, since it links them
+    let p = lc.do_propose(vec![(EXT_ZADDR, 50_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["privacy"], "shielding");
+    let e = lc
+        .do_propose(vec![(EXT_ZADDR, 150_000, None)], None, None, None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("mixed_inputs") && e.contains("privacy_policy=allowrevealedamounts"));
+
+    // 4. Deshielding sends to t-addresses from the note, without pulling in the utxo
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowDeshielding).await;
+    let p = lc.do_propose(vec![(EXT_TADDR, 50_000, None)], None, None, None).await.unwrap();
+    assert_eq!(p["inputs"]["transparent"].len(), 0);
+    assert_eq!(p["privacy"], "deshielding");
+
+    let e = lc
+        .do_propose(vec![(EXT_TADDR, 50_000, None)], None, Some(utxo_only.clone()), None)
+        .await
+        .unwrap_err();
+    assert!(e.contains("privacy_policy=allowrevealedamounts"));
+
+    // 5. A proposal is checked again when it is sent, in case the policy got stricter
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowRevealedAmounts).await;
+    let p = lc
+        .do_propose(vec![(EXT_TADDR, 50_000, None)], None, Some(utxo_only), None)
+        .await
+        .unwrap();
+    assert_eq!(p["privacy"], "transparent");
+
+    let p_mixed = lc
+        .do_propose(vec![(EXT_ZADDR, 150_000, None)], None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(p_mixed["privacy"], "mixed_inputs");
+
+    lc.wallet.set_privacy_policy(PrivacyPolicy::AllowShielding).await;
+    assert!(lc.test_do_send_proposal(p["id"].as_u32().unwrap()).await.is_err());
+    assert_eq!(lc.wallet.tbalance(None).await, 100_000);
+
+    // The policy is saved with the wallet
+    let buf = lc.do_save_to_buffer().await.unwrap();
+    let wallet = LightWallet::read(&buf[..], &config).await.unwrap();
+    assert_eq!(wallet.privacy_policy().await, PrivacyPolicy::AllowShielding);
+
+    // Done
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn block_cache() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    lc.wallet.set_block_cache_size(10).await;
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    // 1. Receive a note early on, and mine enough blocks on top of it for it to be cached
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let value = 100_000;
+    fcbl.add_tx_paying(&extfvk1, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 150).await;
+    assert_eq!(lc.wallet.last_scanned_height().await, 161);
+
+    // 2. Only the blocks that were deep enough to be safe from reorgs when they were downloaded are in the cache
+    let threshold = lc.wallet.wallet_options.read().await.spam_threshold;
+    let cache = CompactBlockCache::new(&config.get_block_cache_path(), 10_000_000).unwrap();
+    let heights = cache.read_range(1, 161, threshold).keys().copied().collect::<Vec<_>>();
+    assert_eq!(heights, (12..=161 - CACHE_MIN_DEPTH).collect::<Vec<_>>());
+
+    // 3. A rescan reads the cached blocks, downloads the rest and finds the same note
+    let balance = lc.do_balance().await;
+    lc.do_rescan().await.unwrap();
+    assert_eq!(lc.do_balance().await, balance);
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), value);
+    assert_eq!(cache.read_range(1, 161, threshold).len(), 61);
+
+    // 4. Blocks removed from the cache, like after a reorg, are downloaded again
+    cache.remove_from(50).unwrap();
+    assert_eq!(cache.read_range(1, 161, threshold).len(), 49);
+    lc.do_rescan().await.unwrap();
+    assert_eq!(lc.do_balance().await, balance);
+    assert_eq!(cache.read_range(1, 161, threshold).len(), 61);
+
+    // 5. With the cache turned off, nothing more is written to it
+    lc.wallet.set_block_cache_size(0).await;
+    let size = cache.size().unwrap();
+    mine_random_blocks(&mut fcbl, &data, &lc, 100).await;
+    assert_eq!(cache.size().unwrap(), size);
+
+    // Shutdown everything cleanly
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn pause_and_cancel_sync() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    assert_eq!(lc.wallet.last_scanned_height().await, 10);
+
+    // 1. There is nothing to cancel or resume
+    assert!(lc.cancel_sync().await.is_err());
+    assert!(lc.resume_sync().await.is_err());
+
+    // 2. While paused, syncs stop before their first batch and leave the wallet where it was
+    lc.pause_sync().await;
+    assert!(lc.is_sync_paused().await);
+
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let value = 100_000;
+    fcbl.add_tx_paying(&extfvk1, value);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let r = lc.do_sync(false).await.unwrap();
+    assert_eq!(r["result"], "paused");
+    assert_eq!(r["total_blocks_synced"].as_u64().unwrap(), 0);
+    assert_eq!(lc.wallet.last_scanned_height().await, 10);
+    assert!(!lc.do_sync_status().await.in_progress);
+
+    // 3. Resuming syncs the rest of the blocks
+    let r = lc.resume_sync().await.unwrap();
+    assert_eq!(r["result"], "success");
+    assert!(!lc.is_sync_paused().await);
+    assert_eq!(lc.wallet.last_scanned_height().await, 16);
+    assert_eq!(lc.do_balance().await["zbalance"].as_u64().unwrap(), value);
+
+    // 4. A cancel only stops the sync it was asked for. One left over from a sync that had already done its last
+    // batch doesn't stop the next sync.
+    *lc.sync_control.write().await = SyncControl::Cancel;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    assert_eq!(lc.wallet.last_scanned_height().await, 21);
+
+    // Shutdown everything cleanly
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn wallet_events() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    let mut rx = lc.subscribe();
+
+    fn drain(rx: &mut UnboundedReceiver<WalletEvent>) -> Vec<JsonValue> {
+        let mut events = vec![];
+        while let Ok(e) = rx.try_recv() {
+            events.push(e.to_json());
+        }
+        events
+    }
+
+    // 1. A sync with new blocks reports its batch and the new height
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+    let events = drain(&mut rx);
+    let names = events.iter().map(|e| e["event"].as_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["sync_batch_started", "sync_batch_finished", "height_advanced"]);
+    assert_eq!(events[0]["first_block"].as_u64().unwrap(), 1);
+    assert_eq!(events[0]["last_block"].as_u64().unwrap(), 10);
+    assert_eq!(events[2]["height"].as_u64().unwrap(), 10);
+
+    // A sync without new blocks doesn't report anything
+    lc.do_sync(false).await.unwrap();
+    assert!(drain(&mut rx).is_empty());
+
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    fcbl.add_tx_paying(&extfvk1, 100_000);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    drain(&mut rx);
+
+    // 2. Sending reports its progress, and mining the tx reports it confirmed
+    let sent_txid = lc.test_do_send(vec![(EXT_ZADDR, 2000, None)]).await.unwrap();
+    let events = drain(&mut rx);
+    assert!(events.iter().all(|e| e["event"] == "send_progress"));
+    assert_eq!(events.last().unwrap()["txid"], sent_txid);
+
+    fcbl.add_pending_sends(&data).await;
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    let events = drain(&mut rx);
+    let confirmed = events.iter().find(|e| e["event"] == "tx_confirmed").unwrap();
+    assert_eq!(confirmed["txid"], sent_txid);
+    assert_eq!(confirmed["height"].as_u64().unwrap(), 17);
+
+    // 3. A tx that is never mined is reported expired once it is removed from the wallet
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    let sent_txid = lc.test_do_send(vec![(EXT_ZADDR, 2000, None)]).await.unwrap();
+    data.write().await.sent_txns.clear();
+    mine_random_blocks(&mut fcbl, &data, &lc, 105).await;
+
+    let events = drain(&mut rx);
+    let expired = events.iter().find(|e| e["event"] == "tx_expired").unwrap();
+    assert_eq!(expired["txid"], sent_txid);
+    assert!(events.iter().all(|e| e["event"] != "tx_confirmed"));
+
+    // Shutdown everything cleanly
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn witness_after_reorg() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    // 1. The wallet is paid, and syncs a few blocks past the payment
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let (tx, _, _) = fcbl.add_tx_paying(&extfvk1, 100_000);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+    assert_eq!(lc.wallet.last_scanned_height().await, 16);
+
+    // 2. The top block is replaced on the server, and more blocks are mined on top of the new one
+    {
+        let mut data = data.write().await;
+        data.blocks.remove(0);
+        fcbl.next_height = 16;
+        fcbl.prev_hash = data.blocks[0].hash();
+    }
+    let cbs = fcbl.add_blocks(5).into_compact_blocks();
+    data.write().await.add_blocks(cbs);
+
+    // 3. The sync rolls the note's witness back below the replaced block, and brings it up to the wallet's height
+    // again, so the note can still be spent
+    lc.do_sync(false).await.unwrap();
+    assert_eq!(lc.wallet.last_scanned_height().await, 20);
+    assert_eq!(
+        lc.wallet.txns.read().await.current[&tx.txid()].s_notes[0]
+            .witnesses
+            .top_height,
+        20
+    );
+    lc.test_do_send(vec![(EXT_ZADDR, 1_000, None)]).await.unwrap();
+
+    // Shutdown everything cleanly
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
+#[tokio::test]
+async fn recent_first_sync() {
+    let (data, config, ready_rx, stop_tx, h1) = create_test_server(UnitTestNetwork).await;
+    ready_rx.await.unwrap();
+
+    let lc = LightClient::test_new(&config, None, 0).await.unwrap();
+    let mut fcbl = FakeCompactBlockList::new(0);
+    mine_random_blocks(&mut fcbl, &data, &lc, 10).await;
+
+    // 1. Before it goes offline, the wallet is paid twice, and sends from the bigger note
+    let extfvk1 = lc.wallet.keys().read().await.get_all_extfvks()[0].clone();
+    let (value1, value2) = (100_000, 30_000);
+    fcbl.add_tx_paying(&extfvk1, value1);
+    let (tx2, _, _) = fcbl.add_tx_paying(&extfvk1, value2);
+    mine_pending_blocks(&mut fcbl, &data, &lc).await;
+    mine_random_blocks(&mut fcbl, &data, &lc, 5).await;
+
+    let sent_value = 20_000;
+    lc.test_do_send(vec![(EXT_ZADDR, sent_value, None)]).await.unwrap();
+    let change = value1 - sent_value - u64::from(DEFAULT_FEE);
+
+    // 2. While it is offline, the send is mined in one of the 10 newest blocks, and a third payment comes in
+    fcbl.add_blocks(12);
+    fcbl.add_pending_sends(&data).await;
+    let value3 = 50_000;
+    fcbl.add_tx_paying(&extfvk1, value3);
+    fcbl.add_blocks(5);
+    data.write().await.add_blocks(fcbl.into_compact_blocks());
+    data.write().await.add_txns(fcbl.into_txns());
+
+    // 3. The 10 newest blocks are scanned into the wallet first. The payment is provisional, the change isn't
+    // counted as received, and only the notes from the newest blocks can be spent
+    lc.wallet
+        .set_sync_order(SyncOrder::RecentFirst { recent_blocks: 10 })
+        .await;
+    lc.start_sync_batch(36, 0, 36, SyncPass::Recent { first: 27 })
+        .await
+        .unwrap();
+    assert_eq!(lc.wallet.last_scanned_height().await, 36);
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["provisional"], true);
+    assert_eq!(balance["provisional_received"].as_u64().unwrap(), value3);
+    assert_eq!(balance["provisional_spent"].as_u64().unwrap(), value1);
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), value2 + value3 + change);
+
+    let old_note = (tx2.txid(), 0);
+    let inputs = lc.wallet.max_spendable_inputs(&SendMaxSource::Sapling).await.unwrap();
+    assert_eq!(inputs.s_notes.len(), 2);
+    assert!(!inputs.s_notes.contains(&old_note));
+
+    let old = CoinSelection {
+        s_notes: vec![old_note],
+        utxos: vec![],
+    };
+    let e = lc.wallet.chosen_inputs(&old, false).await.err().unwrap();
+    assert!(e.contains("older blocks"));
+
+    // 4. Before the older blocks are backfilled, more blocks are mined, and one of them pays the wallet again
+    let value4 = 40_000;
+    fcbl.add_blocks(2);
+    fcbl.add_tx_paying(&extfvk1, value4);
+    fcbl.add_blocks(1);
+    data.write().await.add_blocks(fcbl.into_compact_blocks());
+    data.write().await.add_txns(fcbl.into_txns());
+
+    // The sync backfills the older blocks without downloading the newest ones again, and then goes on to the tip.
+    // After that, the balance isn't provisional and every note can be spent.
+    let r = lc.do_sync(false).await.unwrap();
+    assert_eq!(r["result"], "success");
+    assert_eq!(lc.wallet.last_scanned_height().await, 40);
+    assert_eq!(lc.do_sync_status().await.done_ranges, vec![(18, 26), (37, 40)]);
+    assert!(lc.wallet.backfill.read().await.is_none());
+
+    let total = value2 + value3 + value4 + change;
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["provisional"], false);
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), total);
+
+    let inputs = lc.wallet.max_spendable_inputs(&SendMaxSource::Sapling).await.unwrap();
+    assert_eq!(inputs.s_notes.len(), 4);
+    assert!(inputs.s_notes.contains(&old_note));
+    assert_eq!(
+        lc.wallet.txns.read().await.current[&tx2.txid()].s_notes[0]
+            .witnesses
+            .top_height,
+        40
+    );
+
+    // 5. In a rescan, the spent note is found in the backfill, and is marked spent by the send in the newest blocks
+    lc.wallet
+        .set_sync_order(SyncOrder::RecentFirst { recent_blocks: 14 })
+        .await;
+    lc.do_rescan().await.unwrap();
+    assert_eq!(lc.do_sync_status().await.done_ranges, vec![(27, 40), (1, 26)]);
+
+    let balance = lc.do_balance().await;
+    assert_eq!(balance["provisional"], false);
+    assert_eq!(balance["zbalance"].as_u64().unwrap(), total);
+    assert_eq!(balance["spendable_zbalance"].as_u64().unwrap(), total);
+
+    // Shutdown everything cleanly
+    stop_tx.send(true).unwrap();
+    h1.await.unwrap();
+}
+
 pub const EXT_TADDR: &str = "t1NoS6ZgaUTpmjkge2cVpXGcySasdYDrXqh";
 pub const EXT_ZADDR: &str = "zs1va5902apnzlhdu0pw9r9q7ca8s4vnsrp2alr6xndt69jnepn2v2qrj9vg3wfcnjyks5pg65g9dc";
 pub const EXT_ZADDR2: &str = "zs1fxgluwznkzm52ux7jkf4st5znwzqay8zyz4cydnyegt2rh9uhr9458z0nk62fdsssx0cqhy6lyv";
@@ -28,15 +28,16 @@ use orchard::{
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use futures::Future;
+use rand::{rngs::OsRng, RngCore};
 use incrementalmerkletree::bridgetree::Checkpoint;
 use incrementalmerkletree::Hashable;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 
 use orchard::Anchor;
 use std::sync::mpsc;
 use std::{
     cmp,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{Error, ErrorKind, Read, Write},
     sync::{atomic::AtomicU64, Arc},
     time::SystemTime,
@@ -44,6 +45,8 @@ use std::{
 use tokio::sync::RwLock;
 use zcash_address::unified::Receiver;
 use zcash_address::unified::{Address as UnifiedAddress, Encoding};
+use zeroize::Zeroizing;
+
 use zcash_client_backend::{
     address,
     encoding::{decode_extended_full_viewing_key, decode_extended_spending_key, encode_payment_address},
@@ -55,27 +58,45 @@ use zcash_primitives::merkle_tree::incremental::{read_bridge, read_leu64_usize,
 use zcash_primitives::merkle_tree::HashSer;
 use zcash_primitives::sapling::prover::TxProver;
 use zcash_primitives::{
-    legacy::Script,
+    keys::OutgoingViewingKey,
     memo::Memo,
-    transaction::{
-        builder::Builder,
-        components::{amount::DEFAULT_FEE, OutPoint, TxOut},
-    },
+    transaction::{builder::Builder, Transaction, TxId},
     zip32::ExtendedFullViewingKey,
 };
 
 use self::data::SpendableOrchardNote;
 use self::{
-    data::{BlockData, SaplingNoteData, Utxo, WalletZecPriceInfo},
+    data::{BlockData, CoinSelection, SaplingNoteData, SendFrom, SendMaxSource, Utxo, WalletZecPriceInfo},
+    envelope::WalletEnvelope,
+    events::{WalletEvent, WalletEvents},
+    fee::FeePolicy,
     keys::Keys,
     message::Message,
+    multisig::PartiallySignedTx,
+    offline::{UnsignedSaplingSpend, UnsignedTx},
+    privacy::PrivacyPolicy,
+    proposal::{estimated_tx_size, TxPrivacy, TxProposal, MAX_TX_SIZE},
+    signer::{KeysSigner, ProverRef, Signer},
+    sync_order::{Backfill, SyncOrder},
     wallet_txns::WalletTxns,
 };
 
 pub(crate) mod data;
+mod envelope;
+pub mod events;
 mod extended_key;
+pub(crate) mod fee;
+mod kdf;
 pub(crate) mod keys;
 pub(crate) mod message;
+pub mod multisig;
+pub mod offline;
+pub(crate) mod privacy;
+pub(crate) mod proposal;
+pub mod secret;
+pub mod signer;
+pub(crate) mod slip39;
+pub(crate) mod sync_order;
 pub(crate) mod utils;
 pub(crate) mod wallet_txns;
 mod walletokey;
@@ -85,6 +106,9 @@ mod walletzkey;
 pub const MERKLE_DEPTH: u8 = 32;
 pub const MAX_CHECKPOINTS: usize = 100;
 
+// A transaction ready to be built: its proposal, and the notes and utxos it spends
+type PlannedTx = (TxProposal, Vec<SpendableSaplingNote>, Vec<Utxo>);
+
 pub fn now() -> u64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -100,6 +124,14 @@ pub struct SendProgress {
     pub total: u32,
     pub last_error: Option<String>,
     pub last_txid: Option<String>,
+
+    // What the transactions being sent pay their recipients, and their fees
+    pub last_amount: Option<u64>,
+    pub last_fee: Option<u64>,
+
+    // A send too large for one transaction is split into several. These are how many, and the ones sent so far.
+    pub tx_count: u32,
+    pub txids: Vec<String>,
 }
 
 impl SendProgress {
@@ -111,6 +143,10 @@ impl SendProgress {
             total: 0,
             last_error: None,
             last_txid: None,
+            last_amount: None,
+            last_fee: None,
+            tx_count: 0,
+            txids: vec![],
         }
     }
 }
@@ -128,10 +164,37 @@ pub enum MemoDownloadOption {
     AllMemos,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct WalletOptions {
     pub(crate) download_memos: MemoDownloadOption,
     pub(crate) spam_threshold: i64,
+
+    // Lock an unlocked encrypted wallet after this many seconds without spending activity. 0 disables it.
+    pub(crate) auto_lock_timeout: u64,
+
+    // How the fee for outgoing transactions is computed
+    pub(crate) fee_policy: FeePolicy,
+
+    // Keep the witnesses of notes received by viewing keys up to date, so this wallet can create unsigned
+    // transactions spending them for an offline signer
+    pub(crate) offline_signing: bool,
+
+    // Shield the confirmed transparent balance after every sync, once it reaches `auto_shield_threshold` zats or
+    // `auto_shield_utxos` utxos. 0 disables a trigger. The funds go to `auto_shield_address`, or to the first
+    // z-address if it isn't set.
+    pub(crate) auto_shield_threshold: u64,
+    pub(crate) auto_shield_utxos: u64,
+    pub(crate) auto_shield_address: Option<String>,
+
+    // What outgoing transactions are allowed to reveal on chain
+    pub(crate) privacy_policy: PrivacyPolicy,
+
+    // Keep up to this many MB of compact blocks in the data directory, so rescans don't download them again.
+    // 0 disables the cache.
+    pub(crate) block_cache_size: u64,
+
+    // The order a sync scans new blocks in
+    pub(crate) sync_order: SyncOrder,
 }
 
 impl Default for WalletOptions {
@@ -139,13 +202,22 @@ impl Default for WalletOptions {
         WalletOptions {
             download_memos: MemoDownloadOption::WalletMemos,
             spam_threshold: -1,
+            auto_lock_timeout: 0,
+            fee_policy: FeePolicy::default(),
+            offline_signing: false,
+            auto_shield_threshold: 0,
+            auto_shield_utxos: 0,
+            auto_shield_address: None,
+            privacy_policy: PrivacyPolicy::default(),
+            block_cache_size: 0,
+            sync_order: SyncOrder::default(),
         }
     }
 }
 
 impl WalletOptions {
     pub fn serialized_version() -> u64 {
-        return 2;
+        return 9;
     }
 
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
@@ -169,19 +241,90 @@ impl WalletOptions {
             reader.read_i64::<LittleEndian>()?
         };
 
+        let auto_lock_timeout = if version <= 2 {
+            0
+        } else {
+            reader.read_u64::<LittleEndian>()?
+        };
+
+        let fee_policy = if version <= 3 {
+            FeePolicy::default()
+        } else {
+            FeePolicy::read(&mut reader)?
+        };
+
+        let offline_signing = if version <= 4 { false } else { reader.read_u8()? > 0 };
+
+        let (auto_shield_threshold, auto_shield_utxos, auto_shield_address) = if version <= 5 {
+            (0, 0, None)
+        } else {
+            (
+                reader.read_u64::<LittleEndian>()?,
+                reader.read_u64::<LittleEndian>()?,
+                Optional::read(&mut reader, |r| utils::read_string(r))?,
+            )
+        };
+
+        let privacy_policy = if version <= 6 {
+            PrivacyPolicy::default()
+        } else {
+            PrivacyPolicy::read(&mut reader)?
+        };
+
+        let block_cache_size = if version <= 7 {
+            0
+        } else {
+            reader.read_u64::<LittleEndian>()?
+        };
+
+        let sync_order = if version <= 8 {
+            SyncOrder::default()
+        } else {
+            SyncOrder::read(&mut reader)?
+        };
+
         Ok(Self {
             download_memos,
             spam_threshold,
+            auto_lock_timeout,
+            fee_policy,
+            offline_signing,
+            auto_shield_threshold,
+            auto_shield_utxos,
+            auto_shield_address,
+            privacy_policy,
+            block_cache_size,
+            sync_order,
         })
     }
 
+    pub fn auto_shield_enabled(&self) -> bool {
+        self.auto_shield_threshold > 0 || self.auto_shield_utxos > 0
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         // Write the version
         writer.write_u64::<LittleEndian>(Self::serialized_version())?;
 
         writer.write_u8(self.download_memos as u8)?;
 
-        writer.write_i64::<LittleEndian>(self.spam_threshold)
+        writer.write_i64::<LittleEndian>(self.spam_threshold)?;
+
+        writer.write_u64::<LittleEndian>(self.auto_lock_timeout)?;
+
+        self.fee_policy.write(&mut writer)?;
+
+        writer.write_u8(if self.offline_signing { 1 } else { 0 })?;
+
+        writer.write_u64::<LittleEndian>(self.auto_shield_threshold)?;
+        writer.write_u64::<LittleEndian>(self.auto_shield_utxos)?;
+        Optional::write(&mut writer, self.auto_shield_address.as_ref(), |w, a| utils::write_string(w, a))?;
+
+        self.privacy_policy.write(&mut writer)?;
+
+        writer.write_u64::<LittleEndian>(self.block_cache_size)?;
+
+        self.sync_order.write(&mut writer)
     }
 }
 
@@ -211,26 +354,46 @@ pub struct LightWallet<P> {
     // The Orchard incremental tree
     pub(crate) orchard_witnesses: Arc<RwLock<Option<BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>>>>,
 
+    // The older blocks a recent-first sync hasn't scanned yet, if any
+    pub(crate) backfill: Arc<RwLock<Option<Backfill>>>,
+
     // Progress of an outgoing tx
     send_progress: Arc<RwLock<SendProgress>>,
 
+    // Subscribers to the wallet's events
+    pub(crate) events: WalletEvents,
+
+    // Transaction proposals that haven't been sent or discarded yet, by id
+    proposals: Arc<RwLock<HashMap<u32, TxProposal>>>,
+
+    // Authorizes outgoing transactions instead of the keys in the wallet, if one has been set
+    signer: Arc<RwLock<Option<Arc<dyn Signer>>>>,
+
     // The current price of ZEC. (time_fetched, price in USD)
     pub price: Arc<RwLock<WalletZecPriceInfo>>,
+
+    // If the whole wallet file is encrypted, the envelope used to re-seal it on every write
+    envelope: Arc<RwLock<Option<WalletEnvelope>>>,
+
+    // The still encrypted wallet, if the wallet file is encrypted and it hasn't been unlocked since it was read.
+    // Until then, the keys and history above are empty.
+    sealed: Arc<RwLock<Option<Vec<u8>>>>,
 }
 
 impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
     pub fn serialized_version() -> u64 {
-        return 25;
+        return 27;
     }
 
     pub fn new(
         config: LightClientConfig<P>,
         seed_phrase: Option<String>,
+        passphrase: String,
         height: u64,
         num_zaddrs: u32,
         num_oaddrs: u32,
     ) -> io::Result<Self> {
-        let keys = Keys::new(&config, seed_phrase, num_zaddrs, num_oaddrs)
+        let keys = Keys::new(&config, seed_phrase, passphrase, num_zaddrs, num_oaddrs)
             .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
 
         Ok(Self {
@@ -240,13 +403,41 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             wallet_options: Arc::new(RwLock::new(WalletOptions::default())),
             config,
             orchard_witnesses: Arc::new(RwLock::new(None)),
+            backfill: Arc::new(RwLock::new(None)),
             birthday: AtomicU64::new(height),
             verified_tree: Arc::new(RwLock::new(None)),
             send_progress: Arc::new(RwLock::new(SendProgress::new(0))),
+            events: WalletEvents::default(),
+            proposals: Arc::new(RwLock::new(HashMap::new())),
+            signer: Arc::new(RwLock::new(None)),
             price: Arc::new(RwLock::new(WalletZecPriceInfo::new())),
+            envelope: Arc::new(RwLock::new(None)),
+            sealed: Arc::new(RwLock::new(None)),
         })
     }
 
+    // A wallet whose file is encrypted, and which can't be read until it is unlocked
+    fn new_sealed(config: LightClientConfig<P>, sealed: Vec<u8>) -> Self {
+        Self {
+            keys: Arc::new(RwLock::new(Keys::new_sealed(&config))),
+            txns: Arc::new(RwLock::new(WalletTxns::new())),
+            blocks: Arc::new(RwLock::new(vec![])),
+            wallet_options: Arc::new(RwLock::new(WalletOptions::default())),
+            config,
+            orchard_witnesses: Arc::new(RwLock::new(None)),
+            backfill: Arc::new(RwLock::new(None)),
+            birthday: AtomicU64::new(0),
+            verified_tree: Arc::new(RwLock::new(None)),
+            send_progress: Arc::new(RwLock::new(SendProgress::new(0))),
+            events: WalletEvents::default(),
+            proposals: Arc::new(RwLock::new(HashMap::new())),
+            signer: Arc::new(RwLock::new(None)),
+            price: Arc::new(RwLock::new(WalletZecPriceInfo::new())),
+            envelope: Arc::new(RwLock::new(None)),
+            sealed: Arc::new(RwLock::new(Some(sealed))),
+        }
+    }
+
     pub fn read_tree<H: Hashable + HashSer + Ord + Clone, R: Read>(mut reader: R) -> io::Result<BridgeTree<H, 32>> {
         let _version = reader.read_u64::<LittleEndian>()?;
 
@@ -326,6 +517,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
 
         info!("Reading wallet version {}", version);
 
+        // If the whole wallet file is encrypted, keep it as is until the wallet is unlocked
+        if version >= 26 && reader.read_u8()? == 1 {
+            let mut sealed = vec![];
+            reader.read_to_end(&mut sealed)?;
+
+            return Ok(Self::new_sealed(config.clone(), sealed));
+        }
+
         let keys = if version <= 14 {
             Keys::read_old(version, &mut reader, config)
         } else {
@@ -405,6 +604,12 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             Optional::read(&mut reader, |r| Self::read_tree(r))?
         };
 
+        let backfill = if version <= 26 {
+            None
+        } else {
+            Optional::read(&mut reader, |r| Backfill::read(r))?
+        };
+
         let mut lw = Self {
             keys: Arc::new(RwLock::new(keys)),
             txns: Arc::new(RwLock::new(txns)),
@@ -412,10 +617,16 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             config: config.clone(),
             wallet_options: Arc::new(RwLock::new(wallet_options)),
             orchard_witnesses: Arc::new(RwLock::new(orchard_witnesses)),
+            backfill: Arc::new(RwLock::new(backfill)),
             birthday: AtomicU64::new(birthday),
             verified_tree: Arc::new(RwLock::new(verified_tree)),
             send_progress: Arc::new(RwLock::new(SendProgress::new(0))),
+            events: WalletEvents::default(),
+            proposals: Arc::new(RwLock::new(HashMap::new())),
+            signer: Arc::new(RwLock::new(None)),
             price: Arc::new(RwLock::new(price)),
+            envelope: Arc::new(RwLock::new(None)),
+            sealed: Arc::new(RwLock::new(None)),
         };
 
         // For old wallets, remove unused addresses
@@ -437,16 +648,32 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
     }
 
     pub async fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        if self.keys.read().await.encrypted && self.keys.read().await.unlocked {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!("Cannot write while wallet is unlocked while encrypted."),
-            ));
+        // A wallet that hasn't been unlocked since it was read is written back unchanged
+        if let Some(sealed) = self.sealed.read().await.as_ref() {
+            writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+            writer.write_u8(1)?;
+            return writer.write_all(sealed);
+        }
+
+        if let Some(envelope) = self.envelope.read().await.as_ref() {
+            let mut plaintext = Zeroizing::new(vec![]);
+            self.write_plain(&mut *plaintext).await?;
+
+            writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+            writer.write_u8(1)?;
+            return envelope.seal(&mut writer, &plaintext);
         }
 
+        self.write_plain(writer).await
+    }
+
+    async fn write_plain<W: Write>(&self, mut writer: W) -> io::Result<()> {
         // Write the version
         writer.write_u64::<LittleEndian>(Self::serialized_version())?;
 
+        // The wallet file is not encrypted as a whole
+        writer.write_u8(0)?;
+
         // Write all the keys
         self.keys.read().await.write(&mut writer)?;
 
@@ -478,6 +705,8 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             Self::write_tree(w, o)
         })?;
 
+        Optional::write(&mut writer, self.backfill.read().await.as_ref(), |w, b| b.write(w))?;
+
         Ok(())
     }
 
@@ -531,6 +760,50 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         self.wallet_options.write().await.spam_threshold = value;
     }
 
+    pub async fn set_auto_lock_timeout(&self, value: u64) {
+        self.wallet_options.write().await.auto_lock_timeout = value;
+    }
+
+    pub async fn set_fee_policy(&self, value: FeePolicy) {
+        self.wallet_options.write().await.fee_policy = value;
+    }
+
+    pub async fn fee_policy(&self) -> FeePolicy {
+        self.wallet_options.read().await.fee_policy
+    }
+
+    pub async fn set_offline_signing(&self, value: bool) {
+        self.wallet_options.write().await.offline_signing = value;
+    }
+
+    pub async fn set_privacy_policy(&self, value: PrivacyPolicy) {
+        self.wallet_options.write().await.privacy_policy = value;
+    }
+
+    pub async fn privacy_policy(&self) -> PrivacyPolicy {
+        self.wallet_options.read().await.privacy_policy
+    }
+
+    pub async fn set_block_cache_size(&self, value: u64) {
+        self.wallet_options.write().await.block_cache_size = value;
+    }
+
+    pub async fn set_sync_order(&self, value: SyncOrder) {
+        self.wallet_options.write().await.sync_order = value;
+    }
+
+    pub async fn set_auto_shield_threshold(&self, value: u64) {
+        self.wallet_options.write().await.auto_shield_threshold = value;
+    }
+
+    pub async fn set_auto_shield_utxos(&self, value: u64) {
+        self.wallet_options.write().await.auto_shield_utxos = value;
+    }
+
+    pub async fn set_auto_shield_address(&self, value: Option<String>) {
+        self.wallet_options.write().await.auto_shield_address = value;
+    }
+
     pub async fn get_birthday(&self) -> u64 {
         let birthday = self.birthday.load(std::sync::atomic::Ordering::SeqCst);
         if birthday == 0 {
@@ -561,6 +834,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
 
         p.is_send_in_progress = false;
         p.last_error = Some(e);
+        self.events.emit(WalletEvent::SendProgress(p.clone()));
     }
 
     // Set the previous send's status as success
@@ -569,6 +843,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
 
         p.is_send_in_progress = false;
         p.last_txid = Some(txid);
+        self.events.emit(WalletEvent::SendProgress(p.clone()));
     }
 
     // Reset the send progress status to blank
@@ -739,6 +1014,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         self.txns.write().await.clear();
         self.verified_tree.write().await.take();
         self.orchard_witnesses.write().await.take();
+        self.backfill.write().await.take();
     }
 
     pub async fn set_initial_block(&self, height: u64, hash: &str, _sapling_tree: &str) -> bool {
@@ -797,6 +1073,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         }
     }
 
+    /// While a recent-first sync is backfilling the older blocks, the funds the wallet received from others and the
+    /// funds it spent in the newest blocks, which were scanned first. The balances are provisional until then.
+    pub async fn provisional_funds(&self) -> Option<(u64, u64)> {
+        let recent_start = self.backfill.read().await.as_ref()?.recent_start();
+
+        Some(self.txns.read().await.funds_moved_since(recent_start))
+    }
+
     /// Get the height of the anchor block
     pub async fn get_anchor_height(&self) -> u32 {
         match self.get_target_height_and_anchor_offset().await {
@@ -867,22 +1151,66 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             .sum::<u64>()
     }
 
-    // Get all (unspent) utxos. Unconfirmed spent utxos are included
+    // The unspent utxos of the wallet's own t addresses, including the ones an unconfirmed tx is spending. Multisig
+    // utxos need the co-signers to be spent, so they are left out of the balances and never selected.
     pub async fn get_utxos(&self) -> Vec<Utxo> {
+        let multisigs = self.keys.read().await.get_all_multisig_addresses();
+
+        self.txns
+            .read()
+            .await
+            .current
+            .values()
+            .flat_map(|tx| tx.utxos.iter().filter(|utxo| utxo.spent.is_none()))
+            .filter(|utxo| !multisigs.contains(&utxo.address))
+            .map(|utxo| utxo.clone())
+            .collect::<Vec<Utxo>>()
+    }
+
+    // The utxos that coin selection can pick from: not frozen and not already being spent
+    pub async fn get_spendable_utxos(&self) -> Vec<Utxo> {
+        self.get_utxos()
+            .await
+            .into_iter()
+            .filter(|utxo| utxo.unconfirmed_spent.is_none() && !utxo.frozen)
+            .collect()
+    }
+
+    // The utxos that can be shielded without waiting: in mined transactions, not frozen and not already being spent
+    pub async fn get_shieldable_utxos(&self) -> Vec<Utxo> {
+        let multisigs = self.keys.read().await.get_all_multisig_addresses();
+
+        self.txns
+            .read()
+            .await
+            .current
+            .values()
+            .filter(|tx| !tx.unconfirmed)
+            .flat_map(|tx| tx.utxos.iter().filter(|utxo| utxo.spent.is_none()))
+            .filter(|utxo| utxo.unconfirmed_spent.is_none() && !utxo.frozen)
+            .filter(|utxo| !multisigs.contains(&utxo.address))
+            .map(|utxo| utxo.clone())
+            .collect::<Vec<Utxo>>()
+    }
+
+    pub async fn get_multisig_utxos(&self, address: &str) -> Vec<Utxo> {
         self.txns
             .read()
             .await
             .current
             .values()
             .flat_map(|tx| tx.utxos.iter().filter(|utxo| utxo.spent.is_none()))
+            .filter(|utxo| utxo.address == address)
             .map(|utxo| utxo.clone())
             .collect::<Vec<Utxo>>()
     }
 
+    // Value of the unspent utxos, leaving out the frozen ones, which are counted in `frozen_tbalance`
     pub async fn tbalance(&self, addr: Option<String>) -> u64 {
         self.get_utxos()
             .await
             .iter()
+            .filter(|utxo| !utxo.frozen)
             .filter(|utxo| match addr.as_ref() {
                 Some(a) => utxo.address == *a,
                 None => true,
@@ -891,6 +1219,42 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             .sum::<u64>()
     }
 
+    // Value of the unspent utxos that have been frozen, and won't be spent or shielded
+    pub async fn frozen_tbalance(&self, addr: Option<String>) -> u64 {
+        self.get_utxos()
+            .await
+            .iter()
+            .filter(|utxo| utxo.frozen)
+            .filter(|utxo| match addr.as_ref() {
+                Some(a) => utxo.address == *a,
+                None => true,
+            })
+            .map(|utxo| utxo.value)
+            .sum::<u64>()
+    }
+
+    // Value of the unspent sapling notes that have been frozen, and won't be spent
+    pub async fn frozen_zbalance(&self, addr: Option<String>) -> u64 {
+        self.txns
+            .read()
+            .await
+            .current
+            .values()
+            .flat_map(|tx| tx.s_notes.iter())
+            .filter(|nd| nd.frozen && nd.spent.is_none() && nd.unconfirmed_spent.is_none())
+            .filter(|nd| match addr.as_ref() {
+                Some(a) => {
+                    *a == encode_payment_address(
+                        self.config.hrp_sapling_address(),
+                        &nd.extfvk.fvk.vk.to_payment_address(nd.diversifier).unwrap(),
+                    )
+                }
+                None => true,
+            })
+            .map(|nd| nd.note.value)
+            .sum::<u64>()
+    }
+
     pub async fn unverified_zbalance(&self, addr: Option<String>) -> u64 {
         let anchor_height = self.get_anchor_height().await;
 
@@ -944,7 +1308,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
                 if tx.block <= BlockHeight::from_u32(anchor_height) {
                     tx.s_notes
                         .iter()
-                        .filter(|nd| nd.spent.is_none() && nd.unconfirmed_spent.is_none())
+                        .filter(|nd| nd.spent.is_none() && nd.unconfirmed_spent.is_none() && !nd.frozen)
                         .filter(|nd| match addr.as_ref() {
                             Some(a) => {
                                 *a == encode_payment_address(
@@ -963,6 +1327,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             .sum::<u64>()
     }
 
+    /// The fee for sending the entire spendable shielded balance to `outputs` shielded recipients, under the
+    /// wallet's fee policy. Every note coin selection could pick is spent, and there is no change.
+    pub async fn verified_zbalance_fee(&self, outputs: usize) -> u64 {
+        let notes = self.sapling_candidates(false, None).await.len();
+
+        self.fee_policy().await.fee(0, 0, notes, outputs)
+    }
+
     pub async fn spendable_zbalance(&self, addr: Option<String>) -> u64 {
         let anchor_height = self.get_anchor_height().await;
 
@@ -977,7 +1349,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
                 if tx.block <= BlockHeight::from_u32(anchor_height) {
                     tx.s_notes
                         .iter()
-                        .filter(|nd| nd.spent.is_none() && nd.unconfirmed_spent.is_none())
+                        .filter(|nd| nd.spent.is_none() && nd.unconfirmed_spent.is_none() && !nd.frozen)
                         .filter(|nd| {
                             // Check to see if we have this note's spending key and witnesses
                             keys.have_sapling_spending_key(&nd.extfvk) && nd.witnesses.len() > 0
@@ -1124,6 +1496,11 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
     }
 
     async fn select_orchard_notes(&self, target_amount: Amount) -> Vec<SpendableOrchardNote> {
+        // The orchard tree only gets the newest blocks once a recent-first sync's backfill is done
+        if self.backfill.read().await.is_some() {
+            return vec![];
+        }
+
         let keys = self.keys.read().await;
         let owt = self.orchard_witnesses.read().await;
         let orchard_witness_tree = owt.as_ref().unwrap();
@@ -1188,9 +1565,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         o_notes
     }
 
-    async fn select_sapling_notes(&self, target_amount: Amount) -> Vec<SpendableSaplingNote> {
+    // The sapling notes that coin selection can pick from: unspent, not frozen, not empty and with a witness at the
+    // anchor. Only notes we have the spending key for, or if `watch_only`, only the notes of viewing keys.
+    async fn sapling_candidates(&self, watch_only: bool, from: Option<&str>) -> Vec<SpendableSaplingNote> {
+        // While a recent-first sync is backfilling, only the notes found in the newest blocks have witnesses at the
+        // wallet's height. The older ones may also have been spent in the blocks that aren't scanned yet.
+        let recent_start = self.backfill.read().await.as_ref().map(|b| b.recent_start());
+
         let keys = self.keys.read().await;
-        let mut candidate_notes = self
+        let notes = self
             .txns
             .read()
             .await
@@ -1198,17 +1581,42 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             .iter()
             .flat_map(|(txid, tx)| tx.s_notes.iter().map(move |note| (*txid, note)))
             .filter(|(_, note)| note.note.value > 0)
+            .filter(|(_, note)| recent_start.map_or(true, |h| note.witnesses.top_height >= h))
+            .filter(|(_, note)| {
+                from.map_or(true, |a| {
+                    Self::sapling_note_address(self.config.hrp_sapling_address(), note).as_deref() == Some(a)
+                })
+            })
             .filter_map(|(txid, note)| {
-                // Filter out notes that are already spent
-                if note.spent.is_some() || note.unconfirmed_spent.is_some() {
+                // Filter out notes that are already spent or have been frozen
+                if note.spent.is_some() || note.unconfirmed_spent.is_some() || note.frozen {
                     None
                 } else {
                     // Get the spending key for the selected fvk, if we have it
                     let extsk = keys.get_extsk_for_extfvk(&note.extfvk);
-                    SpendableSaplingNote::from(txid, note, self.config.anchor_offset as usize, &extsk)
+                    match (watch_only, extsk) {
+                        (false, extsk) => {
+                            SpendableSaplingNote::from(txid, note, self.config.anchor_offset as usize, extsk)
+                        }
+                        (true, None) => SpendableSaplingNote::watch_only(txid, note, self.config.anchor_offset as usize),
+                        (true, Some(_)) => None,
+                    }
                 }
             })
             .collect::<Vec<_>>();
+
+        notes
+    }
+
+    // Select sapling notes this wallet can spend, or if `watch_only`, the notes of viewing keys, which are signed
+    // for by an offline signer. If `from` is given, only notes received at that address are selected.
+    async fn select_sapling_notes(
+        &self,
+        target_amount: Amount,
+        watch_only: bool,
+        from: Option<&str>,
+    ) -> Vec<SpendableSaplingNote> {
+        let mut candidate_notes = self.sapling_candidates(watch_only, from).await;
         candidate_notes.sort_by(|a, b| b.note.value.cmp(&a.note.value));
 
         // Select the minimum number of notes required to satisfy the target value
@@ -1241,14 +1649,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         target_amount: Amount,
         transparent_only: bool,
         prefer_orchard: bool,
+        from: Option<&str>,
     ) -> (Vec<SpendableOrchardNote>, Vec<SpendableSaplingNote>, Vec<Utxo>, Amount) {
         // First, we pick all the transparent values, which allows the auto shielding
         let utxos = self
-            .get_utxos()
+            .get_spendable_utxos()
             .await
-            .iter()
-            .filter(|utxo| utxo.unconfirmed_spent.is_none() && utxo.spent.is_none())
-            .map(|utxo| utxo.clone())
+            .into_iter()
+            .filter(|utxo| from.map_or(true, |a| utxo.address == a))
             .collect::<Vec<_>>();
 
         // Check how much we've selected
@@ -1282,14 +1690,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             }
         } else {
             // Collect sapling notes first
-            s_notes = self.select_sapling_notes(remaining_amount.unwrap()).await;
+            s_notes = self.select_sapling_notes(remaining_amount.unwrap(), false, from).await;
             sapling_value_selected = s_notes.iter().fold(Amount::zero(), |prev, sn| {
                 (prev + Amount::from_u64(sn.note.value).unwrap()).unwrap()
             });
 
-            // If we've selected enough, just return
+            // If we've selected enough, or can only spend from one address, just return
             let selected_value = (sapling_value_selected + transparent_value_selected).unwrap();
-            if selected_value > target_amount {
+            if selected_value > target_amount || from.is_some() {
                 return (vec![], s_notes, utxos, selected_value);
             }
         }
@@ -1299,7 +1707,7 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             target_amount - (transparent_value_selected + orchard_value_selected + sapling_value_selected).unwrap();
         if prefer_orchard {
             // Select sapling notes
-            s_notes = self.select_sapling_notes(remaining_amount.unwrap()).await;
+            s_notes = self.select_sapling_notes(remaining_amount.unwrap(), false, from).await;
             sapling_value_selected = s_notes.iter().fold(Amount::zero(), |prev, sn| {
                 (prev + Amount::from_u64(sn.note.value).unwrap()).unwrap()
             });
@@ -1317,11 +1725,14 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         return (o_notes, s_notes, utxos, total_value_selected);
     }
 
-    pub async fn send_to_address<F, Fut, PR: TxProver>(
+    pub async fn send_to_address<F, Fut, PR: TxProver + Send + Sync>(
         &self,
         prover: PR,
         transparent_only: bool,
         tos: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<&CoinSelection>,
+        from: Option<&SendFrom>,
         broadcast_fn: F,
     ) -> Result<(String, Vec<u8>), String>
     where
@@ -1331,189 +1742,984 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         // Reset the progress to start. Any errors will get recorded here
         self.reset_send_progress().await;
 
-        // Call the internal function
-        match self
-            .send_to_address_internal(prover, transparent_only, tos, broadcast_fn)
-            .await
-        {
-            Ok((txid, rawtx)) => {
-                self.set_send_success(txid.clone()).await;
-                Ok((txid, rawtx))
-            }
-            Err(e) => {
-                self.set_send_error(format!("{}", e)).await;
-                Err(e)
-            }
-        }
+        // Select the inputs, and then build and send right away
+        let result = match self.plan_send(transparent_only, tos, fee, inputs, from).await {
+            Ok(plans) => self.send_chain(prover, plans, broadcast_fn).await,
+            Err(e) => Err(e),
+        };
+
+        self.record_send_result(result).await
     }
 
-    async fn send_to_address_internal<F, Fut, PR: TxProver>(
+    /// Shield `utxos` to the sapling address `to`. If there are too many for one transaction, they are shielded
+    /// in several. If `fee` is given, every transaction pays it instead of the fee from the wallet's fee policy.
+    pub async fn shield<F, Fut, PR: TxProver + Send + Sync>(
         &self,
         prover: PR,
-        transparent_only: bool,
-        tos: Vec<(&str, u64, Option<String>)>,
+        to: &str,
+        utxos: Vec<Utxo>,
+        fee: Option<u64>,
         broadcast_fn: F,
     ) -> Result<(String, Vec<u8>), String>
     where
         F: Fn(Box<[u8]>) -> Fut,
         Fut: Future<Output = Result<String, String>>,
     {
-        if !self.keys.read().await.unlocked {
-            return Err("Cannot spend while wallet is locked".to_string());
-        }
+        self.reset_send_progress().await;
 
-        let start_time = now();
-        if tos.len() == 0 {
-            return Err("Need at least one destination address".to_string());
-        }
+        let result = match self.plan_chain(true, (to, None, None), fee, vec![], utxos, None).await {
+            Ok(plans) => self.send_chain(prover, plans, broadcast_fn).await,
+            Err(e) => Err(e),
+        };
 
-        let total_value = tos.iter().map(|to| to.1).sum::<u64>();
-        println!(
-            "0: Creating transaction sending {} ztoshis to {} addresses",
-            total_value,
-            tos.len()
-        );
-        info!("Starting send_to_address_internal with {} recipients", tos.len());
-        println!("[DEBUG] send_to_address_internal called with {} recipients", tos.len());
-        for (i, to) in tos.iter().enumerate() {
-            println!("[DEBUG] Recipient {}: addr={}, amount={}, memo={:?}", i, to.0, to.1, to.2);
-        }
+        self.record_send_result(result).await
+    }
 
-        // Convert address (str) to RecepientAddress and value to Amount
-        let recepients = tos
-            .iter()
-            .map(|to| {
-                let ra = match address::RecipientAddress::decode(&self.config.get_params(), to.0) {
-                    Some(to) => to,
-                    None => {
-                        let e = format!("Invalid recipient address: '{}'", to.0);
-                        error!("{}", e);
-                        return Err(e);
-                    }
-                };
+    /// Send everything spendable from `source` to `to`. The fee is taken out of the amount sent, so there is no
+    /// change. The amount and fee are reported in the send progress.
+    pub async fn send_max<F, Fut, PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        to: &str,
+        memo: Option<String>,
+        source: &SendMaxSource,
+        fee: Option<u64>,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        self.reset_send_progress().await;
 
-                let value = Amount::from_u64(to.1).unwrap();
+        let result = match self.plan_max_send(to, memo, source, fee).await {
+            Ok(plans) => self.send_chain(prover, plans, broadcast_fn).await,
+            Err(e) => Err(e),
+        };
 
-                Ok((ra, value, to.2.clone()))
-            })
-            .collect::<Result<Vec<(address::RecipientAddress, Amount, Option<String>)>, String>>()?;
+        self.record_send_result(result).await
+    }
 
-        // Calculate how much we're sending to each type of address
-        let (_t_out, s_out, _o_out) = recepients
-            .iter()
-            .map(|(to, value, _)| match to {
-                address::RecipientAddress::Unified(_) => (0, 0, value.into()),
-                address::RecipientAddress::Shielded(_) => (0, value.into(), 0),
-                address::RecipientAddress::Transparent(_) => (value.into(), 0, 0),
-            })
-            .reduce(|(t, s, o), (t2, s2, o2)| (t + t2, s + s2, o + o2))
-            .unwrap_or((0, 0, 0));
-
-        // Select notes to cover the target value
-        println!("{}: Selecting notes", now() - start_time);
-        
-        let target_amount = (Amount::from_u64(total_value).unwrap() + DEFAULT_FEE).unwrap();
-        info!("Target amount: {} zatoshis (including fee)", u64::from(target_amount));
-        println!("[DEBUG] Target amount with fee: {} zatoshis", u64::from(target_amount));
-        let target_height = match self.get_target_height().await {
-            Some(h) => BlockHeight::from_u32(h),
-            None => return Err("No blocks in wallet to target, please sync first".to_string()),
-        };
-        println!("[DEBUG] Target height: {:?}", target_height);
+    /// Merge the `count` smallest spendable sapling notes, or all of them, into notes to `to`. Each transaction
+    /// spends as many notes as fit in it, and pays its own fee.
+    pub async fn consolidate<F, Fut, PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        to: &str,
+        count: Option<usize>,
+        fee: Option<u64>,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        self.reset_send_progress().await;
 
-        let (progress_notifier, progress_notifier_rx) = mpsc::channel();
+        let result = match self.plan_consolidation(to, count, fee).await {
+            Ok(plans) => self.send_chain(prover, plans, broadcast_fn).await,
+            Err(e) => Err(e),
+        };
 
-        // BitcoinZ doesn't support Orchard, so use regular builder
-        let mut builder = Builder::new(self.config.get_params().clone(), target_height);
-        builder.with_progress_notifier(progress_notifier);
+        self.record_send_result(result).await
+    }
 
-        // Create a map from address -> sk for all taddrs, so we can spend from the
-        // right address
-        let address_to_sk = self.keys.read().await.get_taddr_to_sk_map();
+    /// The transactions `consolidate` would send, without proving or broadcasting them
+    pub async fn consolidation_proposals(
+        &self,
+        to: &str,
+        count: Option<usize>,
+        fee: Option<u64>,
+    ) -> Result<Vec<TxProposal>, String> {
+        let plans = self.plan_consolidation(to, count, fee).await?;
+        Ok(plans.into_iter().map(|(proposal, _, _)| proposal).collect())
+    }
 
-        // BitcoinZ doesn't support Orchard, so never prefer it
-        let prefer_orchard = false;
+    async fn plan_consolidation(
+        &self,
+        to: &str,
+        count: Option<usize>,
+        fee: Option<u64>,
+    ) -> Result<Vec<PlannedTx>, String> {
+        let selection = self.max_spendable_inputs(&SendMaxSource::Sapling).await?;
+        let (mut s_notes, _) = self.chosen_inputs(&selection, false).await?;
+        s_notes.sort_by_key(|note| note.note.value);
+        s_notes.truncate(count.unwrap_or(s_notes.len()));
+        if s_notes.len() < 2 {
+            return Err(format!("Need at least 2 spendable sapling notes to consolidate, have {}", s_notes.len()));
+        }
 
-        let (o_notes, s_notes, utxos, selected_value) = self
-            .select_notes_and_utxos(target_amount, transparent_only, prefer_orchard)
-            .await;
-        if selected_value < target_amount {
-            let e = format!(
-                "Insufficient verified funds. Have {} zats, need {} zats. NOTE: funds need at least {} confirmations before they can be spent.",
-                u64::from(selected_value), u64::from(target_amount), self.config.anchor_offset + 1
-            );
-            error!("{}", e);
-            return Err(e);
+        // A transaction left with a single note wouldn't merge anything
+        let plans = self
+            .plan_chain(false, (to, None, None), fee, s_notes, vec![], None)
+            .await?
+            .into_iter()
+            .filter(|(proposal, _, _)| proposal.s_notes.len() > 1)
+            .collect::<Vec<_>>();
+        if plans.is_empty() {
+            return Err("The notes are too small to pay the fee for merging them".to_string());
         }
 
-        // Create the transaction
-        println!(
-            "{}: Adding {} o_notes {} s_notes and {} utxos",
-            now() - start_time,
-            o_notes.len(),
-            s_notes.len(),
-            utxos.len()
+        Ok(plans)
+    }
+
+    /// Every note and utxo from `source` that can be spent right now: confirmed, not frozen, with a spending key, and
+    /// while a recent-first sync is backfilling, found in the newest blocks. Multisig utxos are never included. Fails
+    /// if a note was saved without its output index, since it can't be chosen until a rescan fills it in.
+    pub async fn max_spendable_inputs(&self, source: &SendMaxSource) -> Result<CoinSelection, String> {
+        let utxos = match source {
+            SendMaxSource::Sapling => vec![],
+            _ => self
+                .get_spendable_utxos()
+                .await
+                .into_iter()
+                .filter(|utxo| match source {
+                    SendMaxSource::Address(a) => utxo.address == *a,
+                    _ => true,
+                })
+                .map(|utxo| (utxo.txid, utxo.output_index as u32))
+                .collect(),
+        };
+
+        let s_notes = match source {
+            SendMaxSource::Transparent => vec![],
+            _ => {
+                let anchor_height = BlockHeight::from_u32(self.get_anchor_height().await);
+                let recent_start = self.backfill.read().await.as_ref().map(|b| b.recent_start());
+                let keys = self.keys.read().await;
+                let txns = self.txns.read().await;
+
+                let notes = txns
+                    .current
+                    .iter()
+                    .filter(|(_, tx)| tx.block <= anchor_height)
+                    .flat_map(|(txid, tx)| tx.s_notes.iter().map(move |nd| (*txid, nd)))
+                    .filter(|(_, nd)| nd.spent.is_none() && nd.unconfirmed_spent.is_none() && !nd.frozen)
+                    .filter(|(_, nd)| recent_start.map_or(true, |h| nd.witnesses.top_height >= h))
+                    .filter(|(_, nd)| keys.have_sapling_spending_key(&nd.extfvk) && nd.witnesses.len() > 0)
+                    .filter(|(_, nd)| match source {
+                        SendMaxSource::Address(a) => {
+                            Self::sapling_note_address(self.config.hrp_sapling_address(), nd).as_ref() == Some(a)
+                        }
+                        _ => true,
+                    })
+                    .map(|(txid, nd)| (txid, nd.output_index))
+                    .collect::<Vec<_>>();
+
+                let unindexed = notes.iter().filter(|(_, i)| i.is_none()).count();
+                if unindexed > 0 {
+                    return Err(format!(
+                        "{} spendable sapling notes were saved by an older version of the wallet without their \
+                         output index. Run 'rescan' to fill it in",
+                        unindexed
+                    ));
+                }
+
+                notes.into_iter().filter_map(|(txid, i)| i.map(|i| (txid, i))).collect()
+            }
+        };
+
+        Ok(CoinSelection { s_notes, utxos })
+    }
+
+    /// How much `to` receives if it is sent everything spendable from `source`, with the fee taken out of the amount.
+    /// Returns the amount, the fee and the inputs to spend. If the inputs don't fit in one transaction, they are
+    /// spread over several, and the fee is the total of their fees. If `fee` is given, every transaction pays it
+    /// instead of the fee computed from the wallet's fee policy.
+    pub async fn max_send_amount(
+        &self,
+        to: &str,
+        source: &SendMaxSource,
+        fee: Option<u64>,
+    ) -> Result<(u64, u64, CoinSelection), String> {
+        let plans = self.plan_max_send(to, None, source, fee).await?;
+
+        let txns = self.txns.read().await;
+        let selection = CoinSelection {
+            s_notes: plans
+                .iter()
+                .flat_map(|(_, s_notes, _)| s_notes.iter())
+                .filter_map(|n| {
+                    let nd = txns
+                        .current
+                        .get(&n.txid)?
+                        .s_notes
+                        .iter()
+                        .find(|nd| nd.nullifier == n.nullifier)?;
+                    nd.output_index.map(|i| (n.txid, i))
+                })
+                .collect(),
+            utxos: plans
+                .iter()
+                .flat_map(|(_, _, utxos)| utxos.iter())
+                .map(|u| (u.txid, u.output_index as u32))
+                .collect(),
+        };
+
+        Ok((
+            plans.iter().map(|(p, _, _)| p.total_value()).sum(),
+            plans.iter().map(|(p, _, _)| p.fee).sum(),
+            selection,
+        ))
+    }
+
+    // Plan sending everything spendable from `source` to `to`, with no change
+    async fn plan_max_send(
+        &self,
+        to: &str,
+        memo: Option<String>,
+        source: &SendMaxSource,
+        fee: Option<u64>,
+    ) -> Result<Vec<PlannedTx>, String> {
+        let selection = self.max_spendable_inputs(source).await?;
+        let (s_notes, utxos) = self.chosen_inputs(&selection, false).await?;
+        if s_notes.is_empty() && utxos.is_empty() {
+            return Err("There are no spendable funds to send".to_string());
+        }
+
+        self.plan_chain(false, (to, None, memo), fee, s_notes, utxos, None)
+            .await
+    }
+
+    // Plan a send as one transaction, or if its inputs don't fit in one, as a chain of transactions that each pay
+    // part of the amount. Only a payment to a single recipient without chosen inputs can be split.
+    async fn plan_send(
+        &self,
+        transparent_only: bool,
+        tos: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<&CoinSelection>,
+        from: Option<&SendFrom>,
+    ) -> Result<Vec<PlannedTx>, String> {
+        let (proposal, s_notes, utxos) = self
+            .propose_internal(transparent_only, tos.clone(), fee, inputs, from, false)
+            .await?;
+        if proposal.estimated_size() <= MAX_TX_SIZE {
+            return Ok(vec![(proposal, s_notes, utxos)]);
+        }
+        if inputs.is_some() || tos.len() != 1 {
+            proposal.check_size()?;
+        }
+
+        // Split the payment over all the spendable funds the privacy policy allows, largest first
+        let privacy_policy = self.privacy_policy().await;
+        let source = match from {
+            Some(from) => SendMaxSource::Address(from.address.clone()),
+            None if transparent_only => SendMaxSource::Transparent,
+            None if !privacy_policy.allows_mixed_inputs(proposal.t_outputs > 0) => SendMaxSource::Sapling,
+            None => SendMaxSource::All,
+        };
+        let selection = self.max_spendable_inputs(&source).await?;
+        let (s_notes, utxos) = self.chosen_inputs(&selection, false).await?;
+
+        let (address, amount, memo) = tos[0].clone();
+        info!(
+            "Splitting the payment of {} zats, since its {} inputs don't fit in one transaction",
+            amount,
+            proposal.utxos.len() + proposal.s_notes.len()
         );
+        self.plan_chain(transparent_only, (address, Some(amount), memo), fee, s_notes, utxos, from)
+            .await
+    }
+
+    // Split a payment to one recipient into transactions that stay under the size limit. The inputs are spent
+    // largest first, and every transaction pays the recipient what its inputs are worth after its fee, until
+    // `amount` is paid. The last transaction takes the change. If `amount` is None, everything is sent with no
+    // change. If `fee` is given, every transaction pays it.
+    async fn plan_chain(
+        &self,
+        transparent_only: bool,
+        to: (&str, Option<u64>, Option<String>),
+        fee: Option<u64>,
+        s_notes: Vec<SpendableSaplingNote>,
+        utxos: Vec<Utxo>,
+        from: Option<&SendFrom>,
+    ) -> Result<Vec<PlannedTx>, String> {
+        let (address, amount, memo) = to;
+        let (t_outputs, s_outputs) = Self::count_outputs(&self.config, &[(address, 0, None)])?;
+
+        // Only a payment of a given amount has change, which goes back to the source address if asked to
+        let change_address = from
+            .filter(|f| f.change_to_source && amount.is_some())
+            .map(|f| f.address.clone());
+        let transparent_change = match from {
+            Some(from) if change_address.is_some() => self.source_is_transparent(from).await?,
+            _ => false,
+        };
+        let change = if amount.is_some() { 1 } else { 0 };
+        let (t_change, s_change) = if transparent_change { (change, 0) } else { (0, change) };
+        let (tx_t_outputs, tx_s_outputs) = (t_outputs + t_change, s_outputs + s_change);
+
+        let fee_policy = match fee {
+            Some(fee) => FeePolicy::Fixed(fee),
+            None => self.fee_policy().await,
+        };
+        let privacy_policy = self.privacy_policy().await;
+
+        let target_height = match self.get_target_height().await {
+            Some(h) => h,
+            None => return Err("No blocks in wallet to target, please sync first".to_string()),
+        };
+
+        enum Input {
+            Transparent(Utxo),
+            Sapling(SpendableSaplingNote),
+        }
+        let value = |input: &Input| match input {
+            Input::Transparent(utxo) => utxo.value,
+            Input::Sapling(note) => note.note.value,
+        };
+
+        let mut inputs = utxos
+            .into_iter()
+            .map(Input::Transparent)
+            .chain(s_notes.into_iter().map(Input::Sapling))
+            .collect::<Vec<_>>();
+        inputs.sort_by(|a, b| value(b).cmp(&value(a)));
+        let total = inputs.iter().map(value).sum::<u64>();
+        let mut inputs = inputs.into_iter().peekable();
+
+        let mut plans = vec![];
+        let mut remaining = amount;
+        let mut tx_fee = fee_policy.fee(0, t_outputs, 0, s_outputs);
+        while remaining != Some(0) && inputs.peek().is_some() {
+            // Add inputs to the transaction until it pays what is left, or the next one doesn't fit
+            let mut utxos = vec![];
+            let mut s_notes = vec![];
+            let mut tx_value = 0;
+            let mut fee_without_change = tx_fee;
+            while let Some(input) = inputs.peek() {
+                let (t, s) = match input {
+                    Input::Transparent(_) => (utxos.len() + 1, s_notes.len()),
+                    Input::Sapling(_) => (utxos.len(), s_notes.len() + 1),
+                };
+                if tx_value > 0 && estimated_tx_size(t, tx_t_outputs, s, tx_s_outputs) > MAX_TX_SIZE {
+                    break;
+                }
 
-        let mut change = 0u64;
+                tx_value += value(input);
+                match inputs.next() {
+                    Some(Input::Transparent(utxo)) => utxos.push(utxo),
+                    Some(Input::Sapling(note)) => s_notes.push(note),
+                    None => unreachable!(),
+                }
+
+                fee_without_change = fee_policy.fee(utxos.len(), t_outputs, s_notes.len(), s_outputs);
+                if remaining.map_or(false, |r| tx_value >= r + fee_without_change) {
+                    break;
+                }
+            }
+
+            // Only the transaction that pays the rest of the amount has change, and pays for its output. Change too
+            // small to pay for its own output goes to the fee instead.
+            let fee_with_change = fee_policy.fee(utxos.len(), tx_t_outputs, s_notes.len(), tx_s_outputs);
+            tx_fee = match remaining {
+                Some(r) if tx_value > r + fee_with_change => fee_with_change,
+                Some(r) if tx_value > r + fee_without_change => tx_value - r,
+                _ => fee_without_change,
+            };
+
+            // The inputs are getting smaller, so once they can't pay for their own fee, the rest can't either
+            if tx_value <= tx_fee {
+                break;
+            }
 
-        // Add all tinputs
-        utxos
+            let pay = match remaining {
+                Some(r) => cmp::min(r, tx_value - tx_fee),
+                None => tx_value - tx_fee,
+            };
+            remaining = remaining.map(|r| r - pay);
+
+            let proposal = TxProposal {
+                id: OsRng.next_u32(),
+                created: now(),
+                target_height,
+                transparent_only,
+                tos: vec![(address.to_string(), pay, memo.clone())],
+                fee: tx_fee,
+                utxos: utxos.clone(),
+                s_notes: s_notes.iter().map(|n| (n.txid, n.nullifier, n.note.value)).collect(),
+                t_outputs,
+                s_outputs,
+                change_address: change_address.clone(),
+                transparent_change,
+            };
+            proposal.check_privacy(privacy_policy)?;
+            plans.push((proposal, s_notes, utxos));
+        }
+
+        match amount {
+            Some(amount) if remaining != Some(0) => Err(format!(
+                "Insufficient verified funds. Have {} zats, need {} zats plus the fees. NOTE: funds need at least {} \
+                 confirmations before they can be spent.",
+                total,
+                amount,
+                self.config.anchor_offset + 1
+            )),
+            None if plans.is_empty() && transparent_only => Err(format!(
+                "Not enough transparent balance to shield. Have {} zats, need more than {} zats to cover tx fee",
+                total, tx_fee
+            )),
+            None if plans.is_empty() => Err(format!(
+                "Not enough spendable funds to pay the fee. Have {} zats, need more than {} zats",
+                total, tx_fee
+            )),
+            _ => Ok(plans),
+        }
+    }
+
+    // Send planned transactions one after the other, reporting each one in the send progress. If one fails, the
+    // ones before it have already been broadcast.
+    async fn send_chain<F, Fut, PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        plans: Vec<PlannedTx>,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        {
+            let mut p = self.send_progress.write().await;
+            p.last_amount = Some(plans.iter().map(|(proposal, _, _)| proposal.total_value()).sum());
+            p.last_fee = Some(plans.iter().map(|(proposal, _, _)| proposal.fee).sum());
+            p.tx_count = plans.len() as u32;
+        }
+
+        let mut sent = None;
+        for (proposal, s_notes, utxos) in plans {
+            let (txid, raw_tx) = self
+                .send_selected(ProverRef(&prover), &proposal, s_notes, utxos, &broadcast_fn)
+                .await?;
+            {
+                let mut p = self.send_progress.write().await;
+                p.txids.push(txid.clone());
+                self.events.emit(WalletEvent::SendProgress(p.clone()));
+            }
+            sent = Some((txid, raw_tx));
+        }
+
+        sent.ok_or_else(|| "Nothing to send".to_string())
+    }
+
+    /// Select the inputs and compute the fee and change for sending to `tos`, without building the transaction.
+    /// If `inputs` is given, exactly those are spent. If `from` is given, only the funds received at that address
+    /// are spent. The proposal is kept in memory until it is sent with `send_proposal` or discarded.
+    pub async fn propose(
+        &self,
+        transparent_only: bool,
+        tos: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<&CoinSelection>,
+        from: Option<&SendFrom>,
+    ) -> Result<TxProposal, String> {
+        let mut plans = self.plan_send(transparent_only, tos, fee, inputs, from).await?;
+        if plans.len() > 1 {
+            return Err(format!(
+                "This payment has to be split over {} transactions, which can't be proposed. Use send instead.",
+                plans.len()
+            ));
+        }
+
+        let (proposal, _, _) = plans.remove(0);
+        self.proposals.write().await.insert(proposal.id, proposal.clone());
+
+        Ok(proposal)
+    }
+
+    pub async fn discard_proposal(&self, id: u32) -> bool {
+        self.proposals.write().await.remove(&id).is_some()
+    }
+
+    /// Build, prove and broadcast a proposal made with `propose`, spending exactly the inputs it selected.
+    /// The proposal is used up, even if sending fails.
+    pub async fn send_proposal<F, Fut, PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        id: u32,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        self.reset_send_progress().await;
+
+        let proposal = self.proposals.write().await.remove(&id);
+        let result = match proposal {
+            Some(proposal) => match self.proposal_inputs(&proposal).await {
+                Ok((s_notes, utxos)) => {
+                    self.send_chain(prover, vec![(proposal, s_notes, utxos)], broadcast_fn)
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            None => Err(format!("No transaction proposal with id {}", id)),
+        };
+
+        self.record_send_result(result).await
+    }
+
+    async fn record_send_result(
+        &self,
+        result: Result<(String, Vec<u8>), String>,
+    ) -> Result<(String, Vec<u8>), String> {
+        match result {
+            Ok((txid, rawtx)) => {
+                self.set_send_success(txid.clone()).await;
+                Ok((txid, rawtx))
+            }
+            Err(e) => {
+                self.set_send_error(format!("{}", e)).await;
+                Err(e)
+            }
+        }
+    }
+
+    // Look up the notes and utxos a proposal selected. They have to still be spendable, or the proposal is stale.
+    async fn proposal_inputs(&self, proposal: &TxProposal) -> Result<(Vec<SpendableSaplingNote>, Vec<Utxo>), String> {
+        let stale = || "The proposal's inputs are no longer spendable, please create a new proposal".to_string();
+
+        // The privacy policy may have changed since the proposal was made
+        proposal.check_privacy(self.privacy_policy().await)?;
+
+        let keys = self.keys.read().await;
+        if !keys.unlocked {
+            return Err("Cannot spend while wallet is locked".to_string());
+        }
+
+        let txns = self.txns.read().await;
+        let s_notes = proposal
+            .s_notes
+            .iter()
+            .map(|(txid, nullifier, _)| {
+                let nd = txns
+                    .current
+                    .get(txid)?
+                    .s_notes
+                    .iter()
+                    .find(|nd| nd.nullifier == *nullifier && !nd.frozen)?;
+                let extsk = keys.get_extsk_for_extfvk(&nd.extfvk);
+                SpendableSaplingNote::from(*txid, nd, self.config.anchor_offset as usize, extsk)
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(stale)?;
+
+        let utxos = proposal
+            .utxos
             .iter()
             .map(|utxo| {
-                let outpoint: OutPoint = utxo.to_outpoint();
+                txns.current
+                    .get(&utxo.txid)?
+                    .utxos
+                    .iter()
+                    .find(|u| u.output_index == utxo.output_index)
+                    .filter(|u| u.spent.is_none() && u.unconfirmed_spent.is_none() && !u.frozen)
+                    .map(|u| u.clone())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(stale)?;
+
+        Ok((s_notes, utxos))
+    }
+
+    /// Look up the notes and utxos the user chose to spend, and make sure all of them can be spent. If `watch_only`,
+    /// they will be signed for by an offline signer, so this wallet doesn't need their spending keys.
+    pub async fn chosen_inputs(
+        &self,
+        selection: &CoinSelection,
+        watch_only: bool,
+    ) -> Result<(Vec<SpendableSaplingNote>, Vec<Utxo>), String> {
+        let keys = self.keys.read().await;
+        if !watch_only && !keys.unlocked {
+            return Err("Cannot spend while wallet is locked".to_string());
+        }
+
+        if selection.s_notes.iter().collect::<HashSet<_>>().len() != selection.s_notes.len()
+            || selection.utxos.iter().collect::<HashSet<_>>().len() != selection.utxos.len()
+        {
+            return Err("The same input was chosen more than once".to_string());
+        }
+
+        let recent_start = self.backfill.read().await.as_ref().map(|b| b.recent_start());
+        let txns = self.txns.read().await;
+
+        let mut s_notes = vec![];
+        for (txid, output_index) in selection.s_notes.iter() {
+            let nd = txns.find_s_note(txid, *output_index)?;
+
+            if nd.spent.is_some() || nd.unconfirmed_spent.is_some() {
+                return Err(format!("Sapling note {}:{} is already spent", txid, output_index));
+            }
+
+            if nd.frozen {
+                return Err(format!("Sapling note {}:{} is frozen", txid, output_index));
+            }
+
+            if recent_start.map_or(false, |h| nd.witnesses.top_height < h) {
+                return Err(format!(
+                    "Sapling note {}:{} can't be spent until the sync has scanned the older blocks",
+                    txid, output_index
+                ));
+            }
+
+            let spendable = if watch_only {
+                SpendableSaplingNote::watch_only(*txid, nd, self.config.anchor_offset as usize)
+            } else {
+                let extsk = keys
+                    .get_extsk_for_extfvk(&nd.extfvk)
+                    .ok_or(format!("No spending key for sapling note {}:{}", txid, output_index))?;
+
+                SpendableSaplingNote::from(*txid, nd, self.config.anchor_offset as usize, Some(extsk))
+            }
+            .ok_or(format!(
+                "Sapling note {}:{} needs at least {} confirmations before it can be spent",
+                txid,
+                output_index,
+                self.config.anchor_offset + 1
+            ))?;
+            s_notes.push(spendable);
+        }
+
+        let address_to_sk = if watch_only { None } else { Some(keys.get_taddr_to_sk_map()) };
+
+        let mut utxos = vec![];
+        for (txid, output_index) in selection.utxos.iter() {
+            let utxo = txns
+                .current
+                .get(txid)
+                .and_then(|tx| tx.utxos.iter().find(|u| u.output_index == *output_index as u64))
+                .ok_or(format!("No utxo {}:{} in the wallet", txid, output_index))?;
+
+            if utxo.spent.is_some() || utxo.unconfirmed_spent.is_some() {
+                return Err(format!("Utxo {}:{} is already spent", txid, output_index));
+            }
+
+            if utxo.frozen {
+                return Err(format!("Utxo {}:{} is frozen", txid, output_index));
+            }
+
+            if address_to_sk.as_ref().map_or(false, |sks| !sks.contains_key(&utxo.address)) {
+                return Err(format!("No spending key for utxo {}:{}", txid, output_index));
+            }
+
+            utxos.push(utxo.clone());
+        }
+
+        Ok((s_notes, utxos))
+    }
+
+    /// Freeze or unfreeze the given notes and utxos. Frozen inputs are never selected for spending or shielding,
+    /// and can't be chosen explicitly either. Nothing is changed unless all of them are found in the wallet.
+    pub async fn set_frozen(&self, selection: &CoinSelection, frozen: bool) -> Result<(), String> {
+        let mut txns = self.txns.write().await;
+
+        for (txid, output_index) in selection.s_notes.iter() {
+            txns.find_s_note(txid, *output_index)?;
+        }
+        for (txid, output_index) in selection.utxos.iter() {
+            txns.current
+                .get(txid)
+                .and_then(|tx| tx.utxos.iter().find(|u| u.output_index == *output_index as u64))
+                .ok_or(format!("No utxo {}:{} in the wallet", txid, output_index))?;
+        }
+
+        for (txid, output_index) in selection.s_notes.iter() {
+            if let Some(tx) = txns.current.get_mut(txid) {
+                tx.s_notes
+                    .iter_mut()
+                    .filter(|nd| nd.output_index == Some(*output_index))
+                    .for_each(|nd| nd.frozen = frozen);
+            }
+        }
+        for (txid, output_index) in selection.utxos.iter() {
+            if let Some(tx) = txns.current.get_mut(txid) {
+                tx.utxos
+                    .iter_mut()
+                    .filter(|u| u.output_index == *output_index as u64)
+                    .for_each(|u| u.frozen = frozen);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn propose_internal(
+        &self,
+        transparent_only: bool,
+        tos: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<&CoinSelection>,
+        from: Option<&SendFrom>,
+        watch_only: bool,
+    ) -> Result<(TxProposal, Vec<SpendableSaplingNote>, Vec<Utxo>), String> {
+        if !watch_only && !self.keys.read().await.unlocked {
+            return Err("Cannot spend while wallet is locked".to_string());
+        }
+
+        let from_transparent = match from {
+            Some(from) => self.source_is_transparent(from).await?,
+            None => false,
+        };
+        if transparent_only && from.is_some() && !from_transparent {
+            return Err("Only transparent funds can be spent here".to_string());
+        }
+
+        let start_time = now();
+        if tos.len() == 0 {
+            return Err("Need at least one destination address".to_string());
+        }
+
+        let total_value = tos.iter().map(|to| to.1).sum::<u64>();
+        info!(
+            "Proposing a transaction sending {} ztoshis to {} addresses",
+            total_value,
+            tos.len()
+        );
+
+        // Make sure all the memos can be encoded, so a proposal doesn't fail only once it is sent
+        for memo in tos.iter().filter_map(|to| to.2.clone()) {
+            if let Err(e) = utils::interpret_memo_string(memo) {
+                error!("{}", e);
+                return Err(e);
+            }
+        }
+
+        let (t_outputs, s_outputs) = Self::count_outputs(&self.config, &tos)?;
+
+        // The change output goes back to the source address if asked to, and is paid in its pool
+        let change_address = from.filter(|f| f.change_to_source).map(|f| f.address.clone());
+        let transparent_change = change_address.is_some() && from_transparent;
+        let (t_change, s_change) = if transparent_change { (1, 0) } else { (0, 1) };
 
-                let coin = TxOut {
-                    value: Amount::from_u64(utxo.value).unwrap(),
-                    script_pubkey: Script { 0: utxo.script.clone() },
+        // An explicit fee for this send overrides the wallet's fee policy
+        let fee_policy = match fee {
+            Some(fee) => FeePolicy::Fixed(fee),
+            None => self.fee_policy().await,
+        };
+
+        let target_height = match self.get_target_height().await {
+            Some(h) => h,
+            None => return Err("No blocks in wallet to target, please sync first".to_string()),
+        };
+
+        // BitcoinZ doesn't support Orchard, so never prefer it
+        let prefer_orchard = false;
+
+        // Don't pull in utxos if the privacy policy doesn't allow spending them here. Sends that can only spend
+        // transparent funds still select them, and are rejected below with the reason.
+        let privacy_policy = self.privacy_policy().await;
+        let sapling_only = watch_only
+            || (!transparent_only
+                && !from_transparent
+                && !privacy_policy.allows_transparent_inputs(t_outputs + t_change > 0));
+
+        // Unless the user chose the inputs, select notes to cover the target value. The fee can depend on how
+        // many notes and utxos are spent, so keep selecting until the selection pays for its own fee. The fee only grows between rounds, so
+        // this stops once the selection stops growing. A change output is always counted.
+        debug!("{}: Selecting notes", now() - start_time);
+        let mut fee = fee_policy.fee(0, t_outputs + t_change, 0, s_outputs + s_change);
+        let (s_notes, utxos) = if let Some(selection) = inputs {
+            // Spend exactly the inputs the user chose
+            if transparent_only && !selection.s_notes.is_empty() {
+                return Err("Only transparent funds can be spent here".to_string());
+            }
+
+            let (s_notes, utxos) = self.chosen_inputs(selection, watch_only).await?;
+            fee = fee_policy.fee(utxos.len(), t_outputs + t_change, s_notes.len(), s_outputs + s_change);
+
+            // The chosen inputs have to come from the source address too
+            if let Some(from) = from {
+                let hrp = self.config.hrp_sapling_address();
+                let txns = self.txns.read().await;
+                let note_address = |n: &SpendableSaplingNote| {
+                    txns.current
+                        .get(&n.txid)
+                        .and_then(|tx| tx.s_notes.iter().find(|nd| nd.nullifier == n.nullifier))
+                        .and_then(|nd| Self::sapling_note_address(hrp, nd))
                 };
 
-                match address_to_sk.get(&utxo.address) {
-                    Some(sk) => {
-                        change += u64::from(coin.value);
-                        builder.add_transparent_input(*sk, outpoint.clone(), coin.clone())
-                    }
+                if utxos.iter().any(|u| u.address != from.address)
+                    || s_notes.iter().any(|n| note_address(n).as_ref() != Some(&from.address))
+                {
+                    return Err(format!("Some of the chosen inputs weren't received at {}", from.address));
+                }
+            }
+
+            let selected_value = utxos.iter().map(|u| u.value).sum::<u64>()
+                + s_notes.iter().map(|n| n.note.value).sum::<u64>();
+            if selected_value < total_value + fee {
+                let e = format!(
+                    "The chosen inputs have {} zats, need {} zats",
+                    selected_value,
+                    total_value + fee
+                );
+                error!("{}", e);
+                return Err(e);
+            }
+
+            (s_notes, utxos)
+        } else {
+            loop {
+                let target_amount = total_value + fee;
+                info!("Target amount: {} zatoshis (including fee)", target_amount);
+
+                // Without the keys for this wallet's utxos, an offline signer can only spend the notes of viewing keys,
+                // unless utxos are chosen explicitly. The privacy policy can rule out utxos too.
+                let (o_notes, s_notes, utxos, _) = if sapling_only {
+                    let s_notes = self
+                        .select_sapling_notes(
+                            Amount::from_u64(target_amount).unwrap(),
+                            watch_only,
+                            from.map(|f| f.address.as_str()),
+                        )
+                        .await;
+                    (vec![], s_notes, vec![], Amount::zero())
+                } else {
+                    let selection = self
+                        .select_notes_and_utxos(
+                            Amount::from_u64(target_amount).unwrap(),
+                            transparent_only,
+                            prefer_orchard,
+                            from.map(|f| f.address.as_str()),
+                        )
+                        .await;
+
+                    // Spending utxos together with notes links them, which the privacy policy may not allow. If
+                    // the notes alone are enough, spend only them. Otherwise the proposal is rejected below.
+                    let (_, s_notes, utxos, _) = &selection;
+                    if !s_notes.is_empty()
+                        && !utxos.is_empty()
+                        && !privacy_policy.allows_mixed_inputs(t_outputs + t_change > 0)
+                    {
+                        let s_notes = self
+                            .select_sapling_notes(
+                                Amount::from_u64(target_amount).unwrap(),
+                                false,
+                                from.map(|f| f.address.as_str()),
+                            )
+                            .await;
+                        if s_notes.iter().map(|n| n.note.value).sum::<u64>() >= target_amount {
+                            (vec![], s_notes, vec![], Amount::zero())
+                        } else {
+                            selection
+                        }
+                    } else {
+                        selection
+                    }
+                };
+
+                // Orchard notes can't be spent on BitcoinZ, so they don't count towards the target
+                if o_notes.len() > 0 {
+                    warn!("Skipping {} Orchard notes as BitcoinZ doesn't support Orchard", o_notes.len());
+                }
+
+                let selected_value = utxos.iter().map(|u| u.value).sum::<u64>()
+                    + s_notes.iter().map(|n| n.note.value).sum::<u64>();
+                if selected_value < target_amount {
+                    let e = format!(
+                        "Insufficient verified funds. Have {} zats, need {} zats. NOTE: funds need at least {} confirmations before they can be spent.",
+                        selected_value, target_amount, self.config.anchor_offset + 1
+                    );
+                    error!("{}", e);
+                    return Err(e);
+                }
+
+                let needed = fee_policy.fee(utxos.len(), t_outputs + t_change, s_notes.len(), s_outputs + s_change);
+                if needed <= fee {
+                    fee = needed;
+                    break (s_notes, utxos);
+                }
+                fee = needed;
+            }
+        };
+        info!("Transaction fee: {} zatoshis", fee);
+
+        let proposal = TxProposal {
+            id: OsRng.next_u32(),
+            created: now(),
+            target_height,
+            transparent_only,
+            tos: tos
+                .iter()
+                .map(|(a, v, m)| (a.to_string(), *v, m.clone()))
+                .collect(),
+            fee,
+            utxos: utxos.clone(),
+            s_notes: s_notes
+                .iter()
+                .map(|n| (n.txid, n.nullifier, n.note.value))
+                .collect(),
+            t_outputs,
+            s_outputs,
+            change_address,
+            transparent_change,
+        };
+        proposal.check_privacy(privacy_policy)?;
+
+        Ok((proposal, s_notes, utxos))
+    }
+
+    // The source address has to be one of ours. Its funds can be either transparent or sapling.
+    async fn source_is_transparent(&self, from: &SendFrom) -> Result<bool, String> {
+        let keys = self.keys.read().await;
+        if keys.get_all_taddrs().contains(&from.address) {
+            Ok(true)
+        } else if keys.get_all_zaddresses().contains(&from.address) {
+            Ok(false)
+        } else {
+            Err(format!("{} is not an address in this wallet", from.address))
+        }
+    }
+
+    // Count the outputs going to each pool, which the fee policy may charge for. Unified addresses are
+    // paid to their sapling receiver if they have one.
+    fn count_outputs<S: AsRef<str>>(
+        config: &LightClientConfig<P>,
+        tos: &[(S, u64, Option<String>)],
+    ) -> Result<(usize, usize), String> {
+        Ok(Self::decode_recipients(config, tos)?
+            .iter()
+            .map(|(to, _, _)| match to {
+                address::RecipientAddress::Unified(ua) if ua.sapling().is_none() => (1, 0),
+                address::RecipientAddress::Transparent(_) => (1, 0),
+                _ => (0, 1),
+            })
+            .fold((0, 0), |(t, s), (t2, s2)| (t + t2, s + s2)))
+    }
+
+    // Convert address (str) to RecepientAddress and value to Amount
+    fn decode_recipients<S: AsRef<str>>(
+        config: &LightClientConfig<P>,
+        tos: &[(S, u64, Option<String>)],
+    ) -> Result<Vec<(address::RecipientAddress, Amount, Option<String>)>, String> {
+        tos.iter()
+            .map(|to| {
+                let ra = match address::RecipientAddress::decode(&config.get_params(), to.0.as_ref()) {
+                    Some(to) => to,
                     None => {
-                        // Something is very wrong
-                        let e = format!("Couldn't find the secreykey for taddr {}", utxo.address);
+                        let e = format!("Invalid recipient address: '{}'", to.0.as_ref());
                         error!("{}", e);
-
-                        Err(zcash_primitives::transaction::builder::Error::InvalidAmount)
+                        return Err(e);
                     }
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("{:?}", e))?;
-
-        // Skip Orchard notes for BitcoinZ
-        if o_notes.len() > 0 {
-            warn!("Skipping {} Orchard notes as BitcoinZ doesn't support Orchard", o_notes.len());
-        }
-
-        // Add Sapling notes
-        for selected in s_notes.iter() {
-            if let Err(e) = builder.add_sapling_spend(
-                selected.extsk.clone(),
-                selected.diversifier,
-                selected.note.clone(),
-                selected.witness.path().unwrap(),
-            ) {
-                let e = format!("Error adding sapling note: {:?}", e);
-                error!("{}", e);
-                return Err(e);
-            } else {
-                change += selected.note.value;
-            }
-        }
+                };
 
-        // BitcoinZ doesn't need Orchard addresses
+                let value = Amount::from_u64(to.1).unwrap();
 
-        // We'll use the first ovk to encrypt outgoing Txns
-        let s_ovk = self.keys.read().await.zkeys[0].extfvk.fvk.ovk;
-        // BitcoinZ doesn't use Orchard OVK
+                Ok((ra, value, to.2.clone()))
+            })
+            .collect()
+    }
 
+    // Add an output for each recipient, with outgoing metadata encrypted to `ovk`. Returns the number of shielded outputs.
+    fn add_recipients(
+        builder: &mut Builder<'_, P, OsRng>,
+        ovk: OutgoingViewingKey,
+        recepients: Vec<(address::RecipientAddress, Amount, Option<String>)>,
+        start_time: u64,
+    ) -> Result<u32, String> {
         let mut total_z_recepients = 0u32;
-        let mut total_o_recepients = 0u32;
         for (to, value, memo) in recepients {
             // Compute memo if it exists
             let encoded_memo = match memo {
@@ -1531,17 +2737,15 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
                 }
             };
 
-            println!("{}: Adding output", now() - start_time);
+            debug!("{}: Adding output", now() - start_time);
 
             if let Err(e) = match to {
                 address::RecipientAddress::Unified(to) => {
                     // BitcoinZ doesn't support Orchard, so use Sapling component if available
                     if let Some(sapling_addr) = to.sapling() {
                         total_z_recepients += 1;
-                        change -= u64::from(value);
-                        builder.add_sapling_output(Some(s_ovk), sapling_addr.clone(), value, encoded_memo)
+                        builder.add_sapling_output(Some(ovk), sapling_addr.clone(), value, encoded_memo)
                     } else if let Some(t_addr) = to.transparent() {
-                        change -= u64::from(value);
                         builder.add_transparent_output(&t_addr, value)
                     } else {
                         return Err("Unified address has no supported receivers for BitcoinZ".to_string());
@@ -1549,11 +2753,9 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
                 }
                 address::RecipientAddress::Shielded(to) => {
                     total_z_recepients += 1;
-                    change -= u64::from(value);
-                    builder.add_sapling_output(Some(s_ovk), to.clone(), value, encoded_memo)
+                    builder.add_sapling_output(Some(ovk), to.clone(), value, encoded_memo)
                 }
                 address::RecipientAddress::Transparent(to) => {
-                    change -= u64::from(value);
                     builder.add_transparent_output(&to, value)
                 }
             } {
@@ -1563,76 +2765,72 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
             }
         }
 
-        // Change
-        // BitcoinZ doesn't support Orchard, so always send change to Sapling
-        change -= u64::from(DEFAULT_FEE);
-        if change > 0 {
-            // Always send to sapling address for BitcoinZ
-            builder.send_change_to(
-                self.keys.read().await.zkeys[0].extfvk.fvk.ovk,
-                self.keys.read().await.zkeys[0].zaddress.clone(),
-            );
-        }
-
-        // Set up a channel to recieve updates on the progress of building the transaction.
-        let progress = self.send_progress.clone();
+        Ok(total_z_recepients)
+    }
 
-        // Use a separate thread to handle sending from std::mpsc to tokio::sync::mpsc
-        let (tx2, mut rx2) = tokio::sync::mpsc::unbounded_channel();
-        std::thread::spawn(move || {
-            while let Ok(r) = progress_notifier_rx.recv() {
-                tx2.send(r.cur()).unwrap();
-            }
-        });
+    /// Use `signer` to authorize outgoing transactions instead of the spending keys in the wallet, or go back to
+    /// the wallet's own keys with `None`
+    pub async fn set_signer(&self, signer: Option<Arc<dyn Signer>>) {
+        *self.signer.write().await = signer;
+    }
 
-        let progress_handle = tokio::spawn(async move {
-            while let Some(r) = rx2.recv().await {
-                println!("Progress: {}", r);
-                progress.write().await.progress = r;
-            }
+    // Build the transaction for `proposal` from the already selected inputs, have it signed and broadcast it
+    async fn send_selected<F, Fut, PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        proposal: &TxProposal,
+        s_notes: Vec<SpendableSaplingNote>,
+        utxos: Vec<Utxo>,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let start_time = now();
 
-            progress.write().await.is_send_in_progress = false;
-        });
+        let target_height = match self.get_target_height().await {
+            Some(h) => BlockHeight::from_u32(h),
+            None => return Err("No blocks in wallet to target, please sync first".to_string()),
+        };
 
-        {
-            // TODO(orchard): Orchard building progress
-            let mut p = self.send_progress.write().await;
-            p.is_send_in_progress = true;
-            p.progress = 0;
-            p.total = s_notes.len() as u32 + total_z_recepients + total_o_recepients;
+        debug!(
+            "{}: Adding {} s_notes and {} utxos",
+            now() - start_time,
+            s_notes.len(),
+            utxos.len()
+        );
+        let mut utx = self
+            .package_unsigned_tx(u32::from(target_height), proposal.clone(), &s_notes, utxos.clone())
+            .await?;
+
+        // BitcoinZ doesn't support Orchard, so unless the change goes back to the source address, it goes to the
+        // first sapling address, and we'll use its ovk to encrypt outgoing Txns
+        if proposal.change_address.is_none() {
+            let keys = self.keys.read().await;
+            utx.change_address = keys.zkeys[0].zaddress.clone();
+            utx.ovk = keys.zkeys[0].extfvk.fvk.ovk;
         }
 
-        println!("{}: Building transaction", now() - start_time);
-        info!("Building transaction with {} sapling notes, {} orchard notes, {} transparent inputs", s_notes.len(), o_notes.len(), utxos.len());
-        println!("[DEBUG] About to call builder.build() with prover");
-        let (tx, _) = match builder.build(&prover) {
-            Ok(res) => {
-                println!("[DEBUG] builder.build() succeeded");
-                res
-            },
-            Err(e) => {
-                let e = format!("Error creating transaction: {:?}", e);
-                error!("{}", e);
-                println!("[DEBUG] builder.build() failed with error: {}", e);
-                self.send_progress.write().await.is_send_in_progress = false;
-                return Err(e);
+        let signer = self.signer.read().await.clone();
+        let raw_tx = match signer {
+            Some(signer) => {
+                info!(
+                    "Sending transaction with {} sapling notes, {} transparent inputs to the signer",
+                    s_notes.len(),
+                    utxos.len()
+                );
+                signer.sign(&utx).await?
             }
+            None => self.sign_with_progress(prover, &utx).await?,
         };
 
-        // Wait for all the progress to be updated
-        progress_handle.await.unwrap();
+        let branch_id = consensus::BranchId::for_height(&self.config.get_params(), target_height);
+        let tx = Transaction::read(&raw_tx[..], branch_id)
+            .map_err(|e| format!("Couldn't read the signed transaction: {}", e))?;
 
-        println!("{}: Transaction created", now() - start_time);
-        println!("Transaction ID: {}", tx.txid());
+        debug!("{}: Transaction created", now() - start_time);
         info!("Transaction created with txid: {}", tx.txid());
-
-        {
-            self.send_progress.write().await.is_send_in_progress = false;
-        }
-
-        // Create the TX bytes
-        let mut raw_tx = vec![];
-        tx.write(&mut raw_tx).unwrap();
         info!("Transaction size: {} bytes", raw_tx.len());
 
         info!("Broadcasting transaction to network...");
@@ -1641,25 +2839,8 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
 
         // Mark notes as spent.
         {
-            // Mark sapling and orchard notes as unconfirmed spent
+            // Mark sapling notes as unconfirmed spent
             let mut txs = self.txns.write().await;
-            for selected in o_notes {
-                let mut spent_note = txs
-                    .current
-                    .get_mut(&selected.txid)
-                    .unwrap()
-                    .o_notes
-                    .iter_mut()
-                    .find(|nd| {
-                        nd.note.nullifier(&nd.fvk)
-                            == selected
-                                .note
-                                .nullifier(&orchard::keys::FullViewingKey::from(&selected.sk))
-                    })
-                    .unwrap();
-                spent_note.unconfirmed_spent = Some((tx.txid(), u32::from(target_height)));
-            }
-
             for selected in s_notes {
                 let mut spent_note = txs
                     .current
@@ -1706,6 +2887,354 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
         Ok((txid, raw_tx))
     }
 
+    // Sign with the keys in the wallet, reporting the progress of building the transaction in `send_progress`
+    async fn sign_with_progress<PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        utx: &UnsignedTx,
+    ) -> Result<Vec<u8>, String> {
+        let total_z_recepients = Self::decode_recipients(&self.config, &utx.tos)?
+            .iter()
+            .filter(|(to, _, _)| match to {
+                address::RecipientAddress::Unified(ua) => ua.sapling().is_some(),
+                address::RecipientAddress::Shielded(_) => true,
+                address::RecipientAddress::Transparent(_) => false,
+            })
+            .count() as u32;
+
+        // Set up a channel to recieve updates on the progress of building the transaction.
+        let (progress_notifier, progress_notifier_rx) = mpsc::channel();
+        let progress = self.send_progress.clone();
+        let events = self.events.clone();
+
+        // Use a separate thread to handle sending from std::mpsc to tokio::sync::mpsc
+        let (tx2, mut rx2) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while let Ok(r) = progress_notifier_rx.recv() {
+                tx2.send(r.cur()).unwrap();
+            }
+        });
+
+        let progress_handle = tokio::spawn(async move {
+            while let Some(r) = rx2.recv().await {
+                let mut p = progress.write().await;
+                p.progress = r;
+                events.emit(WalletEvent::SendProgress(p.clone()));
+            }
+
+            progress.write().await.is_send_in_progress = false;
+        });
+
+        {
+            // TODO(orchard): Orchard building progress
+            let mut p = self.send_progress.write().await;
+            p.is_send_in_progress = true;
+            p.progress = 0;
+            p.total = utx.s_spends.len() as u32 + total_z_recepients;
+            self.events.emit(WalletEvent::SendProgress(p.clone()));
+        }
+
+        // The notifier is dropped with the signer, which ends the progress updates even if signing fails
+        let result = {
+            let signer = KeysSigner::new(self.config.clone(), self.keys.clone(), prover)
+                .with_progress_notifier(progress_notifier);
+            signer.sign(utx).await
+        };
+
+        // Wait for all the progress to be updated
+        progress_handle.await.unwrap();
+
+        result
+    }
+
+    // Package the selected inputs of `proposal` with their witnesses. The change goes back to the owner of the
+    // spent sapling notes.
+    async fn package_unsigned_tx(
+        &self,
+        target_height: u32,
+        proposal: TxProposal,
+        s_notes: &[SpendableSaplingNote],
+        utxos: Vec<Utxo>,
+    ) -> Result<UnsignedTx, String> {
+        let txns = self.txns.read().await;
+        let s_spends = s_notes
+            .iter()
+            .map(|n| {
+                let nd = txns
+                    .current
+                    .get(&n.txid)
+                    .and_then(|tx| tx.s_notes.iter().find(|nd| nd.nullifier == n.nullifier))
+                    .ok_or(format!("Sapling note in {} is missing", n.txid))?;
+
+                Ok(UnsignedSaplingSpend {
+                    txid: n.txid,
+                    nullifier: n.nullifier,
+                    extfvk: nd.extfvk.clone(),
+                    diversifier: n.diversifier,
+                    note: n.note.clone(),
+                    witness: n.witness.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let anchor = s_spends.first().map(|s| s.witness.root());
+        if s_spends.iter().any(|s| Some(s.witness.root()) != anchor) {
+            return Err("The selected notes' witnesses are at different heights, please sync first".to_string());
+        }
+
+        let owner = match s_spends.first() {
+            Some(s) => s.extfvk.clone(),
+            None => self.keys.read().await.zkeys[0].extfvk.clone(),
+        };
+
+        // Change to a t address can't be added by the builder, so it is paid like any other recipient
+        let mut tos = proposal.tos.clone();
+        let mut change = proposal.change();
+        let mut change_address = owner.default_address().1;
+        match proposal.change_address.as_ref() {
+            Some(address) if proposal.transparent_change => {
+                if change > 0 {
+                    tos.push((address.clone(), change, None));
+                    change = 0;
+                }
+            }
+            Some(address) => {
+                change_address = match address::RecipientAddress::decode(&self.config.get_params(), address) {
+                    Some(address::RecipientAddress::Shielded(pa)) => pa,
+                    _ => return Err(format!("Can't send the change to {}", address)),
+                };
+            }
+            None => {}
+        }
+
+        Ok(UnsignedTx {
+            target_height,
+            fee: proposal.fee,
+            anchor,
+            s_spends,
+            utxos,
+            change,
+            tos,
+            change_address,
+            ovk: owner.fvk.ovk,
+        })
+    }
+
+    /// Select inputs from the notes of viewing keys for sending to `tos`, and package them with their witnesses into
+    /// an unsigned transaction for an offline signer that has the spending keys. Utxos are only spent if they are
+    /// chosen in `inputs`. Needs the `offline_signing` option, so the witnesses of those notes are kept up to date.
+    pub async fn create_unsigned_tx(
+        &self,
+        tos: Vec<(&str, u64, Option<String>)>,
+        fee: Option<u64>,
+        inputs: Option<&CoinSelection>,
+    ) -> Result<UnsignedTx, String> {
+        if !self.wallet_options.read().await.offline_signing {
+            return Err("Offline signing is not enabled. Set the 'offline_signing' option and rescan first".to_string());
+        }
+
+        let (proposal, s_notes, utxos) = self.propose_internal(false, tos, fee, inputs, None, true).await?;
+        proposal.check_size()?;
+
+        self.package_unsigned_tx(proposal.target_height, proposal, &s_notes, utxos)
+            .await
+    }
+
+    /// Prove and sign an unsigned transaction created by a watch-only wallet, with the spending keys in this wallet.
+    /// Returns the txid and the raw transaction, for the watch-only wallet to broadcast.
+    pub async fn sign_unsigned_tx<PR: TxProver + Send + Sync>(
+        &self,
+        prover: PR,
+        utx: &UnsignedTx,
+    ) -> Result<(String, Vec<u8>), String> {
+        let raw_tx = KeysSigner::new(self.config.clone(), self.keys.clone(), prover)
+            .sign(utx)
+            .await?;
+
+        let tx = Transaction::read(
+            &raw_tx[..],
+            consensus::BranchId::for_height(&self.config.get_params(), BlockHeight::from_u32(utx.target_height)),
+        )
+        .map_err(|e| format!("Couldn't read the signed transaction: {}", e))?;
+
+        Ok((tx.txid().to_string(), raw_tx))
+    }
+
+    /// Broadcast a transaction signed offline, and mark the inputs it spends as spent
+    pub async fn broadcast_signed_tx<F, Fut>(&self, raw_tx: Vec<u8>, broadcast_fn: F) -> Result<String, String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let height = match self.get_target_height().await {
+            Some(h) => BlockHeight::from_u32(h),
+            None => return Err("No blocks in wallet to target, please sync first".to_string()),
+        };
+
+        let tx = Transaction::read(&raw_tx[..], consensus::BranchId::for_height(&self.config.get_params(), height))
+            .map_err(|e| format!("Couldn't read the signed transaction: {}", e))?;
+
+        let txid = broadcast_fn(raw_tx.into_boxed_slice()).await?;
+
+        // Mark the notes and utxos it spends as unconfirmed spent
+        {
+            let mut txs = self.txns.write().await;
+            let spent = (tx.txid(), u32::from(height));
+
+            if let Some(s_bundle) = tx.sapling_bundle() {
+                for spend in s_bundle.shielded_spends.iter() {
+                    txs.current
+                        .values_mut()
+                        .flat_map(|wtx| wtx.s_notes.iter_mut())
+                        .filter(|nd| nd.nullifier == spend.nullifier)
+                        .for_each(|nd| nd.unconfirmed_spent = Some(spent));
+                }
+            }
+
+            if let Some(t_bundle) = tx.transparent_bundle() {
+                for vin in t_bundle.vin.iter() {
+                    let prev_txid = TxId::from_bytes(*vin.prevout.hash());
+                    if let Some(wtx) = txs.current.get_mut(&prev_txid) {
+                        wtx.utxos
+                            .iter_mut()
+                            .filter(|u| u.output_index == vin.prevout.n() as u64)
+                            .for_each(|u| u.unconfirmed_spent = Some(spent));
+                    }
+                }
+            }
+        }
+
+        // Add this Tx to the mempool structure
+        let price = self.price.read().await.clone();
+        FetchFullTxns::<P>::scan_full_tx(
+            self.config.clone(),
+            tx,
+            height,
+            true,
+            now() as u32,
+            self.keys.clone(),
+            self.txns.clone(),
+            WalletTx::get_price(now(), &price),
+        )
+        .await;
+
+        Ok(txid)
+    }
+
+    /// Start a transaction spending the funds of the multisig address `from`, for the co-signers to sign. Only
+    /// transparent addresses can be paid, and the change goes back to the multisig address.
+    pub async fn create_multisig_tx(
+        &self,
+        from: &str,
+        tos: Vec<(&str, u64)>,
+        fee: Option<u64>,
+    ) -> Result<PartiallySignedTx, String> {
+        let multisig = self
+            .keys
+            .read()
+            .await
+            .get_multisig(from)
+            .cloned()
+            .ok_or(format!("{} is not a multisig address in this wallet", from))?;
+
+        if tos.is_empty() {
+            return Err("Need at least one destination address".to_string());
+        }
+        for (to, _) in tos.iter() {
+            match address::RecipientAddress::decode(&self.config.get_params(), to) {
+                Some(address::RecipientAddress::Transparent(_)) => {}
+                _ => return Err(format!("Multisig funds can only be sent to transparent addresses, not {}", to)),
+            }
+        }
+
+        // Multisig funds are transparent and only go to transparent addresses, so everything about this is public
+        self.privacy_policy().await.check(TxPrivacy::Transparent)?;
+
+        let target_height = match self.get_target_height().await {
+            Some(h) => h,
+            None => return Err("No blocks in wallet to target, please sync first".to_string()),
+        };
+
+        let fee_policy = match fee {
+            Some(fee) => FeePolicy::Fixed(fee),
+            None => self.fee_policy().await,
+        };
+
+        // Select the largest utxos first, until they pay for the outputs and the fee. The change is counted as an
+        // output for the fee.
+        let total_value = tos.iter().map(|to| to.1).sum::<u64>();
+        let mut candidates = self
+            .get_multisig_utxos(from)
+            .await
+            .into_iter()
+            .filter(|u| u.unconfirmed_spent.is_none() && !u.frozen)
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.value.cmp(&a.value));
+
+        let mut inputs = vec![];
+        let mut selected_value = 0;
+        let mut fee = fee_policy.fee(0, tos.len() + 1, 0, 0);
+        for utxo in candidates {
+            if selected_value >= total_value + fee {
+                break;
+            }
+            selected_value += utxo.value;
+            inputs.push(utxo);
+            fee = fee_policy.fee(inputs.len(), tos.len() + 1, 0, 0);
+        }
+
+        if selected_value < total_value + fee {
+            return Err(format!(
+                "Insufficient funds in {}. Have {} zats, need {} zats (including fee of {} zats)",
+                from,
+                selected_value,
+                total_value + fee,
+                fee
+            ));
+        }
+
+        Ok(PartiallySignedTx::new(
+            target_height,
+            multisig,
+            inputs,
+            tos.into_iter().map(|(to, value)| (to.to_string(), value)).collect(),
+            fee,
+            selected_value - total_value - fee,
+        ))
+    }
+
+    /// Sign a multisig transaction with the keys of this wallet's t addresses that are keys of the multisig
+    /// address. Returns how many of them signed.
+    pub async fn sign_multisig_tx(&self, pstx: &mut PartiallySignedTx) -> Result<usize, String> {
+        let keys = self.keys.read().await;
+        if !keys.unlocked {
+            return Err("Cannot sign while wallet is locked".to_string());
+        }
+
+        let address_to_sk = keys.get_taddr_to_sk_map();
+        let sks = address_to_sk.values().collect::<Vec<_>>();
+        let signed = pstx.sign(&self.config.get_params(), &sks)?;
+        if signed == 0 {
+            return Err(format!("This wallet has none of the keys of {}", pstx.multisig.address));
+        }
+
+        Ok(signed)
+    }
+
+    /// Broadcast a multisig transaction that has all the signatures it needs
+    pub async fn broadcast_multisig_tx<F, Fut>(
+        &self,
+        pstx: &PartiallySignedTx,
+        broadcast_fn: F,
+    ) -> Result<String, String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let raw_tx = pstx.finalize(&self.config.get_params())?;
+        self.broadcast_signed_tx(raw_tx, broadcast_fn).await
+    }
+
     pub async fn encrypt(&self, passwd: String) -> io::Result<()> {
         self.keys.write().await.encrypt(passwd)
     }
@@ -1715,11 +3244,103 @@ impl<P: consensus::Parameters + Send + Sync + 'static> LightWallet<P> {
     }
 
     pub async fn unlock(&self, passwd: String) -> io::Result<()> {
+        self.open_sealed(&passwd).await?;
         self.keys.write().await.unlock(passwd)
     }
 
+    pub async fn change_password(&self, old_passwd: String, new_passwd: String) -> io::Result<()> {
+        self.open_sealed(&old_passwd).await?;
+
+        // Derive the new file key first, so a failure doesn't leave the file and keys with different passwords
+        let envelope = if self.envelope.read().await.is_some() {
+            Some(WalletEnvelope::new(&new_passwd)?)
+        } else {
+            None
+        };
+
+        self.keys.write().await.change_password(old_passwd, new_passwd)?;
+
+        if envelope.is_some() {
+            *self.envelope.write().await = envelope;
+        }
+
+        Ok(())
+    }
+
     pub async fn remove_encryption(&self, passwd: String) -> io::Result<()> {
-        self.keys.write().await.remove_encryption(passwd)
+        self.open_sealed(&passwd).await?;
+        self.keys.write().await.remove_encryption(passwd)?;
+
+        // Without a password, the file can't stay encrypted either
+        *self.envelope.write().await = None;
+
+        Ok(())
+    }
+
+    /// Turn encryption of the whole wallet file on or off. The wallet's keys have to be encrypted already,
+    /// and the file is encrypted under the same password.
+    pub async fn set_file_encryption(&self, passwd: String, enabled: bool) -> io::Result<()> {
+        self.open_sealed(&passwd).await?;
+        self.keys.read().await.check_password(&passwd)?;
+
+        *self.envelope.write().await = if enabled {
+            Some(WalletEnvelope::new(&passwd)?)
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+
+    pub async fn is_file_encrypted(&self) -> bool {
+        self.envelope.read().await.is_some() || self.sealed.read().await.is_some()
+    }
+
+    /// Is the wallet file encrypted and not yet unlocked? If so, its keys and history haven't been loaded.
+    pub async fn is_sealed(&self) -> bool {
+        self.sealed.read().await.is_some()
+    }
+
+    // Decrypt a wallet whose file is encrypted, and load its keys and history. The keys stay locked.
+    async fn open_sealed(&self, passwd: &str) -> io::Result<()> {
+        let mut sealed = self.sealed.write().await;
+        let (envelope, plaintext) = match sealed.as_ref() {
+            Some(s) => WalletEnvelope::open(&s[..], passwd)?,
+            None => return Ok(()),
+        };
+
+        let inner = Self::read(&plaintext[..], &self.config).await?;
+        if inner.is_sealed().await {
+            return Err(Error::new(ErrorKind::InvalidData, "Wallet file is encrypted more than once"));
+        }
+
+        // Swap in the contents rather than the Arcs, since they may already be shared with the syncer
+        std::mem::swap(&mut *self.keys.write().await, &mut *inner.keys.write().await);
+        std::mem::swap(&mut *self.txns.write().await, &mut *inner.txns.write().await);
+        std::mem::swap(&mut *self.blocks.write().await, &mut *inner.blocks.write().await);
+        std::mem::swap(
+            &mut *self.wallet_options.write().await,
+            &mut *inner.wallet_options.write().await,
+        );
+        std::mem::swap(
+            &mut *self.verified_tree.write().await,
+            &mut *inner.verified_tree.write().await,
+        );
+        std::mem::swap(
+            &mut *self.orchard_witnesses.write().await,
+            &mut *inner.orchard_witnesses.write().await,
+        );
+        std::mem::swap(&mut *self.backfill.write().await, &mut *inner.backfill.write().await);
+        std::mem::swap(&mut *self.price.write().await, &mut *inner.price.write().await);
+        self.birthday.store(
+            inner.birthday.load(std::sync::atomic::Ordering::SeqCst),
+            std::sync::atomic::Ordering::SeqCst,
+        );
+
+        *self.envelope.write().await = Some(envelope);
+        *sealed = None;
+
+        Ok(())
     }
 }
 
@@ -1761,7 +3382,7 @@ mod test {
         let amt = Amount::from_u64(10_000).unwrap();
         // Reset the anchor offsets
         lc.wallet.config.anchor_offset = 0;
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected >= amt);
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].note.value, value);
@@ -1778,14 +3399,14 @@ mod test {
 
         // With min anchor_offset at 1, we can't select any notes
         lc.wallet.config.anchor_offset = 1;
-        let (_, notes, utxos, _selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, _selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert_eq!(notes.len(), 0);
         assert_eq!(utxos.len(), 0);
 
         // Mine 1 block, then it should be selectable
         mine_random_blocks(&mut fcbl, &data, &lc, 1).await;
 
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected >= amt);
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].note.value, value);
@@ -1803,7 +3424,7 @@ mod test {
         // Mine 15 blocks, then selecting the note should result in witness only 10 blocks deep
         mine_random_blocks(&mut fcbl, &data, &lc, 15).await;
         lc.wallet.config.anchor_offset = 9;
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true, None).await;
         assert!(selected >= amt);
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].note.value, value);
@@ -1820,13 +3441,13 @@ mod test {
 
         // Trying to select a large amount will fail
         let amt = Amount::from_u64(1_000_000).unwrap();
-        let (_, _, _, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, _, _, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected < amt);
 
         // 4. Get an incoming tx to a t address
         let sk = lc.wallet.keys().read().await.tkeys[0].clone();
         let pk = sk.pubkey().unwrap();
-        let taddr = sk.address;
+        let taddr = sk.address.clone();
         let tvalue = 100_000;
 
         let mut ftx = FakeTransaction::new();
@@ -1836,14 +3457,14 @@ mod test {
 
         // Trying to select a large amount will now succeed
         let amt = Amount::from_u64(value + tvalue - 10_000).unwrap();
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true, None).await;
         assert_eq!(selected, Amount::from_u64(value + tvalue).unwrap());
         assert_eq!(notes.len(), 1);
         assert_eq!(utxos.len(), 1);
 
         // If we set transparent-only = true, only the utxo should be selected
         let amt = Amount::from_u64(tvalue - 10_000).unwrap();
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, true, true).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, true, true, None).await;
         assert_eq!(selected, Amount::from_u64(tvalue).unwrap());
         assert_eq!(notes.len(), 0);
         assert_eq!(utxos.len(), 1);
@@ -1851,7 +3472,7 @@ mod test {
         // Set min confs to 5, so the sapling note will not be selected
         lc.wallet.config.anchor_offset = 4;
         let amt = Amount::from_u64(tvalue - 10_000).unwrap();
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, true, None).await;
         assert_eq!(selected, Amount::from_u64(tvalue).unwrap());
         assert_eq!(notes.len(), 0);
         assert_eq!(utxos.len(), 1);
@@ -1886,7 +3507,7 @@ mod test {
         let amt = Amount::from_u64(10_000).unwrap();
         // Reset the anchor offsets
         lc.wallet.config.anchor_offset = 0;
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected >= amt);
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].note.value, value1);
@@ -1910,7 +3531,7 @@ mod test {
         mine_pending_blocks(&mut fcbl, &data, &lc).await;
 
         let amt = Amount::from_u64(10_000).unwrap();
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected >= amt);
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].note.value, value2);
@@ -1918,7 +3539,7 @@ mod test {
 
         // Selecting a bigger amount should select both notes
         let amt = Amount::from_u64(value1 + value2).unwrap();
-        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false).await;
+        let (_, notes, utxos, selected) = lc.wallet.select_notes_and_utxos(amt, false, false, None).await;
         assert!(selected == amt);
         assert_eq!(notes.len(), 2);
         assert_eq!(utxos.len(), 0);
@@ -346,10 +346,17 @@ pub struct SaplingNoteData {
 
     // If the spending key is available in the wallet (i.e., whether to keep witness up-to-date)
     pub have_spending_key: bool,
+
+    // Index of the output in the tx's sapling bundle. Notes read from older wallets don't have it until they are
+    // found again by a rescan.
+    pub output_index: Option<u32>,
+
+    // Frozen notes are never selected for spending, and are not counted in the spendable balances
+    pub frozen: bool,
 }
 
 // Reading a note also needs the corresponding address to read from.
-fn read_rseed<R: Read>(mut reader: R) -> io::Result<Rseed> {
+pub(super) fn read_rseed<R: Read>(mut reader: R) -> io::Result<Rseed> {
     let note_type = reader.read_u8()?;
 
     let mut r_bytes: [u8; 32] = [0; 32];
@@ -364,7 +371,7 @@ fn read_rseed<R: Read>(mut reader: R) -> io::Result<Rseed> {
     Ok(r)
 }
 
-fn write_rseed<W: Write>(mut writer: W, rseed: &Rseed) -> io::Result<()> {
+pub(super) fn write_rseed<W: Write>(mut writer: W, rseed: &Rseed) -> io::Result<()> {
     let note_type = match rseed {
         Rseed::BeforeZip212(_) => 1,
         Rseed::AfterZip212(_) => 2,
@@ -379,7 +386,7 @@ fn write_rseed<W: Write>(mut writer: W, rseed: &Rseed) -> io::Result<()> {
 
 impl SaplingNoteData {
     fn serialized_version() -> u64 {
-        20
+        22
     }
 
     // Reading a note also needs the corresponding address to read from.
@@ -510,6 +517,14 @@ impl SaplingNoteData {
             reader.read_u8()? > 0
         };
 
+        let output_index = if version <= 20 {
+            None
+        } else {
+            Optional::read(&mut reader, |r| r.read_u32::<LittleEndian>())?
+        };
+
+        let frozen = if version <= 21 { false } else { reader.read_u8()? > 0 };
+
         Ok(SaplingNoteData {
             extfvk,
             diversifier,
@@ -521,6 +536,8 @@ impl SaplingNoteData {
             memo,
             is_change,
             have_spending_key,
+            output_index,
+            frozen,
         })
     }
 
@@ -561,6 +578,10 @@ impl SaplingNoteData {
 
         writer.write_u8(if self.have_spending_key { 1 } else { 0 })?;
 
+        Optional::write(&mut writer, self.output_index, |w, i| w.write_u32::<LittleEndian>(i))?;
+
+        writer.write_u8(if self.frozen { 1 } else { 0 })?;
+
         // Note that we don't write the unconfirmed_spent field, because if the wallet is restarted,
         // we don't want to be beholden to any expired txns
 
@@ -583,11 +604,14 @@ pub struct Utxo {
     // If this utxo was spent in a send, but has not yet been confirmed.
     // Contains the txid and height at which the Tx was broadcast
     pub unconfirmed_spent: Option<(TxId, u32)>,
+
+    // Frozen utxos are never selected for spending or shielding, and are not counted in the spendable balances
+    pub frozen: bool,
 }
 
 impl Utxo {
     pub fn serialized_version() -> u64 {
-        return 3;
+        return 4;
     }
 
     pub fn to_outpoint(&self) -> OutPoint {
@@ -641,6 +665,8 @@ impl Utxo {
             })?
         };
 
+        let frozen = if version <= 3 { false } else { reader.read_u8()? > 0 };
+
         Ok(Utxo {
             address,
             txid,
@@ -651,6 +677,7 @@ impl Utxo {
             spent_at_height,
             spent,
             unconfirmed_spent,
+            frozen,
         })
     }
 
@@ -677,6 +704,8 @@ impl Utxo {
             w.write_u32::<LittleEndian>(height)
         })?;
 
+        writer.write_u8(if self.frozen { 1 } else { 0 })?;
+
         Ok(())
     }
 }
@@ -955,7 +984,9 @@ pub struct SpendableSaplingNote {
     pub diversifier: Diversifier,
     pub note: sapling::Note,
     pub witness: IncrementalWitness<Node>,
-    pub extsk: ExtendedSpendingKey,
+
+    // None for notes of viewing keys, which are signed for by an offline signer
+    pub extsk: Option<ExtendedSpendingKey>,
 }
 
 impl SpendableSaplingNote {
@@ -963,14 +994,28 @@ impl SpendableSaplingNote {
         txid: TxId,
         nd: &SaplingNoteData,
         anchor_offset: usize,
-        extsk: &Option<ExtendedSpendingKey>,
+        extsk: Option<&ExtendedSpendingKey>,
+    ) -> Option<Self> {
+        if extsk.is_some() {
+            Self::new(txid, nd, anchor_offset, extsk.cloned())
+        } else {
+            None
+        }
+    }
+
+    /// A note of a viewing key, that can be put into an unsigned transaction for an offline signer
+    pub fn watch_only(txid: TxId, nd: &SaplingNoteData, anchor_offset: usize) -> Option<Self> {
+        Self::new(txid, nd, anchor_offset, None)
+    }
+
+    fn new(
+        txid: TxId,
+        nd: &SaplingNoteData,
+        anchor_offset: usize,
+        extsk: Option<ExtendedSpendingKey>,
     ) -> Option<Self> {
         // Include only notes that haven't been spent, or haven't been included in an unconfirmed spend yet.
-        if nd.spent.is_none()
-            && nd.unconfirmed_spent.is_none()
-            && extsk.is_some()
-            && nd.witnesses.len() >= (anchor_offset + 1)
-        {
+        if nd.spent.is_none() && nd.unconfirmed_spent.is_none() && nd.witnesses.len() >= (anchor_offset + 1) {
             let witness = nd.witnesses.get(nd.witnesses.len() - anchor_offset - 1);
 
             witness.map(|w| SpendableSaplingNote {
@@ -979,7 +1024,7 @@ impl SpendableSaplingNote {
                 diversifier: nd.diversifier,
                 note: nd.note.clone(),
                 witness: w.clone(),
-                extsk: extsk.clone().unwrap(),
+                extsk,
             })
         } else {
             None
@@ -987,6 +1032,63 @@ impl SpendableSaplingNote {
     }
 }
 
+impl Drop for SpendableSaplingNote {
+    fn drop(&mut self) {
+        if let Some(extsk) = self.extsk.as_mut() {
+            super::secret::wipe_extsk(extsk);
+        }
+    }
+}
+
+// Inputs picked by the user for a send, instead of by note selection. Sapling notes are identified by the txid and
+// output index that created them, and utxos by their outpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoinSelection {
+    pub s_notes: Vec<(TxId, u32)>,
+    pub utxos: Vec<(TxId, u32)>,
+}
+
+// Restricts a send to the funds received by one of the wallet's t or z addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendFrom {
+    pub address: String,
+
+    // Send the change back to `address`, instead of to the wallet's first sapling address
+    pub change_to_source: bool,
+}
+
+// Where the funds come from when sending the wallet's entire spendable balance to one recipient
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendMaxSource {
+    // Every spendable note and utxo
+    All,
+
+    // Only the utxos of the wallet's t addresses
+    Transparent,
+
+    // Only the sapling notes
+    Sapling,
+
+    // Only the funds received by this t or z address
+    Address(String),
+}
+
+impl SendMaxSource {
+    /// Parse the source as given to `sendmax`: "all", "transparent", "sapling" or one of the wallet's `addresses`
+    pub fn parse(s: &str, addresses: &[String]) -> Result<Self, String> {
+        match s {
+            "all" => Ok(SendMaxSource::All),
+            "transparent" => Ok(SendMaxSource::Transparent),
+            "sapling" => Ok(SendMaxSource::Sapling),
+            address if addresses.iter().any(|a| a == address) => Ok(SendMaxSource::Address(address.to_string())),
+            other => Err(format!(
+                "'{}' isn't 'all', 'transparent', 'sapling' or one of this wallet's addresses",
+                other
+            )),
+        }
+    }
+}
+
 // Struct that tracks the latest and historical price of ZEC in the wallet
 #[derive(Clone, Debug)]
 pub struct WalletZecPriceInfo {
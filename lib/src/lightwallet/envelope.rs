@@ -0,0 +1,96 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use sodiumoxide::crypto::secretbox;
+use zcash_encoding::Vector;
+use zeroize::Zeroizing;
+
+use super::kdf::KdfParams;
+
+// Encrypts the entire serialized wallet (keys, txns, memos, address lists and prices) under the
+// wallet password. The derived key is kept in memory after the wallet is opened, so it can be
+// re-sealed on every save without asking for the password again. The spending keys inside are
+// still encrypted on their own, so this key only ever reveals view-only data.
+pub struct WalletEnvelope {
+    kdf: KdfParams,
+    key: secretbox::Key,
+}
+
+impl WalletEnvelope {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    /// A new envelope for `passwd`, with fresh KDF params
+    pub fn new(passwd: &str) -> io::Result<Self> {
+        let kdf = KdfParams::new_argon2id();
+        let key = kdf.derive_key(passwd)?;
+
+        Ok(WalletEnvelope { kdf, key })
+    }
+
+    /// Decrypt a sealed wallet. Returns the envelope, so the wallet can be sealed again with the same
+    /// key, along with the plaintext wallet bytes.
+    pub fn open<R: Read>(mut reader: R, passwd: &str) -> io::Result<(Self, Zeroizing<Vec<u8>>)> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            let e = format!("Don't know how to read wallet envelope version {}", version);
+            return Err(io::Error::new(ErrorKind::InvalidData, e));
+        }
+
+        let kdf = KdfParams::read(&mut reader)?;
+        let nonce = Vector::read(&mut reader, |r| r.read_u8())?;
+        let cipher = Vector::read(&mut reader, |r| r.read_u8())?;
+
+        let key = kdf.derive_key(passwd)?;
+        let nonce = secretbox::Nonce::from_slice(&nonce)
+            .ok_or(io::Error::new(ErrorKind::InvalidData, "Bad wallet envelope nonce"))?;
+
+        let plaintext = secretbox::open(&cipher, &nonce, &key).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "Decryption failed. Is your password correct?",
+            )
+        })?;
+
+        Ok((WalletEnvelope { kdf, key }, Zeroizing::new(plaintext)))
+    }
+
+    /// Encrypt the serialized wallet in `plaintext` under a new nonce and write it out
+    pub fn seal<W: Write>(&self, mut writer: W, plaintext: &[u8]) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        self.kdf.write(&mut writer)?;
+
+        let nonce = secretbox::gen_nonce();
+        let cipher = secretbox::seal(plaintext, &nonce, &self.key);
+
+        Vector::write(&mut writer, nonce.as_ref(), |w, b| w.write_u8(*b))?;
+        Vector::write(&mut writer, &cipher, |w, b| w.write_u8(*b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WalletEnvelope;
+
+    #[test]
+    fn seal_open() {
+        let envelope = WalletEnvelope::new("passwd").unwrap();
+
+        let mut sealed = vec![];
+        envelope.seal(&mut sealed, b"wallet bytes").unwrap();
+        assert!(!sealed.windows(12).any(|w| w == b"wallet bytes"));
+
+        assert!(WalletEnvelope::open(&sealed[..], "wrong passwd").is_err());
+
+        let (envelope2, plaintext) = WalletEnvelope::open(&sealed[..], "passwd").unwrap();
+        assert_eq!(&plaintext[..], b"wallet bytes");
+
+        // The re-opened envelope seals with the same key
+        let mut sealed2 = vec![];
+        envelope2.seal(&mut sealed2, b"more bytes").unwrap();
+        let (_, plaintext) = WalletEnvelope::open(&sealed2[..], "passwd").unwrap();
+        assert_eq!(&plaintext[..], b"more bytes");
+    }
+}
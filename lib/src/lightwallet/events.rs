@@ -0,0 +1,149 @@
+use std::sync::{Arc, Mutex};
+
+use json::{object, JsonValue};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use super::SendProgress;
+
+/// Something that happened to the wallet, sent to subscribers as it happens so they don't have to poll for it
+#[derive(Debug, Clone)]
+pub enum WalletEvent {
+    // A sync batch is about to scan the blocks from `first_block` to `last_block`
+    SyncBatchStarted {
+        batch_num: usize,
+        batch_total: usize,
+        first_block: u64,
+        last_block: u64,
+    },
+    // A sync batch is done and saved
+    SyncBatchFinished {
+        batch_num: usize,
+        batch_total: usize,
+        latest_block: u64,
+    },
+    // The wallet has scanned up to a new height
+    HeightAdvanced { height: u64 },
+    // A transaction for the wallet that wasn't known before showed up in the mempool
+    MempoolTx { txid: String },
+    // A transaction that was in the mempool was mined
+    TxConfirmed { txid: String, height: u64 },
+    // A transaction that was in the mempool was never mined, and has been removed
+    TxExpired { txid: String },
+    // A reorg rolled the wallet back to `height`, and the blocks above it were scanned again
+    ReorgRolledBack { height: u64 },
+    // The progress of building or sending a transaction changed
+    SendProgress(SendProgress),
+    // The signer got a request to sign a transaction, which waits for the user to approve it
+    SigningRequest { id: String },
+}
+
+impl WalletEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WalletEvent::SyncBatchStarted { .. } => "sync_batch_started",
+            WalletEvent::SyncBatchFinished { .. } => "sync_batch_finished",
+            WalletEvent::HeightAdvanced { .. } => "height_advanced",
+            WalletEvent::MempoolTx { .. } => "mempool_tx",
+            WalletEvent::TxConfirmed { .. } => "tx_confirmed",
+            WalletEvent::TxExpired { .. } => "tx_expired",
+            WalletEvent::ReorgRolledBack { .. } => "reorg_rolled_back",
+            WalletEvent::SendProgress(_) => "send_progress",
+            WalletEvent::SigningRequest { .. } => "signing_request",
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut o = match self {
+            WalletEvent::SyncBatchStarted {
+                batch_num,
+                batch_total,
+                first_block,
+                last_block,
+            } => object! {
+                "batch_num" => *batch_num,
+                "batch_total" => *batch_total,
+                "first_block" => *first_block,
+                "last_block" => *last_block,
+            },
+            WalletEvent::SyncBatchFinished {
+                batch_num,
+                batch_total,
+                latest_block,
+            } => object! {
+                "batch_num" => *batch_num,
+                "batch_total" => *batch_total,
+                "latest_block" => *latest_block,
+            },
+            WalletEvent::HeightAdvanced { height } | WalletEvent::ReorgRolledBack { height } => object! {
+                "height" => *height,
+            },
+            WalletEvent::MempoolTx { txid } | WalletEvent::TxExpired { txid } => object! {
+                "txid" => txid.clone(),
+            },
+            WalletEvent::TxConfirmed { txid, height } => object! {
+                "txid" => txid.clone(),
+                "height" => *height,
+            },
+            WalletEvent::SigningRequest { id } => object! {
+                "id" => id.clone(),
+            },
+            WalletEvent::SendProgress(progress) => object! {
+                "id" => progress.id,
+                "sending" => progress.is_send_in_progress,
+                "progress" => progress.progress,
+                "total" => progress.total,
+                "txid" => progress.last_txid.clone(),
+                "error" => progress.last_error.clone(),
+                "transactions" => progress.tx_count,
+                "txids" => progress.txids.clone(),
+            },
+        };
+
+        o["event"] = self.name().into();
+        o
+    }
+}
+
+/// The subscribers to a wallet's events. Every event goes to all of them, and subscribers that have dropped their
+/// receiver are forgotten.
+#[derive(Clone, Default)]
+pub struct WalletEvents {
+    subscribers: Arc<Mutex<Vec<UnboundedSender<WalletEvent>>>>,
+}
+
+impl WalletEvents {
+    pub fn subscribe(&self) -> UnboundedReceiver<WalletEvent> {
+        let (tx, rx) = unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+
+        rx
+    }
+
+    pub fn emit(&self, event: WalletEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{WalletEvent, WalletEvents};
+
+    #[test]
+    fn events() {
+        let events = WalletEvents::default();
+        let mut rx1 = events.subscribe();
+        let rx2 = events.subscribe();
+        drop(rx2);
+
+        events.emit(WalletEvent::HeightAdvanced { height: 10 });
+        assert_eq!(events.subscribers.lock().unwrap().len(), 1);
+
+        let e = rx1.try_recv().unwrap();
+        assert_eq!(e.to_json()["event"], "height_advanced");
+        assert_eq!(e.to_json()["height"].as_u64().unwrap(), 10);
+        assert!(rx1.try_recv().is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    cmp,
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use zcash_primitives::transaction::components::amount::DEFAULT_FEE;
+
+// ZIP-317 defaults: 5000 zats for every logical action, with at least 2 actions charged
+pub const ZIP317_MARGINAL_FEE: u64 = 5_000;
+pub const ZIP317_GRACE_ACTIONS: u64 = 2;
+
+// How the fee for an outgoing transaction is computed. This is saved with the wallet options, and can be
+// overridden for a single send by passing an explicit fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeePolicy {
+    // The same fee for every transaction, regardless of its size
+    Fixed(u64),
+
+    // ZIP-317 style: `marginal_fee` for every logical action, with at least `grace_actions` actions charged.
+    // A logical action is a transparent input or output, or a sapling spend or output, whichever side is larger.
+    PerAction { marginal_fee: u64, grace_actions: u64 },
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        FeePolicy::Fixed(u64::from(DEFAULT_FEE))
+    }
+}
+
+impl FeePolicy {
+    pub fn zip317() -> Self {
+        FeePolicy::PerAction {
+            marginal_fee: ZIP317_MARGINAL_FEE,
+            grace_actions: ZIP317_GRACE_ACTIONS,
+        }
+    }
+
+    /// The fee for a transaction with the given number of transparent inputs/outputs and sapling spends/outputs
+    pub fn fee(&self, t_inputs: usize, t_outputs: usize, s_spends: usize, s_outputs: usize) -> u64 {
+        match self {
+            FeePolicy::Fixed(fee) => *fee,
+            FeePolicy::PerAction {
+                marginal_fee,
+                grace_actions,
+            } => {
+                let actions = cmp::max(t_inputs, t_outputs) + cmp::max(s_spends, s_outputs);
+                marginal_fee * cmp::max(*grace_actions, actions as u64)
+            }
+        }
+    }
+
+    /// Parse a policy as given to `setoption fee_policy=...`. One of:
+    /// "default", "fixed:<zats>", "zip317" or "zip317:<marginal zats per action>"
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parse_zats = |v: &str| {
+            v.parse::<u64>()
+                .map_err(|_| format!("Couldn't understand fee amount {}", v))
+        };
+
+        match s.split_once(':') {
+            None if s == "default" => Ok(FeePolicy::default()),
+            None if s == "zip317" => Ok(FeePolicy::zip317()),
+            Some(("fixed", zats)) => Ok(FeePolicy::Fixed(parse_zats(zats)?)),
+            Some(("zip317", zats)) => Ok(FeePolicy::PerAction {
+                marginal_fee: parse_zats(zats)?,
+                grace_actions: ZIP317_GRACE_ACTIONS,
+            }),
+            _ => Err(format!("Unknown fee policy {}", s)),
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(FeePolicy::Fixed(reader.read_u64::<LittleEndian>()?)),
+            1 => {
+                let marginal_fee = reader.read_u64::<LittleEndian>()?;
+                let grace_actions = reader.read_u64::<LittleEndian>()?;
+
+                Ok(FeePolicy::PerAction {
+                    marginal_fee,
+                    grace_actions,
+                })
+            }
+            v => Err(io::Error::new(ErrorKind::InvalidData, format!("Bad fee policy {}", v))),
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        match self {
+            FeePolicy::Fixed(fee) => {
+                writer.write_u8(0)?;
+                writer.write_u64::<LittleEndian>(*fee)
+            }
+            FeePolicy::PerAction {
+                marginal_fee,
+                grace_actions,
+            } => {
+                writer.write_u8(1)?;
+                writer.write_u64::<LittleEndian>(*marginal_fee)?;
+                writer.write_u64::<LittleEndian>(*grace_actions)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FeePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeePolicy::Fixed(fee) => write!(f, "fixed:{}", fee),
+            FeePolicy::PerAction { marginal_fee, .. } => write!(f, "zip317:{}", marginal_fee),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zcash_primitives::transaction::components::amount::DEFAULT_FEE;
+
+    use super::FeePolicy;
+
+    #[test]
+    fn fee_policy() {
+        assert_eq!(FeePolicy::default().fee(5, 1, 7, 2), u64::from(DEFAULT_FEE));
+        assert_eq!(FeePolicy::Fixed(20_000).fee(0, 0, 1, 2), 20_000);
+
+        // Small transactions pay for the grace actions
+        let zip317 = FeePolicy::zip317();
+        assert_eq!(zip317.fee(0, 0, 1, 2), 10_000);
+        assert_eq!(zip317.fee(1, 0, 0, 1), 10_000);
+
+        // Bigger ones pay per action
+        assert_eq!(zip317.fee(3, 1, 4, 2), 35_000);
+
+        assert_eq!(FeePolicy::parse("default").unwrap(), FeePolicy::default());
+        assert_eq!(FeePolicy::parse("fixed:2000").unwrap(), FeePolicy::Fixed(2000));
+        assert_eq!(FeePolicy::parse("zip317").unwrap(), zip317);
+        assert_eq!(FeePolicy::parse(&zip317.to_string()).unwrap(), zip317);
+        assert!(FeePolicy::parse("fixed:lots").is_err());
+        assert!(FeePolicy::parse("auction").is_err());
+
+        let mut buf = vec![];
+        zip317.write(&mut buf).unwrap();
+        assert_eq!(FeePolicy::read(&buf[..]).unwrap(), zip317);
+    }
+}
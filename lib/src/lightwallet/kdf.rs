@@ -0,0 +1,153 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use sodiumoxide::crypto::{pwhash::argon2id13, secretbox};
+use zcash_encoding::Vector;
+
+use super::keys::double_sha256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KdfType {
+    // Wallets written before the KDF was versioned used a bare Sha256(Sha256(passwd))
+    DoubleSha256 = 0,
+    Argon2id = 1,
+}
+
+// Parameters for deriving the wallet's secretbox key from the user's password. These are written
+// into the wallet file, so they can be strengthened later without breaking existing wallets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdfParams {
+    pub(super) kdf: KdfType,
+    salt: Vec<u8>,
+    opslimit: u64,
+    memlimit: u64,
+}
+
+impl KdfParams {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    /// The KDF used by wallets before versioned encryption was introduced.
+    pub fn legacy() -> Self {
+        KdfParams {
+            kdf: KdfType::DoubleSha256,
+            salt: vec![],
+            opslimit: 0,
+            memlimit: 0,
+        }
+    }
+
+    /// A fresh set of Argon2id parameters with a random salt
+    pub fn new_argon2id() -> Self {
+        let argon2id13::Salt(salt) = argon2id13::gen_salt();
+        let argon2id13::OpsLimit(opslimit) = argon2id13::OPSLIMIT_INTERACTIVE;
+        let argon2id13::MemLimit(memlimit) = argon2id13::MEMLIMIT_INTERACTIVE;
+
+        KdfParams {
+            kdf: KdfType::Argon2id,
+            salt: salt.to_vec(),
+            opslimit: opslimit as u64,
+            memlimit: memlimit as u64,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.kdf == KdfType::DoubleSha256
+    }
+
+    pub fn derive_key(&self, passwd: &str) -> io::Result<secretbox::Key> {
+        match self.kdf {
+            KdfType::DoubleSha256 => {
+                // The doublesha256 of the password is the right length
+                Ok(secretbox::Key::from_slice(&double_sha256(passwd.as_bytes())).unwrap())
+            }
+            KdfType::Argon2id => {
+                let salt = argon2id13::Salt::from_slice(&self.salt)
+                    .ok_or(io::Error::new(ErrorKind::InvalidData, "Bad KDF salt"))?;
+
+                let mut key = secretbox::Key([0u8; secretbox::KEYBYTES]);
+                argon2id13::derive_key(
+                    &mut key.0,
+                    passwd.as_bytes(),
+                    &salt,
+                    argon2id13::OpsLimit(self.opslimit as usize),
+                    argon2id13::MemLimit(self.memlimit as usize),
+                )
+                .map_err(|_| io::Error::new(ErrorKind::Other, "Couldn't derive key from password"))?;
+
+                Ok(key)
+            }
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            let e = format!("Don't know how to read KDF version {}", version);
+            return Err(io::Error::new(ErrorKind::InvalidData, e));
+        }
+
+        let kdf = match reader.read_u8()? {
+            0 => KdfType::DoubleSha256,
+            1 => KdfType::Argon2id,
+            n => return Err(io::Error::new(ErrorKind::InvalidData, format!("Unknown KDF type {}", n))),
+        };
+
+        let salt = Vector::read(&mut reader, |r| r.read_u8())?;
+        let opslimit = reader.read_u64::<LittleEndian>()?;
+        let memlimit = reader.read_u64::<LittleEndian>()?;
+
+        Ok(KdfParams {
+            kdf,
+            salt,
+            opslimit,
+            memlimit,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        writer.write_u8(self.kdf as u8)?;
+        Vector::write(&mut writer, &self.salt, |w, b| w.write_u8(*b))?;
+        writer.write_u64::<LittleEndian>(self.opslimit)?;
+        writer.write_u64::<LittleEndian>(self.memlimit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KdfParams;
+
+    #[test]
+    fn kdf_serialize() {
+        let params = KdfParams::new_argon2id();
+
+        let mut v = vec![];
+        params.write(&mut v).unwrap();
+        let params2 = KdfParams::read(&v[..]).unwrap();
+
+        assert_eq!(params, params2);
+        assert_eq!(
+            params.derive_key("passwd").unwrap(),
+            params2.derive_key("passwd").unwrap()
+        );
+    }
+
+    #[test]
+    fn kdf_salted() {
+        // Two wallets with the same password should not share a key
+        let params1 = KdfParams::new_argon2id();
+        let params2 = KdfParams::new_argon2id();
+
+        assert_ne!(
+            params1.derive_key("passwd").unwrap(),
+            params2.derive_key("passwd").unwrap()
+        );
+        assert_ne!(
+            params1.derive_key("passwd").unwrap(),
+            KdfParams::legacy().derive_key("passwd").unwrap()
+        );
+    }
+}
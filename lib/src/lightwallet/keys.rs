@@ -1,7 +1,4 @@
-use std::{
-    collections::HashMap,
-    io::{self, Error, ErrorKind, Read, Write},
-};
+use std::io::{self, Error, ErrorKind, Read, Write};
 
 use base58::{FromBase58, ToBase58};
 use bip39::{Language, Mnemonic, Seed};
@@ -9,6 +6,7 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use orchard::keys::{FullViewingKey, IncomingViewingKey, Scope};
 use rand::{rngs::OsRng, Rng};
 use ripemd160::Digest;
+use secp256k1::{PublicKey, Secp256k1};
 use sha2::Sha256;
 use sodiumoxide::crypto::secretbox;
 use zcash_address::unified::Encoding;
@@ -19,10 +17,12 @@ use zcash_client_backend::{
 use zcash_encoding::Vector;
 use zcash_primitives::{
     consensus,
+    keys::OutgoingViewingKey,
     legacy::TransparentAddress,
     sapling::PaymentAddress,
     zip32::{ChildIndex, ExtendedFullViewingKey, ExtendedSpendingKey},
 };
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::{
     lightclient::lightclient_config::{LightClientConfig, GAP_RULE_UNUSED_ADDRESSES},
@@ -30,6 +30,10 @@ use crate::{
 };
 
 use super::{
+    kdf::KdfParams,
+    multisig::MultisigAddress,
+    secret::TSecretKeys,
+    slip39,
     walletokey::WalletOKey,
     wallettkey::{WalletTKey, WalletTKeyType},
     walletzkey::{WalletZKey, WalletZKeyType},
@@ -110,7 +114,16 @@ pub struct Keys<P> {
     enc_seed: [u8; 48], // If locked, this contains the encrypted seed
     nonce: Vec<u8>,     // Nonce used to encrypt the wallet.
 
-    seed: [u8; 32], // Seed phrase for this wallet. If wallet is locked, this is 0
+    // Parameters used to derive the encryption key from the wallet password
+    kdf: KdfParams,
+
+    seed: Zeroizing<[u8; 32]>, // Seed phrase for this wallet. If wallet is locked, this is 0
+
+    // Optional BIP39 passphrase (the "25th word") used with the seed phrase. If wallet is locked, this is empty
+    passphrase: Zeroizing<String>,
+
+    // If the wallet is encrypted and has a passphrase, the nonce followed by the encrypted passphrase
+    enc_passphrase: Vec<u8>,
 
     // List of keys, actually in this wallet. This is a combination of HD keys derived from the seed,
     // viewing keys and imported spending keys.
@@ -123,11 +136,14 @@ pub struct Keys<P> {
     // Unified address (Orchard) keys actually in this wallet.
     // If wallet is locked, only viewing keys are present.
     pub(crate) okeys: Vec<WalletOKey>,
+
+    // Multisig P2SH addresses whose funds this wallet tracks. This wallet might hold some of their keys.
+    pub(crate) multisigs: Vec<MultisigAddress>,
 }
 
 impl<P: consensus::Parameters> Keys<P> {
     pub fn serialized_version() -> u64 {
-        return 22;
+        return 25;
     }
 
     #[cfg(test)]
@@ -139,27 +155,53 @@ impl<P: consensus::Parameters> Keys<P> {
             unlocked: true,
             enc_seed: [0; 48],
             nonce: vec![],
-            seed: [0u8; 32],
+            kdf: KdfParams::legacy(),
+            seed: Zeroizing::new([0u8; 32]),
+            passphrase: Zeroizing::new(String::new()),
+            enc_passphrase: vec![],
             zkeys: vec![],
             tkeys: vec![],
             okeys: vec![],
+            multisigs: vec![],
+        }
+    }
+
+    /// Placeholder keys for a wallet whose whole file is still encrypted. They look like a locked, encrypted
+    /// wallet with no addresses, and are replaced by the real keys when the wallet is unlocked.
+    pub fn new_sealed(config: &LightClientConfig<P>) -> Self {
+        Self {
+            config: config.clone(),
+            encrypted: true,
+            unlocked: false,
+            enc_seed: [0; 48],
+            nonce: vec![],
+            kdf: KdfParams::legacy(),
+            seed: Zeroizing::new([0u8; 32]),
+            passphrase: Zeroizing::new(String::new()),
+            enc_passphrase: vec![],
+            zkeys: vec![],
+            tkeys: vec![],
+            okeys: vec![],
+            multisigs: vec![],
         }
     }
 
     pub fn new(
         config: &LightClientConfig<P>,
         seed_phrase: Option<String>,
+        passphrase: String,
         num_zaddrs: u32,
         num_oaddrs: u32,
     ) -> Result<Self, String> {
-        let mut seed_bytes = [0u8; 32];
+        let mut seed_bytes = Zeroizing::new([0u8; 32]);
 
         if seed_phrase.is_none() {
             // Create a random seed.
             let mut system_rng = OsRng;
-            system_rng.fill(&mut seed_bytes);
+            system_rng.fill(&mut *seed_bytes);
         } else {
-            let phrase = match Mnemonic::from_phrase(seed_phrase.unwrap().as_str(), Language::English) {
+            let seed_phrase = Zeroizing::new(seed_phrase.unwrap());
+            let phrase = match Mnemonic::from_phrase(seed_phrase.as_str(), Language::English) {
                 Ok(p) => p,
                 Err(e) => {
                     let e = format!("Error parsing phrase: {}", e);
@@ -173,7 +215,8 @@ impl<P: consensus::Parameters> Keys<P> {
 
         // The seed bytes is the raw entropy. To pass it to HD wallet generation,
         // we need to get the 64 byte bip39 entropy
-        let bip39_seed = Seed::new(&Mnemonic::from_entropy(&seed_bytes, Language::English).unwrap(), "");
+        let passphrase = Zeroizing::new(passphrase);
+        let bip39_seed = Self::bip39_seed(&seed_bytes[..], &passphrase);
 
         // Derive only the first sk and address
         let tpk = WalletTKey::new_hdkey(config, 0, &bip39_seed.as_bytes());
@@ -201,10 +244,14 @@ impl<P: consensus::Parameters> Keys<P> {
             unlocked: true,
             enc_seed: [0; 48],
             nonce: vec![],
+            kdf: KdfParams::legacy(),
             seed: seed_bytes,
+            passphrase,
+            enc_passphrase: vec![],
             zkeys,
             tkeys: vec![tpk],
             okeys,
+            multisigs: vec![],
         })
     }
 
@@ -223,8 +270,8 @@ impl<P: consensus::Parameters> Keys<P> {
         };
 
         // Seed
-        let mut seed_bytes = [0u8; 32];
-        reader.read_exact(&mut seed_bytes)?;
+        let mut seed_bytes = Zeroizing::new([0u8; 32]);
+        reader.read_exact(&mut *seed_bytes)?;
 
         let zkeys = if version <= 6 {
             // Up until version 6, the wallet keys were written out individually
@@ -327,10 +374,14 @@ impl<P: consensus::Parameters> Keys<P> {
             unlocked: !encrypted,
             enc_seed,
             nonce,
+            kdf: KdfParams::legacy(),
             seed: seed_bytes,
+            passphrase: Zeroizing::new(String::new()),
+            enc_passphrase: vec![],
             zkeys,
             tkeys,
             okeys: vec![],
+            multisigs: vec![],
         })
     }
 
@@ -351,9 +402,25 @@ impl<P: consensus::Parameters> Keys<P> {
 
         let nonce = Vector::read(&mut reader, |r| r.read_u8())?;
 
+        // Wallets before version 23 always used doublesha256 of the password
+        let kdf = if version <= 22 {
+            KdfParams::legacy()
+        } else {
+            KdfParams::read(&mut reader)?
+        };
+
         // Seed
-        let mut seed_bytes = [0u8; 32];
-        reader.read_exact(&mut seed_bytes)?;
+        let mut seed_bytes = Zeroizing::new([0u8; 32]);
+        reader.read_exact(&mut *seed_bytes)?;
+
+        let (passphrase, enc_passphrase) = if version <= 23 {
+            (String::new(), vec![])
+        } else {
+            (
+                utils::read_string(&mut reader)?,
+                Vector::read(&mut reader, |r| r.read_u8())?,
+            )
+        };
 
         let okeys = if version <= 21 {
             vec![]
@@ -383,16 +450,26 @@ impl<P: consensus::Parameters> Keys<P> {
             Vector::read(&mut reader, |r| WalletTKey::read(r))?
         };
 
+        let multisigs = if version <= 24 {
+            vec![]
+        } else {
+            Vector::read(&mut reader, |r| MultisigAddress::read(r, config))?
+        };
+
         let keys = Self {
             config: config.clone(),
             encrypted,
             unlocked: !encrypted,
             enc_seed,
             nonce,
+            kdf,
             seed: seed_bytes,
+            passphrase: Zeroizing::new(passphrase),
+            enc_passphrase,
             zkeys,
             tkeys,
             okeys,
+            multisigs,
         };
 
         // If there are no okeys, derive the first one.
@@ -413,8 +490,39 @@ impl<P: consensus::Parameters> Keys<P> {
         // Write the nonce
         Vector::write(&mut writer, &self.nonce, |w, b| w.write_u8(*b))?;
 
+        // Write the KDF params
+        self.kdf.write(&mut writer)?;
+
+        // An encrypted wallet is always written as if it was locked, so unlocking it never puts the seed, the
+        // passphrase or the spending keys on disk
+        let locked_keys = if self.encrypted && self.unlocked {
+            let mut zkeys = self.zkeys.clone();
+            zkeys.iter_mut().map(|zk| zk.lock()).collect::<io::Result<Vec<_>>>()?;
+
+            let mut tkeys = self.tkeys.clone();
+            tkeys.iter_mut().map(|tk| tk.lock()).collect::<io::Result<Vec<_>>>()?;
+
+            Some((zkeys, tkeys))
+        } else {
+            None
+        };
+        let (zkeys, tkeys) = match locked_keys.as_ref() {
+            Some((zkeys, tkeys)) => (zkeys, tkeys),
+            None => (&self.zkeys, &self.tkeys),
+        };
+
         // Write the seed
-        writer.write_all(&self.seed)?;
+        if self.encrypted {
+            writer.write_all(&[0u8; 32])?;
+        } else {
+            writer.write_all(&self.seed[..])?;
+        }
+
+        // Write the passphrase. If the wallet is encrypted, only the encrypted passphrase is written.
+        let no_passphrase = String::new();
+        let passphrase = if self.encrypted { &no_passphrase } else { &*self.passphrase };
+        utils::write_string(&mut writer, passphrase)?;
+        Vector::write(&mut writer, &self.enc_passphrase, |w, b| w.write_u8(*b))?;
 
         // Flush after writing the seed, so in case of a disaster, we can still recover the seed.
         writer.flush()?;
@@ -423,10 +531,13 @@ impl<P: consensus::Parameters> Keys<P> {
         Vector::write(&mut writer, &self.okeys, |w, ok| ok.write(w))?;
 
         // Write all the wallet's zkeys
-        Vector::write(&mut writer, &self.zkeys, |w, zk| zk.write(w))?;
+        Vector::write(&mut writer, zkeys, |w, zk| zk.write(w))?;
 
         // Write the transparent private keys
-        Vector::write(&mut writer, &self.tkeys, |w, sk| sk.write(w))?;
+        Vector::write(&mut writer, tkeys, |w, sk| sk.write(w))?;
+
+        // Write the multisig addresses
+        Vector::write(&mut writer, &self.multisigs, |w, m| m.write(w))?;
 
         Ok(())
     }
@@ -435,15 +546,35 @@ impl<P: consensus::Parameters> Keys<P> {
         self.config.clone()
     }
 
-    pub fn get_seed_phrase(&self) -> String {
+    pub fn get_seed_phrase(&self) -> Zeroizing<String> {
         if !self.unlocked {
-            return "".to_string();
+            return Zeroizing::new("".to_string());
         }
 
-        Mnemonic::from_entropy(&self.seed, Language::English)
-            .unwrap()
-            .phrase()
-            .to_string()
+        Zeroizing::new(
+            Mnemonic::from_entropy(&self.seed[..], Language::English)
+                .unwrap()
+                .phrase()
+                .to_string(),
+        )
+    }
+
+    /// Split the seed into `count` SLIP-39 shares, any `threshold` of which restore the same seed phrase
+    pub fn get_seed_shares(&self, threshold: u8, count: u8) -> Result<Vec<Zeroizing<String>>, String> {
+        if !self.unlocked {
+            return Err("Wallet is locked".to_string());
+        }
+
+        slip39::split_seed(&self.seed[..], threshold, count)
+    }
+
+    /// The BIP39 passphrase the seed phrase was used with. Empty if there is none, or if the wallet is locked.
+    pub fn get_passphrase(&self) -> Zeroizing<String> {
+        if !self.unlocked {
+            return Zeroizing::new("".to_string());
+        }
+
+        self.passphrase.clone()
     }
 
     pub fn get_all_orchard_fvks(&self) -> Vec<FullViewingKey> {
@@ -502,6 +633,22 @@ impl<P: consensus::Parameters> Keys<P> {
             .unwrap_or(false)
     }
 
+    // If `address` belongs to one of the sapling keys we can spend with
+    pub fn have_sapling_spending_key_for_address(&self, address: &PaymentAddress) -> bool {
+        self.zkeys
+            .iter()
+            .filter(|zk| zk.have_spending_key())
+            .any(|zk| zk.extfvk.fvk.vk.to_payment_address(*address.diversifier()).as_ref() == Some(address))
+    }
+
+    // If `ovk` is the outgoing viewing key of one of the sapling keys we can spend with
+    pub fn have_sapling_spending_key_for_ovk(&self, ovk: &OutgoingViewingKey) -> bool {
+        self.zkeys
+            .iter()
+            .filter(|zk| zk.have_spending_key())
+            .any(|zk| zk.extfvk.fvk.ovk.0 == ovk.0)
+    }
+
     pub fn have_orchard_spending_key(&self, fvk: &FullViewingKey) -> bool {
         self.okeys
             .iter()
@@ -518,19 +665,21 @@ impl<P: consensus::Parameters> Keys<P> {
             .flatten()
     }
 
-    pub fn get_extsk_for_extfvk(&self, extfvk: &ExtendedFullViewingKey) -> Option<ExtendedSpendingKey> {
+    pub fn get_extsk_for_extfvk(&self, extfvk: &ExtendedFullViewingKey) -> Option<&ExtendedSpendingKey> {
         self.zkeys
             .iter()
             .find(|zk| zk.extfvk == *extfvk)
-            .map(|zk| zk.extsk.clone())
+            .map(|zk| zk.extsk.as_ref())
             .flatten()
     }
 
-    pub fn get_taddr_to_sk_map(&self) -> HashMap<String, secp256k1::SecretKey> {
-        self.tkeys
-            .iter()
-            .map(|tk| (tk.address.clone(), tk.key.unwrap().clone()))
-            .collect()
+    pub fn get_taddr_to_sk_map(&self) -> TSecretKeys {
+        TSecretKeys::new(
+            self.tkeys
+                .iter()
+                .map(|tk| (tk.address.clone(), tk.key.unwrap().clone()))
+                .collect(),
+        )
     }
 
     // If one of the last 'n' taddress was used, ensure we add the next HD taddress to the wallet.
@@ -615,7 +764,7 @@ impl<P: consensus::Parameters> Keys<P> {
             .max_by(|ok1, ok2| ok1.hdkey_num.unwrap().cmp(&ok2.hdkey_num.unwrap()))
             .map_or(0, |ok| ok.hdkey_num.unwrap() + 1);
 
-        let bip39_seed = bip39::Seed::new(&Mnemonic::from_entropy(&self.seed, Language::English).unwrap(), "");
+        let bip39_seed = Self::bip39_seed(&self.seed[..], &self.passphrase);
 
         let spending_key =
             orchard::keys::SpendingKey::from_zip32_seed(&bip39_seed.as_bytes(), self.config.get_coin_type(), pos)
@@ -644,7 +793,7 @@ impl<P: consensus::Parameters> Keys<P> {
             .max_by(|zk1, zk2| zk1.hdkey_num.unwrap().cmp(&zk2.hdkey_num.unwrap()))
             .map_or(0, |zk| zk.hdkey_num.unwrap() + 1);
 
-        let bip39_seed = bip39::Seed::new(&Mnemonic::from_entropy(&self.seed, Language::English).unwrap(), "");
+        let bip39_seed = Self::bip39_seed(&self.seed[..], &self.passphrase);
 
         let (extsk, _, _) = Self::get_zaddr_from_bip39seed(&self.config, &bip39_seed.as_bytes(), pos);
 
@@ -671,7 +820,7 @@ impl<P: consensus::Parameters> Keys<P> {
             .max_by(|sk1, sk2| sk1.hdkey_num.unwrap().cmp(&sk2.hdkey_num.unwrap()))
             .map_or(0, |sk| sk.hdkey_num.unwrap() + 1);
 
-        let bip39_seed = bip39::Seed::new(&Mnemonic::from_entropy(&self.seed, Language::English).unwrap(), "");
+        let bip39_seed = Self::bip39_seed(&self.seed[..], &self.passphrase);
 
         let key = WalletTKey::new_hdkey(&self.config, pos, &bip39_seed.as_bytes());
         let address = key.address.clone();
@@ -680,6 +829,35 @@ impl<P: consensus::Parameters> Keys<P> {
         address
     }
 
+    /// Track the M-of-N multisig address made from the co-signers' public keys, and return it.
+    /// NOTE: This will not rescan the wallet
+    pub fn add_multisig_address(&mut self, threshold: u8, pubkeys: Vec<PublicKey>) -> Result<String, String> {
+        let multisig = MultisigAddress::new(&self.config, threshold, pubkeys)?;
+        let address = multisig.address.clone();
+        if !self.multisigs.iter().any(|m| m.address == address) {
+            self.multisigs.push(multisig);
+        }
+
+        Ok(address)
+    }
+
+    pub fn get_all_multisig_addresses(&self) -> Vec<String> {
+        self.multisigs.iter().map(|m| m.address.clone()).collect()
+    }
+
+    pub fn get_multisig(&self, address: &str) -> Option<&MultisigAddress> {
+        self.multisigs.iter().find(|m| m.address == address)
+    }
+
+    /// The public key of one of the wallet's t addresses, to give to the co-signers of a multisig address
+    pub fn get_taddr_pubkey(&self, address: &str) -> Option<PublicKey> {
+        self.tkeys
+            .iter()
+            .find(|tk| tk.address == address)
+            .and_then(|tk| tk.key)
+            .map(|sk| PublicKey::from_secret_key(&Secp256k1::new(), &sk))
+    }
+
     // Get all z-address private keys. Returns a Vector of (address, privatekey, viewkey)
     pub fn get_z_private_keys(&self) -> Vec<(String, String, String)> {
         let keys = self
@@ -721,14 +899,16 @@ impl<P: consensus::Parameters> Keys<P> {
             return Err(io::Error::new(ErrorKind::AlreadyExists, "Wallet is already encrypted"));
         }
 
-        // Get the doublesha256 of the password, which is the right length
-        let key = secretbox::Key::from_slice(&double_sha256(passwd.as_bytes())).unwrap();
+        // New wallets are always encrypted with a salted, memory-hard KDF
+        let kdf = KdfParams::new_argon2id();
+        let key = kdf.derive_key(&passwd)?;
         let nonce = secretbox::gen_nonce();
 
-        let cipher = secretbox::seal(&self.seed, &nonce, &key);
+        let cipher = secretbox::seal(&self.seed[..], &nonce, &key);
 
         self.enc_seed.copy_from_slice(&cipher);
         self.nonce = nonce.as_ref().to_vec();
+        self.enc_passphrase = Self::seal_passphrase(&self.passphrase, &key);
 
         // Encrypt the individual keys
         self.zkeys
@@ -741,6 +921,7 @@ impl<P: consensus::Parameters> Keys<P> {
             .map(|k| k.encrypt(&key))
             .collect::<io::Result<Vec<()>>>()?;
 
+        self.kdf = kdf;
         self.encrypted = true;
         self.lock()?;
 
@@ -756,8 +937,9 @@ impl<P: consensus::Parameters> Keys<P> {
             return Err(io::Error::new(ErrorKind::AlreadyExists, "Wallet is already locked"));
         }
 
-        // Empty the seed and the secret keys
-        self.seed.copy_from_slice(&[0u8; 32]);
+        // Wipe the seed, the passphrase and the secret keys
+        self.seed.zeroize();
+        self.passphrase.zeroize();
 
         // Remove all the private key from the zkeys and tkeys
         self.tkeys
@@ -784,12 +966,25 @@ impl<P: consensus::Parameters> Keys<P> {
             return Err(Error::new(ErrorKind::AlreadyExists, "Wallet is already unlocked"));
         }
 
-        // Get the doublesha256 of the password, which is the right length
-        let key = secretbox::Key::from_slice(&double_sha256(passwd.as_bytes())).unwrap();
+        self.unlock_keys(&passwd)?;
+
+        // Wallets encrypted with the old doublesha256 key are transparently upgraded. The new
+        // envelope is written to disk the next time the wallet is saved.
+        if self.kdf.is_legacy() {
+            self.rewrap(KdfParams::new_argon2id(), &passwd)?;
+        }
+
+        Ok(())
+    }
+
+    // Decrypt the seed with `passwd` and restore the spending keys from it. This is also the password check, since
+    // the seed only decrypts with the right password.
+    fn unlock_keys(&mut self, passwd: &str) -> io::Result<()> {
+        let key = self.kdf.derive_key(passwd)?;
         let nonce = secretbox::Nonce::from_slice(&self.nonce).unwrap();
 
         let seed = match secretbox::open(&self.enc_seed, &nonce, &key) {
-            Ok(s) => s,
+            Ok(s) => Zeroizing::new(s),
             Err(_) => {
                 return Err(io::Error::new(
                     ErrorKind::InvalidData,
@@ -803,7 +998,8 @@ impl<P: consensus::Parameters> Keys<P> {
 
         // The seed bytes is the raw entropy. To pass it to HD wallet generation,
         // we need to get the 64 byte bip39 entropy
-        let bip39_seed = bip39::Seed::new(&Mnemonic::from_entropy(&seed, Language::English).unwrap(), "");
+        let passphrase = self.open_passphrase(&key)?;
+        let bip39_seed = Self::bip39_seed(&seed[..], &passphrase);
         let config = self.config.clone();
 
         // Transparent keys
@@ -818,7 +1014,8 @@ impl<P: consensus::Parameters> Keys<P> {
             .map(|zk| zk.unlock(&config, bip39_seed.as_bytes(), &key))
             .collect::<io::Result<Vec<()>>>()?;
 
-        self.seed.copy_from_slice(&seed);
+        self.seed.copy_from_slice(&seed[..]);
+        self.passphrase = passphrase;
 
         self.encrypted = true;
         self.unlocked = true;
@@ -826,6 +1023,91 @@ impl<P: consensus::Parameters> Keys<P> {
         Ok(())
     }
 
+    // Re-encrypt the seed and the imported keys under a key derived from `passwd` with `kdf`.
+    // The wallet needs to be unlocked, and stays unlocked.
+    fn rewrap(&mut self, kdf: KdfParams, passwd: &str) -> io::Result<()> {
+        if !self.encrypted || !self.unlocked {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Wallet needs to be encrypted and unlocked",
+            ));
+        }
+
+        let key = kdf.derive_key(passwd)?;
+        let nonce = secretbox::gen_nonce();
+
+        let cipher = secretbox::seal(&self.seed[..], &nonce, &key);
+        let enc_passphrase = Self::seal_passphrase(&self.passphrase, &key);
+
+        // Re-seal the imported keys. HD keys are re-derived from the seed, so they don't need it.
+        // Work on copies, so that the keys are either all re-encrypted or not at all
+        let mut zkeys = self.zkeys.clone();
+        zkeys
+            .iter_mut()
+            .map(|k| k.reencrypt(&key))
+            .collect::<io::Result<Vec<()>>>()?;
+
+        let mut tkeys = self.tkeys.clone();
+        tkeys
+            .iter_mut()
+            .map(|k| k.reencrypt(&key))
+            .collect::<io::Result<Vec<()>>>()?;
+
+        self.zkeys = zkeys;
+        self.tkeys = tkeys;
+        self.enc_seed.copy_from_slice(&cipher);
+        self.nonce = nonce.as_ref().to_vec();
+        self.enc_passphrase = enc_passphrase;
+        self.kdf = kdf;
+
+        Ok(())
+    }
+
+    /// Check that `passwd` decrypts this wallet's seed, without unlocking the wallet
+    pub fn check_password(&self, passwd: &str) -> io::Result<()> {
+        if !self.encrypted {
+            return Err(Error::new(ErrorKind::AlreadyExists, "Wallet is not encrypted"));
+        }
+
+        let key = self.kdf.derive_key(passwd)?;
+        let nonce = secretbox::Nonce::from_slice(&self.nonce)
+            .ok_or(io::Error::new(ErrorKind::InvalidData, "Bad wallet nonce"))?;
+
+        match secretbox::open(&self.enc_seed, &nonce, &key).map(Zeroizing::new) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Decryption failed. Is your password correct?",
+            )),
+        }
+    }
+
+    // Change the wallet password by re-wrapping the seed and imported keys in memory. The wallet is
+    // never decrypted on disk, and is left in the same locked/unlocked state it was in.
+    pub fn change_password(&mut self, old_passwd: String, new_passwd: String) -> io::Result<()> {
+        if !self.encrypted {
+            return Err(Error::new(ErrorKind::AlreadyExists, "Wallet is not encrypted"));
+        }
+
+        // The old password is always verified, even if the wallet is already unlocked. A locked wallet is checked by
+        // unlocking it. If re-wrapping fails, the wallet keeps its old encryption.
+        let was_locked = !self.unlocked;
+        if was_locked {
+            self.unlock_keys(&old_passwd)?;
+        } else {
+            self.check_password(&old_passwd)?;
+        }
+
+        let result = self.rewrap(KdfParams::new_argon2id(), &new_passwd);
+
+        // A wallet that was locked is locked again, whether the password was changed or not
+        if was_locked {
+            self.lock()?;
+        }
+
+        result
+    }
+
     // Removing encryption means unlocking it and setting the self.encrypted = false,
     // permanantly removing the encryption
     pub fn remove_encryption(&mut self, passwd: String) -> io::Result<()> {
@@ -852,7 +1134,9 @@ impl<P: consensus::Parameters> Keys<P> {
         // Permanantly remove the encryption
         self.encrypted = false;
         self.nonce = vec![];
+        self.kdf = KdfParams::legacy();
         self.enc_seed.copy_from_slice(&[0u8; 48]);
+        self.enc_passphrase = vec![];
 
         Ok(())
     }
@@ -866,6 +1150,44 @@ impl<P: consensus::Parameters> Keys<P> {
     }
 
     /// STATIC METHODS
+    // The 64 byte bip39 seed used for HD derivation, from the raw seed entropy and the optional passphrase
+    pub fn bip39_seed(entropy: &[u8], passphrase: &str) -> Seed {
+        Seed::new(&Mnemonic::from_entropy(entropy, Language::English).unwrap(), passphrase)
+    }
+
+    // Encrypt the passphrase under `key`, as the nonce followed by the ciphertext. Empty if there is no passphrase.
+    fn seal_passphrase(passphrase: &str, key: &secretbox::Key) -> Vec<u8> {
+        if passphrase.is_empty() {
+            return vec![];
+        }
+
+        let nonce = secretbox::gen_nonce();
+        let mut sealed = nonce.as_ref().to_vec();
+        sealed.extend(secretbox::seal(passphrase.as_bytes(), &nonce, key));
+
+        sealed
+    }
+
+    fn open_passphrase(&self, key: &secretbox::Key) -> io::Result<Zeroizing<String>> {
+        if self.enc_passphrase.is_empty() {
+            return Ok(Zeroizing::new(String::new()));
+        }
+
+        if self.enc_passphrase.len() < secretbox::NONCEBYTES {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Bad encrypted passphrase"));
+        }
+
+        let (nonce, cipher) = self.enc_passphrase.split_at(secretbox::NONCEBYTES);
+        let nonce = secretbox::Nonce::from_slice(nonce).unwrap();
+        let bytes = secretbox::open(cipher, &nonce, key)
+            .map(Zeroizing::new)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Decryption failed. Is your password correct?"))?;
+
+        String::from_utf8(bytes.to_vec())
+            .map(Zeroizing::new)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))
+    }
+
     pub fn address_from_pubkeyhash(&self, ta: Option<TransparentAddress>) -> Option<String> {
         match ta {
             Some(TransparentAddress::PublicKey(hash)) => {
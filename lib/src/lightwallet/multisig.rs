@@ -0,0 +1,533 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use json::{object, JsonValue};
+use ripemd160::Digest;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use sha2::Sha256;
+use zcash_client_backend::address::RecipientAddress;
+use zcash_encoding::{Optional, Vector};
+use zcash_primitives::{
+    consensus::{self, BlockHeight, BranchId},
+    legacy::{Script, TransparentAddress},
+    transaction::{
+        components::{
+            transparent::{self, TxIn, TxOut},
+            Amount,
+        },
+        sighash::{SignableInput, SIGHASH_ALL},
+        sighash_v4::v4_signature_hash,
+        Authorized, TransactionData, TxVersion,
+    },
+};
+
+use crate::lightclient::lightclient_config::LightClientConfig;
+
+use super::{data::Utxo, keys::ToBase58Check, utils};
+
+// Script opcodes used by multisig redeem scripts and their script sigs
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_1: u8 = 0x51;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+// With compressed keys, this keeps the redeem script under the 520 byte push limit
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+/// A M-of-N P2SH address. Funds sent to it can only be spent with the signatures of `threshold` of the keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultisigAddress {
+    pub threshold: u8,
+    pub pubkeys: Vec<PublicKey>,
+    pub address: String,
+}
+
+impl MultisigAddress {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    pub fn new<P: consensus::Parameters>(
+        config: &LightClientConfig<P>,
+        threshold: u8,
+        pubkeys: Vec<PublicKey>,
+    ) -> Result<Self, String> {
+        if pubkeys.is_empty() || pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(format!("A multisig address needs between 1 and {} keys", MAX_MULTISIG_KEYS));
+        }
+        if threshold == 0 || threshold as usize > pubkeys.len() {
+            return Err(format!(
+                "The number of signatures needed has to be between 1 and {}",
+                pubkeys.len()
+            ));
+        }
+        if (1..pubkeys.len()).any(|i| pubkeys[..i].contains(&pubkeys[i])) {
+            return Err("The same key can't be used twice in a multisig address".to_string());
+        }
+
+        let mut multisig = MultisigAddress {
+            threshold,
+            pubkeys,
+            address: String::new(),
+        };
+
+        let mut hash160 = ripemd160::Ripemd160::new();
+        hash160.update(Sha256::digest(&multisig.redeem_script()));
+        multisig.address = hash160.finalize().to_base58check(&config.base58_script_address(), &[]);
+
+        Ok(multisig)
+    }
+
+    /// Parse hex encoded compressed public keys, as shown by the `tpubkey` command
+    pub fn parse_pubkeys(pubkeys: &[&str]) -> Result<Vec<PublicKey>, String> {
+        pubkeys
+            .iter()
+            .map(|pk| {
+                let bytes = hex::decode(pk).map_err(|_| format!("Public key {} is not hex", pk))?;
+                if bytes.len() != 33 {
+                    return Err(format!("Public key {} is not a compressed public key", pk));
+                }
+                PublicKey::from_slice(&bytes).map_err(|e| format!("Invalid public key {}: {}", pk, e))
+            })
+            .collect()
+    }
+
+    // OP_M <pubkey 1> ... <pubkey N> OP_N OP_CHECKMULTISIG
+    pub fn redeem_script(&self) -> Vec<u8> {
+        let mut script = vec![OP_1 + self.threshold - 1];
+        for pk in self.pubkeys.iter() {
+            push_data(&mut script, &pk.serialize());
+        }
+        script.push(OP_1 + self.pubkeys.len() as u8 - 1);
+        script.push(OP_CHECKMULTISIG);
+
+        script
+    }
+
+    pub fn script_pubkey(&self) -> Script {
+        let mut hash160 = ripemd160::Ripemd160::new();
+        hash160.update(Sha256::digest(&self.redeem_script()));
+
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&hash160.finalize());
+        TransparentAddress::Script(hash).script()
+    }
+
+    pub fn read<R: Read, P: consensus::Parameters>(mut reader: R, config: &LightClientConfig<P>) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Can't read multisig address version {}", version),
+            ));
+        }
+
+        let threshold = reader.read_u8()?;
+        let pubkeys = Vector::read(&mut reader, |r| {
+            let mut pk = [0u8; 33];
+            r.read_exact(&mut pk)?;
+            PublicKey::from_slice(&pk).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })?;
+
+        Self::new(config, threshold, pubkeys).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        writer.write_u8(self.threshold)?;
+        Vector::write(&mut writer, &self.pubkeys, |w, pk| w.write_all(&pk.serialize()))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "address" => self.address.clone(),
+            "threshold" => self.threshold,
+            "pubkeys" => self.pubkeys.iter().map(|pk| hex::encode(pk.serialize())).collect::<Vec<_>>(),
+        }
+    }
+}
+
+/// A transaction spending the funds of a multisig address, which is passed around the co-signers until it has
+/// enough signatures to be broadcast. It can only pay transparent addresses, and the change goes back to the
+/// multisig address.
+pub struct PartiallySignedTx {
+    pub target_height: u32,
+    pub multisig: MultisigAddress,
+    pub inputs: Vec<Utxo>,
+
+    // (address, amount) for every recipient
+    pub tos: Vec<(String, u64)>,
+    pub fee: u64,
+    pub change: u64,
+
+    // The signature of each of the multisig keys, in order, for every input
+    pub signatures: Vec<Vec<Option<Vec<u8>>>>,
+}
+
+impl PartiallySignedTx {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    pub fn new(
+        target_height: u32,
+        multisig: MultisigAddress,
+        inputs: Vec<Utxo>,
+        tos: Vec<(String, u64)>,
+        fee: u64,
+        change: u64,
+    ) -> Self {
+        let signatures = vec![vec![None; multisig.pubkeys.len()]; inputs.len()];
+        PartiallySignedTx {
+            target_height,
+            multisig,
+            inputs,
+            tos,
+            fee,
+            change,
+            signatures,
+        }
+    }
+
+    /// The number of signatures every input has. The transaction can be broadcast once this reaches the threshold.
+    pub fn signature_count(&self) -> usize {
+        self.signatures
+            .iter()
+            .map(|sigs| sigs.iter().filter(|s| s.is_some()).count())
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signature_count() >= self.multisig.threshold as usize
+    }
+
+    // The transaction, with the given script sig for every input. The sighashes are computed with empty script sigs.
+    fn tx_data<P: consensus::Parameters>(
+        &self,
+        params: &P,
+        script_sigs: Vec<Script>,
+    ) -> Result<TransactionData<Authorized>, String> {
+        let vin = self
+            .inputs
+            .iter()
+            .zip(script_sigs.into_iter())
+            .map(|(utxo, script_sig)| TxIn {
+                prevout: utxo.to_outpoint(),
+                script_sig,
+                sequence: u32::MAX,
+            })
+            .collect::<Vec<_>>();
+
+        let mut vout = self
+            .tos
+            .iter()
+            .map(|(address, amount)| match RecipientAddress::decode(params, address) {
+                Some(RecipientAddress::Transparent(ta)) => Ok(TxOut {
+                    value: Amount::from_u64(*amount).map_err(|_| format!("Invalid amount {}", amount))?,
+                    script_pubkey: ta.script(),
+                }),
+                _ => Err(format!("Multisig funds can only be sent to transparent addresses, not {}", address)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if self.change > 0 {
+            vout.push(TxOut {
+                value: Amount::from_u64(self.change).map_err(|_| format!("Invalid change {}", self.change))?,
+                script_pubkey: self.multisig.script_pubkey(),
+            });
+        }
+
+        let height = BlockHeight::from_u32(self.target_height);
+
+        // The co-signers might take a while, so the transaction doesn't expire
+        Ok(TransactionData::from_parts(
+            TxVersion::Sapling,
+            BranchId::for_height(params, height),
+            0,
+            BlockHeight::from_u32(0),
+            Some(transparent::Bundle {
+                vin,
+                vout,
+                authorization: transparent::Authorized,
+            }),
+            None,
+            None,
+            None,
+        ))
+    }
+
+    fn sighashes<P: consensus::Parameters>(&self, params: &P) -> Result<Vec<Message>, String> {
+        let unsigned = self.tx_data(params, vec![Script(vec![]); self.inputs.len()])?;
+        let redeem_script = Script(self.multisig.redeem_script());
+
+        Ok(self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, utxo)| {
+                let sighash = v4_signature_hash(
+                    &unsigned,
+                    &SignableInput::Transparent {
+                        hash_type: SIGHASH_ALL,
+                        index,
+                        script_code: &redeem_script,
+                        value: Amount::from_u64(utxo.value).unwrap(),
+                    },
+                );
+                Message::from_slice(sighash.as_bytes()).unwrap()
+            })
+            .collect())
+    }
+
+    /// Sign every input with those of `sks` that are keys of the multisig address. Returns how many keys signed.
+    pub fn sign<P: consensus::Parameters>(&mut self, params: &P, sks: &[&SecretKey]) -> Result<usize, String> {
+        let secp = Secp256k1::new();
+        let sighashes = self.sighashes(params)?;
+
+        let mut signed = 0;
+        for sk in sks {
+            let pk = PublicKey::from_secret_key(&secp, sk);
+            let k = match self.multisig.pubkeys.iter().position(|p| *p == pk) {
+                Some(k) => k,
+                None => continue,
+            };
+
+            for (sigs, sighash) in self.signatures.iter_mut().zip(sighashes.iter()) {
+                let mut sig = secp.sign_ecdsa(sighash, sk).serialize_der().to_vec();
+                sig.push(SIGHASH_ALL);
+                sigs[k] = Some(sig);
+            }
+            signed += 1;
+        }
+
+        Ok(signed)
+    }
+
+    /// Add the signatures from a co-signer's copy of the same transaction. Every signature is checked first.
+    pub fn merge<P: consensus::Parameters>(&mut self, params: &P, other: &PartiallySignedTx) -> Result<(), String> {
+        let mut ours = vec![];
+        let mut theirs = vec![];
+        self.write_unsigned(&mut ours).map_err(|e| e.to_string())?;
+        other.write_unsigned(&mut theirs).map_err(|e| e.to_string())?;
+        if ours != theirs {
+            return Err("The partially signed transactions are not the same transaction".to_string());
+        }
+
+        let secp = Secp256k1::verification_only();
+        let sighashes = self.sighashes(params)?;
+        for (i, sigs) in other.signatures.iter().enumerate() {
+            for (k, sig) in sigs.iter().enumerate() {
+                let sig = match sig {
+                    Some(sig) => sig,
+                    None => continue,
+                };
+
+                let valid = sig.split_last().map_or(false, |(hash_type, der)| {
+                    *hash_type == SIGHASH_ALL
+                        && Signature::from_der(der)
+                            .map(|s| secp.verify_ecdsa(&sighashes[i], &s, &self.multisig.pubkeys[k]).is_ok())
+                            .unwrap_or(false)
+                });
+                if !valid {
+                    return Err(format!("Invalid signature for input {} from key {}", i, k));
+                }
+
+                self.signatures[i][k] = Some(sig.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The raw transaction, once every input has enough signatures
+    pub fn finalize<P: consensus::Parameters>(&self, params: &P) -> Result<Vec<u8>, String> {
+        if !self.is_complete() {
+            return Err(format!(
+                "The transaction has {} of the {} signatures it needs",
+                self.signature_count(),
+                self.multisig.threshold
+            ));
+        }
+
+        // OP_0 <sig 1> ... <sig M> <redeem script>. The OP_0 is consumed by an off-by-one in OP_CHECKMULTISIG.
+        let redeem_script = self.multisig.redeem_script();
+        let script_sigs = self
+            .signatures
+            .iter()
+            .map(|sigs| {
+                let mut script = vec![OP_0];
+                for sig in sigs.iter().flatten().take(self.multisig.threshold as usize) {
+                    push_data(&mut script, sig);
+                }
+                push_data(&mut script, &redeem_script);
+                Script(script)
+            })
+            .collect();
+
+        let tx = self
+            .tx_data(params, script_sigs)?
+            .freeze()
+            .map_err(|e| format!("Couldn't create the transaction: {}", e))?;
+
+        let mut raw_tx = vec![];
+        tx.write(&mut raw_tx).map_err(|e| e.to_string())?;
+
+        Ok(raw_tx)
+    }
+
+    pub fn read<R: Read, P: consensus::Parameters>(mut reader: R, config: &LightClientConfig<P>) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Can't read partially signed transaction version {}", version),
+            ));
+        }
+
+        let target_height = reader.read_u32::<LittleEndian>()?;
+        let multisig = MultisigAddress::read(&mut reader, config)?;
+        let inputs = Vector::read(&mut reader, |r| Utxo::read(r))?;
+        let tos = Vector::read(&mut reader, |r| {
+            let address = utils::read_string(&mut *r)?;
+            let amount = r.read_u64::<LittleEndian>()?;
+            Ok((address, amount))
+        })?;
+        let fee = reader.read_u64::<LittleEndian>()?;
+        let change = reader.read_u64::<LittleEndian>()?;
+
+        let signatures = Vector::read(&mut reader, |r| {
+            Vector::read(r, |r| Optional::read(r, |r| Vector::read(r, |r| r.read_u8())))
+        })?;
+        if signatures.len() != inputs.len() || signatures.iter().any(|s| s.len() != multisig.pubkeys.len()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Wrong number of signatures"));
+        }
+
+        Ok(PartiallySignedTx {
+            target_height,
+            multisig,
+            inputs,
+            tos,
+            fee,
+            change,
+            signatures,
+        })
+    }
+
+    // Everything but the signatures, which co-signers add
+    fn write_unsigned<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        writer.write_u32::<LittleEndian>(self.target_height)?;
+        self.multisig.write(&mut writer)?;
+        Vector::write(&mut writer, &self.inputs, |w, u| u.write(w))?;
+        Vector::write(&mut writer, &self.tos, |w, (address, amount)| {
+            utils::write_string(&mut *w, address)?;
+            w.write_u64::<LittleEndian>(*amount)
+        })?;
+        writer.write_u64::<LittleEndian>(self.fee)?;
+        writer.write_u64::<LittleEndian>(self.change)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.write_unsigned(&mut writer)?;
+
+        Vector::write(&mut writer, &self.signatures, |w, sigs| {
+            Vector::write(w, sigs, |w, sig| {
+                Optional::write(w, sig.as_ref(), |w, sig| Vector::write(w, sig, |w, b| w.write_u8(*b)))
+            })
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "from" => self.multisig.address.clone(),
+            "threshold" => self.multisig.threshold,
+            "outputs" => self.tos.iter().map(|(address, amount)| object! {
+                "address" => address.clone(),
+                "amount" => *amount,
+            }).collect::<Vec<_>>(),
+            "fee" => self.fee,
+            "change" => self.change,
+            "inputs" => self.inputs.iter().zip(self.signatures.iter()).map(|(u, sigs)| object! {
+                "txid" => format!("{}", u.txid),
+                "output_index" => u.output_index,
+                "value" => u.value,
+                "signed_by" => sigs.iter().enumerate().filter(|(_, s)| s.is_some()).map(|(k, _)| {
+                    hex::encode(self.multisig.pubkeys[k].serialize())
+                }).collect::<Vec<_>>(),
+            }).collect::<Vec<_>>(),
+            "signatures" => self.signature_count(),
+            "complete" => self.is_complete(),
+        }
+    }
+}
+
+// Push `data` onto the stack
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    if data.len() < OP_PUSHDATA1 as usize {
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xff {
+        script.push(OP_PUSHDATA1);
+        script.push(data.len() as u8);
+    } else {
+        script.push(OP_PUSHDATA2);
+        script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    }
+    script.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod test {
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    use crate::lightclient::lightclient_config::{LightClientConfig, UnitTestNetwork};
+
+    use super::MultisigAddress;
+
+    fn pubkeys(n: u8) -> Vec<PublicKey> {
+        let secp = Secp256k1::new();
+        (1..=n)
+            .map(|i| PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[i; 32]).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn redeem_script() {
+        let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+        let multisig = MultisigAddress::new(&config, 2, pubkeys(3)).unwrap();
+
+        let script = multisig.redeem_script();
+        assert_eq!(script.len(), 3 + 3 * 34);
+        assert_eq!(script[0], 0x52);
+        assert_eq!(script[1], 33);
+        assert_eq!(script[script.len() - 2], 0x53);
+        assert_eq!(script[script.len() - 1], 0xae);
+
+        // The address is the hash of the redeem script, so it depends on the order of the keys
+        assert!(multisig.address.starts_with("t"));
+        let mut reordered = pubkeys(3);
+        reordered.reverse();
+        assert_ne!(multisig.address, MultisigAddress::new(&config, 2, reordered).unwrap().address);
+
+        let mut buf = vec![];
+        multisig.write(&mut buf).unwrap();
+        assert_eq!(MultisigAddress::read(&buf[..], &config).unwrap(), multisig);
+    }
+
+    #[test]
+    fn bad_multisig() {
+        let config = LightClientConfig::create_unconnected(UnitTestNetwork, None);
+
+        assert!(MultisigAddress::new(&config, 0, pubkeys(2)).is_err());
+        assert!(MultisigAddress::new(&config, 3, pubkeys(2)).is_err());
+        assert!(MultisigAddress::new(&config, 1, vec![]).is_err());
+        assert!(MultisigAddress::new(&config, 2, pubkeys(16)).is_err());
+
+        let mut repeated = pubkeys(2);
+        repeated.push(repeated[0]);
+        assert!(MultisigAddress::new(&config, 2, repeated).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use json::{object, JsonValue};
+use sha2::{Digest, Sha256};
+use zcash_client_backend::encoding::encode_payment_address;
+use zcash_encoding::{Optional, Vector};
+use zcash_primitives::{
+    keys::OutgoingViewingKey,
+    merkle_tree::{Hashable, IncrementalWitness},
+    sapling::{Diversifier, Node, Note, Nullifier, PaymentAddress},
+    transaction::TxId,
+    zip32::ExtendedFullViewingKey,
+};
+
+use super::{
+    data::{read_rseed, write_rseed, Utxo},
+    utils,
+};
+
+// A sapling note spent by an unsigned transaction, with everything the signer needs to prove the spend
+pub struct UnsignedSaplingSpend {
+    pub txid: TxId,
+    pub nullifier: Nullifier,
+    pub extfvk: ExtendedFullViewingKey,
+    pub diversifier: Diversifier,
+    pub note: Note,
+    pub witness: IncrementalWitness<Node>,
+}
+
+impl UnsignedSaplingSpend {
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut txid_bytes = [0u8; 32];
+        reader.read_exact(&mut txid_bytes)?;
+
+        let mut nullifier = [0u8; 32];
+        reader.read_exact(&mut nullifier)?;
+
+        let extfvk = ExtendedFullViewingKey::read(&mut reader)?;
+
+        let mut diversifier = [0u8; 11];
+        reader.read_exact(&mut diversifier)?;
+        let diversifier = Diversifier(diversifier);
+
+        let value = reader.read_u64::<LittleEndian>()?;
+        let rseed = read_rseed(&mut reader)?;
+        let note = extfvk
+            .fvk
+            .vk
+            .to_payment_address(diversifier)
+            .and_then(|pa| pa.create_note(value, rseed))
+            .ok_or(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Couldn't create the note for the address",
+            ))?;
+
+        let witness = IncrementalWitness::<Node>::read(&mut reader)?;
+
+        Ok(UnsignedSaplingSpend {
+            txid: TxId::from_bytes(txid_bytes),
+            nullifier: Nullifier(nullifier),
+            extfvk,
+            diversifier,
+            note,
+            witness,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.txid.as_ref())?;
+        writer.write_all(&self.nullifier.0)?;
+        self.extfvk.write(&mut writer)?;
+        writer.write_all(&self.diversifier.0)?;
+        writer.write_u64::<LittleEndian>(self.note.value)?;
+        write_rseed(&mut writer, &self.note.rseed)?;
+        self.witness.write(&mut writer)
+    }
+}
+
+// A transaction created by a watch-only wallet, with its inputs selected and its fee and change computed. It is
+// carried to an offline wallet holding the spending keys, which proves and signs it. The result is then
+// broadcast by the watch-only wallet.
+pub struct UnsignedTx {
+    pub target_height: u32,
+    pub fee: u64,
+
+    // All the sapling spends have witnesses to this anchor
+    pub anchor: Option<Node>,
+    pub s_spends: Vec<UnsignedSaplingSpend>,
+    pub utxos: Vec<Utxo>,
+
+    // (address, amount, memo) for every recipient
+    pub tos: Vec<(String, u64, Option<String>)>,
+
+    // The change goes back to the owner of the spent notes, and outputs are encrypted to their ovk
+    pub change: u64,
+    pub change_address: PaymentAddress,
+    pub ovk: OutgoingViewingKey,
+}
+
+impl UnsignedTx {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    pub fn total_value(&self) -> u64 {
+        self.tos.iter().map(|to| to.1).sum()
+    }
+
+    pub fn selected_value(&self) -> u64 {
+        self.utxos.iter().map(|u| u.value).sum::<u64>() + self.s_spends.iter().map(|s| s.note.value).sum::<u64>()
+    }
+
+    /// A short hash of the transaction, shown to whoever signs it, who has to give it back to confirm they've seen
+    /// what they are signing
+    pub fn id(&self) -> String {
+        let mut bytes = vec![];
+        self.write(&mut bytes).unwrap();
+
+        hex::encode(&Sha256::digest(&bytes)[..8])
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Can't read unsigned transaction version {}", version),
+            ));
+        }
+
+        let target_height = reader.read_u32::<LittleEndian>()?;
+        let fee = reader.read_u64::<LittleEndian>()?;
+        let anchor = Optional::read(&mut reader, |r| Node::read(r))?;
+        let s_spends = Vector::read(&mut reader, |r| UnsignedSaplingSpend::read(r))?;
+        let utxos = Vector::read(&mut reader, |r| Utxo::read(r))?;
+        let tos = Vector::read(&mut reader, |r| {
+            let address = utils::read_string(&mut *r)?;
+            let amount = r.read_u64::<LittleEndian>()?;
+            let memo = Optional::read(&mut *r, |r| utils::read_string(r))?;
+            Ok((address, amount, memo))
+        })?;
+        let change = reader.read_u64::<LittleEndian>()?;
+
+        let mut change_address = [0u8; 43];
+        reader.read_exact(&mut change_address)?;
+        let change_address = PaymentAddress::from_bytes(&change_address)
+            .ok_or(io::Error::new(io::ErrorKind::InvalidData, "Bad change address"))?;
+
+        let mut ovk = [0u8; 32];
+        reader.read_exact(&mut ovk)?;
+
+        Ok(UnsignedTx {
+            target_height,
+            fee,
+            anchor,
+            s_spends,
+            utxos,
+            tos,
+            change,
+            change_address,
+            ovk: OutgoingViewingKey(ovk),
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        writer.write_u32::<LittleEndian>(self.target_height)?;
+        writer.write_u64::<LittleEndian>(self.fee)?;
+        Optional::write(&mut writer, self.anchor, |w, a| a.write(w))?;
+        Vector::write(&mut writer, &self.s_spends, |w, s| s.write(w))?;
+        Vector::write(&mut writer, &self.utxos, |w, u| u.write(w))?;
+        Vector::write(&mut writer, &self.tos, |w, (address, amount, memo)| {
+            utils::write_string(&mut *w, address)?;
+            w.write_u64::<LittleEndian>(*amount)?;
+            Optional::write(&mut *w, memo.as_ref(), |w, m| utils::write_string(w, m))
+        })?;
+        writer.write_u64::<LittleEndian>(self.change)?;
+        writer.write_all(&self.change_address.to_bytes())?;
+        writer.write_all(&self.ovk.0)
+    }
+
+    pub fn to_json(&self, hrp_sapling_address: &str) -> JsonValue {
+        let anchor = self.anchor.map(|a| {
+            let mut bytes = vec![];
+            a.write(&mut bytes).unwrap();
+            hex::encode(bytes)
+        });
+
+        object! {
+            "id" => self.id(),
+            "target_height" => self.target_height,
+            "outputs" => self.tos.iter().map(|(address, amount, memo)| object! {
+                "address" => address.clone(),
+                "amount" => *amount,
+                "memo" => memo.clone(),
+            }).collect::<Vec<_>>(),
+            "total" => self.total_value(),
+            "fee" => self.fee,
+            "selected_value" => self.selected_value(),
+            "change" => self.change,
+            "change_address" => encode_payment_address(hrp_sapling_address, &self.change_address),
+            "anchor" => anchor,
+            "inputs" => object! {
+                "transparent" => self.utxos.iter().map(|u| object! {
+                    "txid" => format!("{}", u.txid),
+                    "output_index" => u.output_index,
+                    "address" => u.address.clone(),
+                    "value" => u.value,
+                }).collect::<Vec<_>>(),
+                "sapling" => self.s_spends.iter().map(|s| object! {
+                    "txid" => format!("{}", s.txid),
+                    "value" => s.note.value,
+                }).collect::<Vec<_>>(),
+            },
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use super::proposal::TxPrivacy;
+
+// What an outgoing transaction is allowed to reveal on chain. Each policy allows everything the ones before it do.
+// This is saved with the wallet options, and checked whenever a transaction is planned or sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PrivacyPolicy {
+    // Only shielded inputs paying shielded outputs
+    FullyShielded,
+    // Also transparent inputs paying shielded outputs, which reveals the transparent addresses and amounts
+    AllowShielding,
+    // Also shielded inputs paying transparent outputs, which reveals the recipients and amounts
+    AllowDeshielding,
+    // Anything, including transparent inputs paying transparent outputs
+    AllowRevealedAmounts,
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        PrivacyPolicy::AllowRevealedAmounts
+    }
+}
+
+impl PrivacyPolicy {
+    /// The strictest policy that allows a transaction with this privacy
+    pub fn required_for(privacy: TxPrivacy) -> Self {
+        match privacy {
+            TxPrivacy::Shielded => PrivacyPolicy::FullyShielded,
+            TxPrivacy::Shielding => PrivacyPolicy::AllowShielding,
+            TxPrivacy::Deshielding => PrivacyPolicy::AllowDeshielding,
+            TxPrivacy::Transparent | TxPrivacy::MixedInputs | TxPrivacy::Mixed => PrivacyPolicy::AllowRevealedAmounts,
+        }
+    }
+
+    pub fn allows(&self, privacy: TxPrivacy) -> bool {
+        *self >= Self::required_for(privacy)
+    }
+
+    /// Make sure a transaction with this privacy doesn't reveal more than the policy allows
+    pub fn check(&self, privacy: TxPrivacy) -> Result<(), String> {
+        if self.allows(privacy) {
+            return Ok(());
+        }
+
+        Err(format!(
+            "The '{}' privacy policy doesn't allow this {} transaction. {}. Spend other funds, or allow it with \
+             'setoption privacy_policy={}'",
+            self,
+            privacy.as_str(),
+            privacy.description(),
+            Self::required_for(privacy)
+        ))
+    }
+
+    /// Whether utxos may be spent in a transaction, depending on whether it pays any transparent outputs
+    pub fn allows_transparent_inputs(&self, transparent_outputs: bool) -> bool {
+        if transparent_outputs {
+            self.allows(TxPrivacy::Transparent)
+        } else {
+            self.allows(TxPrivacy::Shielding)
+        }
+    }
+
+    /// Whether utxos may be spent together with shielded notes, depending on whether any outputs are transparent
+    pub fn allows_mixed_inputs(&self, transparent_outputs: bool) -> bool {
+        if transparent_outputs {
+            self.allows(TxPrivacy::Mixed)
+        } else {
+            self.allows(TxPrivacy::MixedInputs)
+        }
+    }
+
+    /// Parse a policy as given to `setoption privacy_policy=...`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "fullyshielded" => Ok(PrivacyPolicy::FullyShielded),
+            "allowshielding" => Ok(PrivacyPolicy::AllowShielding),
+            "allowdeshielding" => Ok(PrivacyPolicy::AllowDeshielding),
+            "allowrevealedamounts" | "default" => Ok(PrivacyPolicy::AllowRevealedAmounts),
+            _ => Err(format!("Unknown privacy policy {}", s)),
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(PrivacyPolicy::FullyShielded),
+            1 => Ok(PrivacyPolicy::AllowShielding),
+            2 => Ok(PrivacyPolicy::AllowDeshielding),
+            3 => Ok(PrivacyPolicy::AllowRevealedAmounts),
+            v => Err(io::Error::new(ErrorKind::InvalidData, format!("Bad privacy policy {}", v))),
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u8(*self as u8)
+    }
+}
+
+impl fmt::Display for PrivacyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PrivacyPolicy::FullyShielded => "fullyshielded",
+            PrivacyPolicy::AllowShielding => "allowshielding",
+            PrivacyPolicy::AllowDeshielding => "allowdeshielding",
+            PrivacyPolicy::AllowRevealedAmounts => "allowrevealedamounts",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PrivacyPolicy;
+    use crate::lightwallet::proposal::TxPrivacy;
+
+    #[test]
+    fn privacy_policy() {
+        // Every policy allows what the stricter ones do
+        assert!(PrivacyPolicy::FullyShielded.allows(TxPrivacy::Shielded));
+        assert!(!PrivacyPolicy::FullyShielded.allows(TxPrivacy::Shielding));
+        assert!(PrivacyPolicy::AllowShielding.allows(TxPrivacy::Shielding));
+        assert!(!PrivacyPolicy::AllowShielding.allows(TxPrivacy::Deshielding));
+        assert!(PrivacyPolicy::AllowDeshielding.allows(TxPrivacy::Shielding));
+        assert!(PrivacyPolicy::AllowDeshielding.allows(TxPrivacy::Deshielding));
+        assert!(!PrivacyPolicy::AllowDeshielding.allows(TxPrivacy::Mixed));
+        assert!(!PrivacyPolicy::AllowDeshielding.allows(TxPrivacy::MixedInputs));
+        assert!(PrivacyPolicy::AllowRevealedAmounts.allows(TxPrivacy::MixedInputs));
+        assert!(PrivacyPolicy::default().allows(TxPrivacy::Transparent));
+
+        // Utxos can be shielded, but spending them to a t-address reveals the amounts
+        assert!(PrivacyPolicy::AllowShielding.allows_transparent_inputs(false));
+        assert!(!PrivacyPolicy::AllowDeshielding.allows_transparent_inputs(true));
+        assert!(!PrivacyPolicy::FullyShielded.allows_transparent_inputs(false));
+
+        // Spending them together with notes links them to the shielded funds, even when paying shielded outputs
+        assert!(!PrivacyPolicy::AllowShielding.allows_mixed_inputs(false));
+        assert!(!PrivacyPolicy::AllowDeshielding.allows_mixed_inputs(true));
+        assert!(PrivacyPolicy::AllowRevealedAmounts.allows_mixed_inputs(false));
+
+        // The policies survive a round trip through their names and the wallet file
+        for policy in [
+            PrivacyPolicy::FullyShielded,
+            PrivacyPolicy::AllowShielding,
+            PrivacyPolicy::AllowDeshielding,
+            PrivacyPolicy::AllowRevealedAmounts,
+        ] {
+            assert_eq!(PrivacyPolicy::parse(&policy.to_string()).unwrap(), policy);
+
+            let mut buf = vec![];
+            policy.write(&mut buf).unwrap();
+            assert_eq!(PrivacyPolicy::read(&buf[..]).unwrap(), policy);
+        }
+        assert!(PrivacyPolicy::parse("private").is_err());
+    }
+}
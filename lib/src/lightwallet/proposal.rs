@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use json::{array, object, JsonValue};
+use zcash_primitives::{sapling::Nullifier, transaction::TxId};
+
+use super::data::Utxo;
+use super::privacy::PrivacyPolicy;
+
+// Nodes only relay transactions up to 100kB, even though consensus allows larger ones. These are upper bounds on
+// the size of each part of a v4 transaction, used to keep the transactions we build under that limit.
+pub const MAX_TX_SIZE: usize = 100_000;
+const TX_HEADER_SIZE: usize = 150;
+const T_INPUT_SIZE: usize = 150;
+const T_OUTPUT_SIZE: usize = 34;
+const S_SPEND_SIZE: usize = 384;
+const S_OUTPUT_SIZE: usize = 948;
+
+/// An upper bound on the size of a transaction with the given number of transparent inputs/outputs and sapling
+/// spends/outputs
+pub fn estimated_tx_size(t_inputs: usize, t_outputs: usize, s_spends: usize, s_outputs: usize) -> usize {
+    TX_HEADER_SIZE
+        + t_inputs * T_INPUT_SIZE
+        + t_outputs * T_OUTPUT_SIZE
+        + s_spends * S_SPEND_SIZE
+        + s_outputs * S_OUTPUT_SIZE
+}
+
+// How much of a transaction is visible on chain, from the pools it spends from and pays to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxPrivacy {
+    // Shielded inputs paying only shielded outputs
+    Shielded,
+    // Transparent inputs paying only shielded outputs
+    Shielding,
+    // Both transparent and shielded inputs, paying only shielded outputs
+    MixedInputs,
+    // Shielded inputs paying transparent outputs
+    Deshielding,
+    // Transparent inputs paying transparent outputs
+    Transparent,
+    // Both transparent and shielded inputs, paying transparent outputs
+    Mixed,
+}
+
+impl TxPrivacy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxPrivacy::Shielded => "shielded",
+            TxPrivacy::Shielding => "shielding",
+            TxPrivacy::MixedInputs => "mixed_inputs",
+            TxPrivacy::Deshielding => "deshielding",
+            TxPrivacy::Transparent => "transparent",
+            TxPrivacy::Mixed => "mixed",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TxPrivacy::Shielded => "It only spends shielded funds to shielded recipients",
+            TxPrivacy::Shielding => "It spends transparent funds, whose addresses and amounts are public",
+            TxPrivacy::MixedInputs => "It spends transparent funds together with shielded funds, which links them",
+            TxPrivacy::Deshielding => "It pays transparent recipients, whose addresses and amounts are public",
+            TxPrivacy::Transparent => "It spends transparent funds to transparent recipients, so all of it is public",
+            TxPrivacy::Mixed => "It spends transparent and shielded funds together to transparent recipients",
+        }
+    }
+}
+
+// A transaction that has had its inputs selected and its fee computed, but hasn't been proved or broadcast.
+// The UI can show it to the user, and then send it with the same inputs or discard it.
+#[derive(Debug, Clone)]
+pub struct TxProposal {
+    pub id: u32,
+    pub created: u64,
+    pub target_height: u32,
+    pub transparent_only: bool,
+
+    // (address, amount, memo) for every recipient
+    pub tos: Vec<(String, u64, Option<String>)>,
+    pub fee: u64,
+
+    // The selected inputs. Sapling notes are (txid, nullifier, value)
+    pub utxos: Vec<Utxo>,
+    pub s_notes: Vec<(TxId, Nullifier, u64)>,
+
+    // Number of recipients paid in each pool, not counting change
+    pub t_outputs: usize,
+    pub s_outputs: usize,
+
+    // Where the change goes, if not to the wallet's first sapling address, and whether that is a t address
+    pub change_address: Option<String>,
+    pub transparent_change: bool,
+}
+
+impl TxProposal {
+    pub fn total_value(&self) -> u64 {
+        self.tos.iter().map(|to| to.1).sum()
+    }
+
+    pub fn selected_value(&self) -> u64 {
+        self.utxos.iter().map(|u| u.value).sum::<u64>() + self.s_notes.iter().map(|n| n.2).sum::<u64>()
+    }
+
+    /// The change, which is sent to `change_address` or else the wallet's first sapling address
+    pub fn change(&self) -> u64 {
+        self.selected_value() - self.total_value() - self.fee
+    }
+
+    fn has_transparent_change(&self) -> bool {
+        self.transparent_change && self.change() > 0
+    }
+
+    /// An upper bound on the size of the transaction, including its change output
+    pub fn estimated_size(&self) -> usize {
+        let change = if self.change() > 0 { 1 } else { 0 };
+        let (t_change, s_change) = if self.transparent_change { (change, 0) } else { (0, change) };
+
+        estimated_tx_size(
+            self.utxos.len(),
+            self.t_outputs + t_change,
+            self.s_notes.len(),
+            self.s_outputs + s_change,
+        )
+    }
+
+    /// Make sure the transaction isn't too large to be relayed
+    pub fn check_size(&self) -> Result<(), String> {
+        if self.estimated_size() > MAX_TX_SIZE {
+            Err(format!(
+                "The transaction would be about {} bytes, over the limit of {} bytes. Spend fewer inputs, or send to \
+                 a single recipient so the payment can be split over several transactions",
+                self.estimated_size(),
+                MAX_TX_SIZE
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Make sure the transaction doesn't reveal more than the privacy policy allows
+    pub fn check_privacy(&self, policy: PrivacyPolicy) -> Result<(), String> {
+        policy.check(self.privacy())
+    }
+
+    pub fn privacy(&self) -> TxPrivacy {
+        let t_in = !self.utxos.is_empty();
+        let s_in = !self.s_notes.is_empty();
+        let t_out = self.t_outputs > 0 || self.has_transparent_change();
+
+        match (t_in, s_in, t_out) {
+            (false, _, false) => TxPrivacy::Shielded,
+            (true, false, false) => TxPrivacy::Shielding,
+            (true, true, false) => TxPrivacy::MixedInputs,
+            (false, _, true) => TxPrivacy::Deshielding,
+            (true, false, true) => TxPrivacy::Transparent,
+            (true, true, true) => TxPrivacy::Mixed,
+        }
+    }
+
+    /// Human readable notes on what this transaction reveals on chain
+    pub fn privacy_notes(&self) -> Vec<String> {
+        let mut notes = vec![];
+
+        if !self.utxos.is_empty() {
+            let taddrs = self.utxos.iter().map(|u| &u.address).collect::<HashSet<_>>();
+            notes.push(format!(
+                "Spends {} transparent input(s), whose addresses and amounts are public",
+                self.utxos.len()
+            ));
+            if taddrs.len() > 1 {
+                notes.push(format!(
+                    "Publicly links {} of your transparent addresses together",
+                    taddrs.len()
+                ));
+            }
+            if !self.s_notes.is_empty() {
+                notes.push("Links your transparent addresses to the shielded funds spent with them".to_string());
+            }
+        }
+
+        if self.t_outputs > 0 {
+            notes.push(format!(
+                "Pays {} transparent recipient(s), whose addresses and amounts are public",
+                self.t_outputs
+            ));
+            if !self.s_notes.is_empty() {
+                notes.push("Moves shielded funds into the transparent pool".to_string());
+            }
+        }
+
+        if self.has_transparent_change() {
+            notes.push("Sends the change to a transparent address, where its amount is public".to_string());
+        }
+
+        if notes.is_empty() {
+            notes.push("Fully shielded. Addresses, amounts and memos are not public".to_string());
+        }
+
+        notes
+    }
+
+    fn pools(t: bool, s: bool) -> JsonValue {
+        let mut pools = array![];
+        if t {
+            pools.push("transparent").unwrap();
+        }
+        if s {
+            pools.push("sapling").unwrap();
+        }
+        pools
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "id" => self.id,
+            "created" => self.created,
+            "target_height" => self.target_height,
+            "transparent_only" => self.transparent_only,
+            "outputs" => self.tos.iter().map(|(address, amount, memo)| object! {
+                "address" => address.clone(),
+                "amount" => *amount,
+                "memo" => memo.clone(),
+            }).collect::<Vec<_>>(),
+            "total" => self.total_value(),
+            "fee" => self.fee,
+            "selected_value" => self.selected_value(),
+            "change" => self.change(),
+            "change_address" => self.change_address.clone(),
+            "estimated_size" => self.estimated_size(),
+            "inputs" => object! {
+                "transparent" => self.utxos.iter().map(|u| object! {
+                    "txid" => format!("{}", u.txid),
+                    "output_index" => u.output_index,
+                    "address" => u.address.clone(),
+                    "value" => u.value,
+                }).collect::<Vec<_>>(),
+                "sapling" => self.s_notes.iter().map(|(txid, _, value)| object! {
+                    "txid" => format!("{}", txid),
+                    "value" => *value,
+                }).collect::<Vec<_>>(),
+            },
+            "pools_spent" => Self::pools(!self.utxos.is_empty(), !self.s_notes.is_empty()),
+            "pools_received" => Self::pools(
+                self.t_outputs > 0 || self.has_transparent_change(),
+                self.s_outputs > 0 || (self.change() > 0 && !self.transparent_change),
+            ),
+            "privacy" => self.privacy().as_str(),
+            "privacy_notes" => self.privacy_notes(),
+        }
+    }
+}
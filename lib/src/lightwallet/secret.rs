@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic,
+};
+
+use json::JsonValue;
+use secp256k1::SecretKey;
+use zcash_primitives::zip32::ExtendedSpendingKey;
+use zeroize::Zeroize;
+
+// The spending key types from zcash_primitives and secp256k1 don't implement `Zeroize`, so
+// these helpers overwrite their secret parts in place before they are dropped.
+
+// Overwrite `v` with a volatile write, so the compiler can't elide it as a dead store. The old value isn't dropped,
+// so this is only for types without heap data.
+fn volatile_set<T>(v: &mut T, with: T) {
+    unsafe { ptr::write_volatile(v, with) };
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+#[cfg(test)]
+thread_local! {
+    // How many keys were wiped on this thread, so tests can check that locking and dropping wipes them
+    pub static WIPED_KEYS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+fn count_wipe() {
+    #[cfg(test)]
+    WIPED_KEYS.with(|w| w.set(w.get() + 1));
+}
+
+/// Wipe a sapling spending key. The whole key, including the chain code its child keys are derived with, is
+/// overwritten with the key of an all-zero seed, and then its spend authorizing and outgoing viewing parts are zeroed.
+pub fn wipe_extsk(extsk: &mut ExtendedSpendingKey) {
+    volatile_set(extsk, ExtendedSpendingKey::master(&[0u8; 32]));
+    volatile_set(&mut extsk.expsk.ask, jubjub::Fr::zero());
+    volatile_set(&mut extsk.expsk.nsk, jubjub::Fr::zero());
+    extsk.expsk.ovk.0.zeroize();
+    count_wipe();
+}
+
+/// Wipe a transparent secret key. An all-zero key is not a valid secp256k1 key, so it's overwritten with 1
+pub fn wipe_tsk(sk: &mut SecretKey) {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+
+    volatile_set(sk, SecretKey::from_slice(&one).unwrap());
+    count_wipe();
+}
+
+/// Transparent secret keys by address, as handed to the tx builder. The keys are wiped when this is dropped.
+pub struct TSecretKeys(HashMap<String, SecretKey>);
+
+impl TSecretKeys {
+    pub fn new(keys: HashMap<String, SecretKey>) -> Self {
+        TSecretKeys(keys)
+    }
+
+    fn wipe(&mut self) {
+        self.0.values_mut().for_each(wipe_tsk);
+    }
+}
+
+impl Deref for TSecretKeys {
+    type Target = HashMap<String, SecretKey>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TSecretKeys {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+/// A JSON object with secrets in it, like an exported seed phrase. Its strings are wiped when it is dropped, so
+/// put secrets in as `JsonValue::String`, since short strings are stored inline where they can't be wiped.
+pub struct SecretJson(JsonValue);
+
+impl SecretJson {
+    pub fn new(json: JsonValue) -> Self {
+        SecretJson(json)
+    }
+}
+
+impl Deref for SecretJson {
+    type Target = JsonValue;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretJson {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for SecretJson {
+    fn drop(&mut self) {
+        wipe_json(&mut self.0);
+    }
+}
+
+// Wipe all the strings in a JSON value
+fn wipe_json(v: &mut JsonValue) {
+    match v {
+        JsonValue::String(s) => s.zeroize(),
+        JsonValue::Array(a) => a.iter_mut().for_each(wipe_json),
+        JsonValue::Object(o) => o.iter_mut().for_each(|(_, v)| wipe_json(v)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use json::{object, JsonValue};
+    use secp256k1::SecretKey;
+    use zcash_primitives::zip32::ExtendedSpendingKey;
+
+    use super::{wipe_extsk, wipe_json, wipe_tsk, TSecretKeys, WIPED_KEYS};
+
+    #[test]
+    fn wipe_keys() {
+        let mut extsk = ExtendedSpendingKey::master(&[1u8; 32]);
+        wipe_extsk(&mut extsk);
+        assert_eq!(extsk.expsk.ask, jubjub::Fr::zero());
+        assert_eq!(extsk.expsk.nsk, jubjub::Fr::zero());
+        assert_eq!(extsk.expsk.ovk.0, [0u8; 32]);
+
+        // Nothing else of the key is left either, including the chain code
+        let (mut bytes, mut zero_bytes) = (vec![], vec![]);
+        extsk.write(&mut bytes).unwrap();
+        ExtendedSpendingKey::master(&[0u8; 32]).write(&mut zero_bytes).unwrap();
+        assert_eq!(bytes[..41], zero_bytes[..41]);
+
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let wiped = SecretKey::from_slice(&one).unwrap();
+
+        let sk = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let mut wiped_sk = sk;
+        wipe_tsk(&mut wiped_sk);
+        assert_eq!(wiped_sk, wiped);
+
+        // The keys handed to the tx builder are wiped in the map itself, and dropping them does that
+        let map = vec![("t1".to_string(), sk), ("t2".to_string(), sk)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let mut keys = TSecretKeys::new(map.clone());
+        keys.wipe();
+        assert_eq!(keys.get("t1"), Some(&wiped));
+        assert_eq!(keys.get("t2"), Some(&wiped));
+
+        let before = WIPED_KEYS.with(|w| w.get());
+        drop(TSecretKeys::new(map));
+        assert_eq!(WIPED_KEYS.with(|w| w.get()), before + 2);
+    }
+
+    #[test]
+    fn wipe_secret_json() {
+        let mut j = object! {
+            "shares" => vec![JsonValue::String("first share".to_string())],
+            "seed" => JsonValue::String("seed phrase".to_string()),
+            "birthday" => 100,
+        };
+        wipe_json(&mut j);
+
+        assert_eq!(j["shares"][0], "");
+        assert_eq!(j["seed"], "");
+        assert_eq!(j["birthday"], 100);
+    }
+}
@@ -0,0 +1,503 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use json::JsonValue;
+use log::{error, info, warn};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{oneshot, RwLock},
+    task::JoinHandle,
+    time,
+};
+use zcash_primitives::{
+    consensus::{self, BlockHeight},
+    legacy::Script,
+    merkle_tree::MerklePath,
+    sapling::{
+        prover::TxProver,
+        redjubjub::{PublicKey, Signature},
+        Diversifier, Node, PaymentAddress, ProofGenerationKey, Rseed,
+    },
+    transaction::{
+        builder::{Builder, Progress},
+        components::{Amount, TxOut, GROTH_PROOF_SIZE},
+    },
+};
+
+use crate::lightclient::lightclient_config::LightClientConfig;
+
+use super::{
+    events::{WalletEvent, WalletEvents},
+    keys::Keys,
+    now,
+    offline::UnsignedTx,
+    LightWallet,
+};
+
+// Largest request or response accepted over a signer socket
+const MAX_FRAME_SIZE: u64 = 16 * 1024 * 1024;
+
+// Shortest token accepted for a signer socket. Any process on the machine can connect to the socket, so the token is
+// what keeps them from having transactions signed.
+pub const MIN_TOKEN_LEN: usize = 16;
+
+// How long a signing request waits for the user to approve it before it is rejected
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+// How long a connection to the signer gets to send its token and request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Authorizes the spends of a transaction. The wallet selects the inputs and computes the fee and change, and
+/// hands the signer an `UnsignedTx` with everything needed to prove and sign it. The signer returns the raw,
+/// fully signed transaction.
+pub trait Signer: Send + Sync {
+    fn sign<'a>(&'a self, utx: &'a UnsignedTx) -> BoxFuture<'a, Result<Vec<u8>, String>>;
+}
+
+/// Lends a prover to a `KeysSigner`, so the same prover can be used for several transactions
+pub struct ProverRef<'a, PR>(pub &'a PR);
+
+impl<'a, PR: TxProver> TxProver for ProverRef<'a, PR> {
+    type SaplingProvingContext = PR::SaplingProvingContext;
+
+    fn new_sapling_proving_context(&self) -> Self::SaplingProvingContext {
+        self.0.new_sapling_proving_context()
+    }
+
+    fn spend_proof(
+        &self,
+        ctx: &mut Self::SaplingProvingContext,
+        proof_generation_key: ProofGenerationKey,
+        diversifier: Diversifier,
+        rseed: Rseed,
+        ar: jubjub::Fr,
+        value: u64,
+        anchor: bls12_381::Scalar,
+        merkle_path: MerklePath<Node>,
+    ) -> Result<([u8; GROTH_PROOF_SIZE], jubjub::ExtendedPoint, PublicKey), ()> {
+        self.0
+            .spend_proof(ctx, proof_generation_key, diversifier, rseed, ar, value, anchor, merkle_path)
+    }
+
+    fn output_proof(
+        &self,
+        ctx: &mut Self::SaplingProvingContext,
+        esk: jubjub::Fr,
+        payment_address: PaymentAddress,
+        rcm: jubjub::Fr,
+        value: u64,
+    ) -> ([u8; GROTH_PROOF_SIZE], jubjub::ExtendedPoint) {
+        self.0.output_proof(ctx, esk, payment_address, rcm, value)
+    }
+
+    fn binding_sig(
+        &self,
+        ctx: &mut Self::SaplingProvingContext,
+        value_balance: Amount,
+        sighash: &[u8; 32],
+    ) -> Result<Signature, ()> {
+        self.0.binding_sig(ctx, value_balance, sighash)
+    }
+}
+
+/// Signs with the spending keys held in memory by the wallet
+pub struct KeysSigner<P, PR> {
+    config: LightClientConfig<P>,
+    keys: Arc<RwLock<Keys<P>>>,
+    prover: PR,
+
+    // Handed to the builder to report how many spends and outputs have been proven
+    progress_notifier: Mutex<Option<mpsc::Sender<Progress>>>,
+}
+
+impl<P: consensus::Parameters + Send + Sync + 'static, PR: TxProver + Send + Sync> KeysSigner<P, PR> {
+    pub fn new(config: LightClientConfig<P>, keys: Arc<RwLock<Keys<P>>>, prover: PR) -> Self {
+        KeysSigner {
+            config,
+            keys,
+            prover,
+            progress_notifier: Mutex::new(None),
+        }
+    }
+
+    pub fn with_progress_notifier(self, progress_notifier: mpsc::Sender<Progress>) -> Self {
+        *self.progress_notifier.lock().unwrap() = Some(progress_notifier);
+        self
+    }
+
+    async fn sign_with_keys(&self, utx: &UnsignedTx) -> Result<Vec<u8>, String> {
+        let keys = self.keys.read().await;
+        if !keys.unlocked {
+            return Err("Cannot spend while wallet is locked".to_string());
+        }
+
+        if utx.selected_value() != utx.total_value() + utx.fee + utx.change {
+            return Err("The unsigned transaction's inputs don't add up to its outputs, fee and change".to_string());
+        }
+
+        // The change address and ovk come from whoever created the transaction, so make sure the change comes back
+        // to us and the outgoing metadata is encrypted to us
+        if !keys.have_sapling_spending_key_for_address(&utx.change_address) {
+            return Err("The change of the unsigned transaction isn't sent to this wallet".to_string());
+        }
+        if !keys.have_sapling_spending_key_for_ovk(&utx.ovk) {
+            return Err("The unsigned transaction's outgoing viewing key isn't one of this wallet's keys".to_string());
+        }
+
+        let start_time = now();
+        let recepients = LightWallet::<P>::decode_recipients(&self.config, &utx.tos)?;
+
+        let mut builder = Builder::new(self.config.get_params().clone(), BlockHeight::from_u32(utx.target_height));
+        if let Some(progress_notifier) = self.progress_notifier.lock().unwrap().take() {
+            builder.with_progress_notifier(progress_notifier);
+        }
+        builder.set_fee(Amount::from_u64(utx.fee).unwrap());
+
+        // The keys are wiped when the map is dropped, but the builder keeps its own
+        // copies of the keys it is handed until it is dropped.
+        if !utx.utxos.is_empty() {
+            let address_to_sk = keys.get_taddr_to_sk_map();
+            for utxo in utx.utxos.iter() {
+                let sk = address_to_sk
+                    .get(&utxo.address)
+                    .ok_or(format!("No spending key for utxo {}:{}", utxo.txid, utxo.output_index))?;
+
+                let coin = TxOut {
+                    value: Amount::from_u64(utxo.value).unwrap(),
+                    script_pubkey: Script { 0: utxo.script.clone() },
+                };
+                builder
+                    .add_transparent_input(*sk, utxo.to_outpoint(), coin)
+                    .map_err(|e| format!("Error adding utxo: {:?}", e))?;
+            }
+        }
+
+        for spend in utx.s_spends.iter() {
+            if Some(spend.witness.root()) != utx.anchor {
+                return Err(format!("The witness for the sapling note in {} is not to the anchor", spend.txid));
+            }
+
+            let extsk = keys
+                .get_extsk_for_extfvk(&spend.extfvk)
+                .ok_or(format!("No spending key for the sapling note in {}", spend.txid))?;
+            let path = spend
+                .witness
+                .path()
+                .ok_or(format!("Bad witness for the sapling note in {}", spend.txid))?;
+
+            builder
+                .add_sapling_spend(extsk.clone(), spend.diversifier, spend.note.clone(), path)
+                .map_err(|e| format!("Error adding sapling note: {:?}", e))?;
+        }
+
+        LightWallet::<P>::add_recipients(&mut builder, utx.ovk, recepients, start_time)?;
+
+        if utx.change > 0 {
+            builder.send_change_to(utx.ovk, utx.change_address.clone());
+        }
+
+        info!("Signing transaction with {} sapling notes, {} transparent inputs", utx.s_spends.len(), utx.utxos.len());
+        let (tx, _) = builder.build(&self.prover).map_err(|e| {
+            let e = format!("Error creating transaction: {:?}", e);
+            error!("{}", e);
+            e
+        })?;
+
+        let mut raw_tx = vec![];
+        tx.write(&mut raw_tx).unwrap();
+
+        Ok(raw_tx)
+    }
+}
+
+impl<P: consensus::Parameters + Send + Sync + 'static, PR: TxProver + Send + Sync> Signer for KeysSigner<P, PR> {
+    fn sign<'a>(&'a self, utx: &'a UnsignedTx) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        Box::pin(self.sign_with_keys(utx))
+    }
+}
+
+/// Sends the unsigned transaction to a signer listening on a local socket, and waits for the signed
+/// transaction. The signer on the other end might be another wallet holding the keys (see `serve`), or a bridge
+/// to a hardware wallet or HSM.
+///
+/// Every request is the length-prefixed (u64 LE) token shared with the signer, followed by a length-prefixed
+/// serialized `UnsignedTx`. The reply is a status byte, 0 for success or 1 for an error, followed by a
+/// length-prefixed raw transaction or error message.
+pub struct SocketSigner {
+    address: SocketAddr,
+    token: String,
+}
+
+impl SocketSigner {
+    pub fn new(address: &str, token: &str) -> Result<Self, String> {
+        let address: SocketAddr = address
+            .parse()
+            .map_err(|e| format!("Invalid signer address '{}': {}", address, e))?;
+
+        // The unsigned transaction has the viewing keys and notes being spent, so it should never leave the machine
+        if !address.ip().is_loopback() {
+            return Err(format!("The signer address {} is not a local address", address));
+        }
+        check_token(token)?;
+
+        Ok(SocketSigner {
+            address,
+            token: token.to_string(),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    async fn sign_remote(&self, utx: &UnsignedTx) -> io::Result<Result<Vec<u8>, String>> {
+        let mut request = vec![];
+        utx.write(&mut request)?;
+
+        let mut stream = TcpStream::connect(self.address).await?;
+        write_frame(&mut stream, self.token.as_bytes()).await?;
+        write_frame(&mut stream, &request).await?;
+
+        let status = stream.read_u8().await?;
+        let response = read_frame(&mut stream).await?;
+
+        match status {
+            0 => Ok(Ok(response)),
+            _ => Ok(Err(String::from_utf8_lossy(&response).to_string())),
+        }
+    }
+}
+
+impl Signer for SocketSigner {
+    fn sign<'a>(&'a self, utx: &'a UnsignedTx) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        Box::pin(async move {
+            match self.sign_remote(utx).await {
+                Ok(Ok(raw_tx)) => Ok(raw_tx),
+                Ok(Err(e)) => Err(format!("The signer refused to sign: {}", e)),
+                Err(e) => Err(format!("Couldn't reach the signer at {}: {}", self.address, e)),
+            }
+        })
+    }
+}
+
+/// Make sure a token for a signer socket is long enough to be hard to guess
+pub fn check_token(token: &str) -> Result<(), String> {
+    if token.len() < MIN_TOKEN_LEN {
+        return Err(format!(
+            "The signer token should be at least {} characters",
+            MIN_TOKEN_LEN
+        ));
+    }
+
+    Ok(())
+}
+
+/// Decides if a signing request received by `serve` should be signed
+pub trait Approver: Send + Sync {
+    fn approve<'a>(&'a self, utx: &'a UnsignedTx) -> BoxFuture<'a, bool>;
+}
+
+/// Keeps the signing requests received by `serve` until the user has checked their outputs, fee and change, and
+/// approved or rejected them by id. Requests that aren't answered in time are rejected.
+pub struct PendingApprovals {
+    hrp_sapling_address: String,
+    events: WalletEvents,
+    requests: Mutex<HashMap<String, (JsonValue, oneshot::Sender<bool>)>>,
+}
+
+impl PendingApprovals {
+    pub fn new(hrp_sapling_address: &str, events: WalletEvents) -> Self {
+        PendingApprovals {
+            hrp_sapling_address: hrp_sapling_address.to_string(),
+            events,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The requests waiting to be approved, as shown by `UnsignedTx::to_json`
+    pub fn list(&self) -> JsonValue {
+        JsonValue::Array(
+            self.requests
+                .lock()
+                .unwrap()
+                .values()
+                .map(|(request, _)| request.clone())
+                .collect(),
+        )
+    }
+
+    pub fn answer(&self, id: &str, approve: bool) -> Result<(), String> {
+        let (_, reply) = self
+            .requests
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or(format!("No signing request with id {}", id))?;
+
+        reply
+            .send(approve)
+            .map_err(|_| format!("The signing request {} is no longer waiting", id))
+    }
+}
+
+impl Approver for PendingApprovals {
+    fn approve<'a>(&'a self, utx: &'a UnsignedTx) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            let id = utx.id();
+            let (reply, answer) = oneshot::channel();
+            self.requests
+                .lock()
+                .unwrap()
+                .insert(id.clone(), (utx.to_json(&self.hrp_sapling_address), reply));
+            self.events.emit(WalletEvent::SigningRequest { id: id.clone() });
+
+            let approved = matches!(time::timeout(APPROVAL_TIMEOUT, answer).await, Ok(Ok(true)));
+            self.requests.lock().unwrap().remove(&id);
+
+            approved
+        })
+    }
+}
+
+/// A signer answering requests in the background, until it is dropped
+pub struct RunningSigner {
+    pub approvals: Arc<PendingApprovals>,
+    handle: JoinHandle<()>,
+}
+
+impl RunningSigner {
+    pub fn start<S: Signer + 'static>(
+        listener: TcpListener,
+        token: String,
+        signer: S,
+        approvals: Arc<PendingApprovals>,
+    ) -> Self {
+        let approver = approvals.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = serve(listener, &token, &signer, &*approver).await {
+                error!("Signer stopped: {}", e);
+            }
+        });
+
+        RunningSigner { approvals, handle }
+    }
+}
+
+impl Drop for RunningSigner {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Answer signing requests from `SocketSigner`s with `signer`, until the listener fails. Only requests from this
+/// machine that carry `token` are considered, and only the ones `approver` approves are signed. Connections are
+/// served side by side, so one that is slow to send its request, or waiting to be approved, doesn't hold up the
+/// others.
+pub async fn serve<S: Signer + ?Sized, A: Approver + ?Sized>(
+    listener: TcpListener,
+    token: &str,
+    signer: &S,
+    approver: &A,
+) -> io::Result<()> {
+    let mut requests = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (mut stream, peer) = accepted?;
+                if !peer.ip().is_loopback() {
+                    warn!("Refusing signing request from {}", peer);
+                    continue;
+                }
+
+                requests.push(async move {
+                    if let Err(e) = serve_request(&mut stream, token, signer, approver).await {
+                        warn!("Signing request from {} failed: {}", peer, e);
+                    }
+                });
+            }
+            Some(_) = requests.next(), if !requests.is_empty() => {}
+        }
+    }
+}
+
+async fn serve_request<S: Signer + ?Sized, A: Approver + ?Sized>(
+    stream: &mut TcpStream,
+    token: &str,
+    signer: &S,
+    approver: &A,
+) -> io::Result<()> {
+    let request_token = read_frame_timeout(stream).await?;
+    let request = read_frame_timeout(stream).await?;
+
+    let result = if ring::constant_time::verify_slices_are_equal(&request_token, token.as_bytes()).is_err() {
+        warn!("Refusing signing request with a bad token");
+        Err("Bad signer token".to_string())
+    } else {
+        match UnsignedTx::read(&request[..]) {
+            Ok(utx) => {
+                info!(
+                    "Signing request {} for {} to {} recipients",
+                    utx.id(),
+                    utx.total_value(),
+                    utx.tos.len()
+                );
+                if approver.approve(&utx).await {
+                    signer.sign(&utx).await
+                } else {
+                    Err("The signing request was rejected".to_string())
+                }
+            }
+            Err(e) => Err(format!("Couldn't read the unsigned transaction: {}", e)),
+        }
+    };
+
+    match result {
+        Ok(raw_tx) => {
+            stream.write_u8(0).await?;
+            write_frame(stream, &raw_tx).await
+        }
+        Err(e) => {
+            stream.write_u8(1).await?;
+            write_frame(stream, e.as_bytes()).await
+        }
+    }
+}
+
+async fn write_frame(stream: &mut TcpStream, bytes: &[u8]) -> io::Result<()> {
+    stream.write_u64_le(bytes.len() as u64).await?;
+    stream.write_all(bytes).await?;
+    stream.flush().await
+}
+
+// Read a frame from a connection to the signer, which has to arrive within `REQUEST_TIMEOUT`
+async fn read_frame_timeout(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    match time::timeout(REQUEST_TIMEOUT, read_frame(stream)).await {
+        Ok(r) => r,
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Timed out waiting for the signing request",
+        )),
+    }
+}
+
+async fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let len = stream.read_u64_le().await?;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes is too large", len),
+        ));
+    }
+
+    let mut bytes = vec![0u8; len as usize];
+    stream.read_exact(&mut bytes).await?;
+
+    Ok(bytes)
+}
@@ -0,0 +1,81 @@
+use bip39::{Language, Mnemonic};
+use sssmc39::{combine_mnemonics, generate_mnemonics};
+use zeroize::Zeroizing;
+
+// SLIP-39 shares are used as a backup encoding of the wallet's seed entropy. Combining them gives back the
+// same BIP39 seed phrase, so a restored wallet derives the same keys. If the wallet has a BIP39 passphrase,
+// it is not part of the shares and has to be backed up separately.
+
+// The shares themselves are not protected by a SLIP-39 passphrase, so the cheapest iteration count is enough
+const ITERATION_EXPONENT: u8 = 0;
+
+/// Split the seed entropy into `count` SLIP-39 shares, any `threshold` of which can restore it
+pub fn split_seed(entropy: &[u8], threshold: u8, count: u8) -> Result<Vec<Zeroizing<String>>, String> {
+    if threshold == 0 || threshold > count {
+        return Err(format!("Threshold must be between 1 and the number of shares ({})", count));
+    }
+
+    let groups = generate_mnemonics(1, &[(threshold, count)], entropy, "", ITERATION_EXPONENT)
+        .map_err(|e| format!("Couldn't split the seed into shares: {}", e))?;
+
+    let shares = groups[0]
+        .mnemonic_list()
+        .map_err(|e| format!("Couldn't encode the seed shares: {}", e))?;
+
+    Ok(shares
+        .into_iter()
+        .map(|words| Zeroizing::new(words.join(" ")))
+        .collect())
+}
+
+/// Combine SLIP-39 shares back into the seed phrase they were split from
+pub fn combine_shares(shares: &[String]) -> Result<Zeroizing<String>, String> {
+    let mnemonics = shares
+        .iter()
+        .map(|share| share.split_whitespace().map(|w| w.to_lowercase()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let entropy = Zeroizing::new(
+        combine_mnemonics(&mnemonics, "").map_err(|e| format!("Couldn't combine the seed shares: {}", e))?,
+    );
+
+    let mnemonic = Mnemonic::from_entropy(&entropy, Language::English)
+        .map_err(|e| format!("The shares don't contain a valid seed: {}", e))?;
+
+    Ok(Zeroizing::new(mnemonic.phrase().to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use bip39::{Language, Mnemonic};
+    use rand::{rngs::OsRng, Rng};
+
+    use super::{combine_shares, split_seed};
+
+    #[test]
+    fn split_combine() {
+        let mut entropy = [0u8; 32];
+        OsRng.fill(&mut entropy);
+        let phrase = Mnemonic::from_entropy(&entropy, Language::English).unwrap().phrase().to_string();
+
+        let shares = split_seed(&entropy, 2, 3)
+            .unwrap()
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(shares.len(), 3);
+
+        // Any 2 of the 3 shares restore the seed phrase
+        for (a, b) in [(0, 1), (0, 2), (1, 2)] {
+            let restored = combine_shares(&[shares[a].clone(), shares[b].clone()]).unwrap();
+            assert_eq!(*restored, phrase);
+        }
+
+        // But 1 doesn't
+        assert!(combine_shares(&shares[..1]).is_err());
+
+        // Bad thresholds are rejected
+        assert!(split_seed(&entropy, 0, 3).is_err());
+        assert!(split_seed(&entropy, 4, 3).is_err());
+    }
+}
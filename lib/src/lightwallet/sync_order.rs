@@ -0,0 +1,184 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use zcash_encoding::Vector;
+
+use super::data::BlockData;
+
+// How many of the newest blocks a recent-first sync looks at before the others, unless told otherwise
+pub const DEFAULT_RECENT_BLOCKS: u64 = 1_000;
+
+// The order a sync scans the blocks the wallet hasn't seen yet in. This is saved with the wallet options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOrder {
+    // Oldest to newest, in batches
+    OldestFirst,
+
+    // When the wallet is more than `recent_blocks` behind, first scan the newest `recent_blocks` blocks into the
+    // wallet, so the notes received in them can be spent right away, then backfill the older blocks. The balance
+    // is provisional until the backfill is done.
+    RecentFirst { recent_blocks: u64 },
+}
+
+impl Default for SyncOrder {
+    fn default() -> Self {
+        SyncOrder::OldestFirst
+    }
+}
+
+impl SyncOrder {
+    /// The newest blocks to scan first, from `last_scanned_height` up to `tip`. None if they should just be scanned in
+    /// order, because of the sync order or because there aren't enough of them to be worth it.
+    pub fn recent_range(&self, last_scanned_height: u64, tip: u64) -> Option<(u64, u64)> {
+        match self {
+            SyncOrder::RecentFirst { recent_blocks }
+                if *recent_blocks > 0 && tip.saturating_sub(last_scanned_height) > *recent_blocks =>
+            {
+                Some((tip - recent_blocks + 1, tip))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse an order as given to `setoption sync_order=...`. One of:
+    /// "oldestfirst", "recentfirst" or "recentfirst:<blocks>"
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            None if s == "oldestfirst" || s == "default" => Ok(SyncOrder::OldestFirst),
+            None if s == "recentfirst" => Ok(SyncOrder::RecentFirst {
+                recent_blocks: DEFAULT_RECENT_BLOCKS,
+            }),
+            Some(("recentfirst", blocks)) => match blocks.parse::<u64>() {
+                Ok(recent_blocks) if recent_blocks > 0 => Ok(SyncOrder::RecentFirst { recent_blocks }),
+                _ => Err(format!("Couldn't understand block count {}", blocks)),
+            },
+            _ => Err(format!("Unknown sync order {}", s)),
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(SyncOrder::OldestFirst),
+            1 => Ok(SyncOrder::RecentFirst {
+                recent_blocks: reader.read_u64::<LittleEndian>()?,
+            }),
+            v => Err(io::Error::new(ErrorKind::InvalidData, format!("Bad sync order {}", v))),
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        match self {
+            SyncOrder::OldestFirst => writer.write_u8(0),
+            SyncOrder::RecentFirst { recent_blocks } => {
+                writer.write_u8(1)?;
+                writer.write_u64::<LittleEndian>(*recent_blocks)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyncOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncOrder::OldestFirst => write!(f, "oldestfirst"),
+            SyncOrder::RecentFirst { recent_blocks } => write!(f, "recentfirst:{}", recent_blocks),
+        }
+    }
+}
+
+// The older blocks a recent-first sync still has to scan, after it scanned the newest ones into the wallet. It is
+// saved with the wallet, so an interrupted backfill carries on where it stopped.
+pub struct Backfill {
+    // The blocks up to here have been scanned
+    pub(crate) scanned_height: u64,
+
+    // The top blocks of the backfill, highest first, used if something gets re-orged
+    pub(crate) blocks: Vec<BlockData>,
+
+    // The newest blocks, which were scanned first, highest first. Once the backfill reaches them, the witnesses of the
+    // older notes are brought up to the wallet's height and their spends looked for with these, so they don't have to
+    // be downloaded again.
+    pub(crate) recent_blocks: Vec<BlockData>,
+}
+
+impl Backfill {
+    pub fn serialized_version() -> u64 {
+        return 1;
+    }
+
+    pub fn new(scanned_height: u64, blocks: Vec<BlockData>, recent_blocks: Vec<BlockData>) -> Self {
+        Self {
+            scanned_height,
+            blocks,
+            recent_blocks,
+        }
+    }
+
+    /// The first of the newest blocks, which were scanned first
+    pub fn recent_start(&self) -> u64 {
+        self.recent_blocks
+            .last()
+            .map(|b| b.height)
+            .unwrap_or(self.scanned_height + 1)
+    }
+
+    /// The last block the backfill has to scan
+    pub fn last_height(&self) -> u64 {
+        self.recent_start() - 1
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.scanned_height >= self.last_height()
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let _version = reader.read_u64::<LittleEndian>()?;
+
+        let scanned_height = reader.read_u64::<LittleEndian>()?;
+        let blocks = Vector::read(&mut reader, |r| BlockData::read(r))?;
+        let recent_blocks = Vector::read(&mut reader, |r| BlockData::read(r))?;
+
+        Ok(Self::new(scanned_height, blocks, recent_blocks))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        writer.write_u64::<LittleEndian>(self.scanned_height)?;
+        Vector::write(&mut writer, &self.blocks, |w, b| b.write(w))?;
+        Vector::write(&mut writer, &self.recent_blocks, |w, b| b.write(w))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SyncOrder, DEFAULT_RECENT_BLOCKS};
+
+    #[test]
+    fn sync_order() {
+        let recent = SyncOrder::parse("recentfirst:100").unwrap();
+        assert_eq!(recent, SyncOrder::RecentFirst { recent_blocks: 100 });
+        assert_eq!(
+            SyncOrder::parse("recentfirst").unwrap(),
+            SyncOrder::RecentFirst {
+                recent_blocks: DEFAULT_RECENT_BLOCKS
+            }
+        );
+        assert_eq!(SyncOrder::parse("oldestfirst").unwrap(), SyncOrder::default());
+        assert_eq!(SyncOrder::parse(&recent.to_string()).unwrap(), recent);
+        assert!(SyncOrder::parse("recentfirst:0").is_err());
+        assert!(SyncOrder::parse("sideways").is_err());
+
+        // Only wallets that are far enough behind look at the recent blocks first
+        assert_eq!(recent.recent_range(1000, 1150), Some((1051, 1150)));
+        assert_eq!(recent.recent_range(1000, 1100), None);
+        assert_eq!(SyncOrder::OldestFirst.recent_range(1000, 5000), None);
+
+        let mut buf = vec![];
+        recent.write(&mut buf).unwrap();
+        assert_eq!(SyncOrder::read(&buf[..]).unwrap(), recent);
+    }
+}
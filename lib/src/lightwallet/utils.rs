@@ -1,6 +1,7 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryInto;
 use std::io::{self, Read, Write};
-use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::{memo::MemoBytes, transaction::TxId};
 
 pub fn read_string<R: Read>(mut reader: R) -> io::Result<String> {
     // Strings are written as <littleendian> len + bytes
@@ -34,3 +35,14 @@ pub fn interpret_memo_string(memo_str: String) -> Result<MemoBytes, String> {
 
     MemoBytes::from_bytes(&s_bytes).map_err(|_| format!("Error creating output. Memo '{:?}' is too long", memo_str))
 }
+
+// Parse a txid as it is displayed (byte-reversed hex)
+pub fn parse_txid(txid: &str) -> Result<TxId, String> {
+    let mut bytes = hex::decode(txid).map_err(|e| format!("Couldn't parse txid {}: {}", txid, e))?;
+    bytes.reverse();
+
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("Txid {} is not 32 bytes", txid))?;
+    Ok(TxId::from_bytes(bytes))
+}
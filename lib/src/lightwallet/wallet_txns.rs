@@ -216,10 +216,10 @@ impl WalletTxns {
             .iter()
             .filter(|(_, wtx)| !wtx.unconfirmed) // Update only confirmed notes
             .flat_map(|(txid, wtx)| {
-                // Fetch notes that are before the before_block.
+                // Fetch notes that are before the before_block. Only the notes whose witnesses are kept have any:
+                // the ones we can spend, and with offline signing, the ones of viewing keys.
                 wtx.s_notes.iter().filter_map(move |snd| {
                     if wtx.block <= before_block
-                        && snd.have_spending_key
                         && snd.witnesses.len() > 0
                         && snd.spent.is_none()
                     {
@@ -236,6 +236,55 @@ impl WalletTxns {
         self.current.get(&txid).map(|t| t.total_funds_spent()).unwrap_or(0)
     }
 
+    // The shielded funds received from others, and all the funds spent, in the txns mined from `height` on. The notes
+    // of the wallet's own txns, the ones that spend its funds or that it has outgoing metadata for, are change.
+    pub fn funds_moved_since(&self, height: u64) -> (u64, u64) {
+        let height = BlockHeight::from_u32(height as u32);
+
+        self.current
+            .values()
+            .filter(|wtx| !wtx.unconfirmed && wtx.block >= height)
+            .fold((0, 0), |(received, spent), wtx| {
+                let own = wtx.total_funds_spent() > 0 || !wtx.outgoing_metadata.is_empty();
+                let received = if own {
+                    received
+                } else {
+                    received
+                        + wtx
+                            .s_notes
+                            .iter()
+                            .filter(|nd| !nd.is_change)
+                            .map(|nd| nd.note.value)
+                            .sum::<u64>()
+                        + wtx
+                            .o_notes
+                            .iter()
+                            .filter(|nd| !nd.is_change)
+                            .map(|nd| nd.note.value().inner())
+                            .sum::<u64>()
+                };
+
+                (received, spent + wtx.total_funds_spent())
+            })
+    }
+
+    // Find the sapling note at `output_index` in `txid`. Notes saved by older versions of the wallet don't have their
+    // output index until a rescan fills it in, so they can't be found this way yet, and the error says so.
+    pub fn find_s_note(&self, txid: &TxId, output_index: u32) -> Result<&SaplingNoteData, String> {
+        let not_found = || format!("No sapling note {}:{} in the wallet", txid, output_index);
+
+        let tx = self.current.get(txid).ok_or_else(not_found)?;
+        match tx.s_notes.iter().find(|nd| nd.output_index == Some(output_index)) {
+            Some(nd) => Ok(nd),
+            None if tx.s_notes.iter().any(|nd| nd.output_index.is_none()) => Err(format!(
+                "{}. Its notes were saved by an older version of the wallet without their output index. \
+                 Run 'rescan' to fill it in",
+                not_found()
+            )),
+            None => Err(not_found()),
+        }
+    }
+
     pub fn get_unspent_s_nullifiers(&self) -> Vec<(Nullifier, u64, TxId)> {
         self.current
             .iter()
@@ -576,6 +625,7 @@ impl WalletTxns {
                 spent_at_height: None,
                 spent: None,
                 unconfirmed_spent: None,
+                frozen: false,
             });
         }
     }
@@ -588,6 +638,7 @@ impl WalletTxns {
         note: Note,
         to: PaymentAddress,
         extfvk: &ExtendedFullViewingKey,
+        output_index: u32,
     ) {
         // Check if this is a change note
         let is_change = self.total_funds_spent_in(&txid) > 0;
@@ -609,6 +660,8 @@ impl WalletTxns {
                     memo: None,
                     is_change,
                     have_spending_key: false,
+                    output_index: Some(output_index),
+                    frozen: false,
                 };
 
                 wtx.s_notes.push(nd);
@@ -675,7 +728,9 @@ impl WalletTxns {
         to: PaymentAddress,
         extfvk: &ExtendedFullViewingKey,
         have_spending_key: bool,
+        keep_witnesses: bool,
         witness: IncrementalWitness<Node>,
+        output_index: u32,
     ) {
         // Check if this is a change note
         let is_change = self.total_funds_spent_in(&txid) > 0;
@@ -685,7 +740,7 @@ impl WalletTxns {
         wtx.block = height;
 
         let nullifier = note.nf(&extfvk.fvk.vk.nk, witness.position() as u64);
-        let witnesses = if have_spending_key {
+        let witnesses = if keep_witnesses {
             WitnessCache::new(vec![witness], u64::from(height))
         } else {
             WitnessCache::empty()
@@ -704,6 +759,8 @@ impl WalletTxns {
                     memo: None,
                     is_change,
                     have_spending_key,
+                    output_index: Some(output_index),
+                    frozen: false,
                 };
 
                 wtx.s_notes.push(nd);
@@ -717,6 +774,7 @@ impl WalletTxns {
                 // This is likely to happen if the previous wallet wasn't synced properly or was aborted in the middle of a sync,
                 // and has some dangling witnesses
                 n.witnesses = witnesses;
+                n.output_index = Some(output_index);
             }
         }
     }
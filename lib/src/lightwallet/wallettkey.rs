@@ -7,6 +7,7 @@ use sha2::Sha256;
 use sodiumoxide::crypto::secretbox;
 use zcash_encoding::{Optional, Vector};
 use zcash_primitives::consensus;
+use zeroize::Zeroizing;
 
 use crate::{
     lightclient::lightclient_config::LightClientConfig,
@@ -15,7 +16,7 @@ use crate::{
 
 use super::{
     keys::{FromBase58Check, ToBase58Check},
-    utils,
+    secret, utils,
 };
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -231,11 +232,19 @@ impl WalletTKey {
         })
     }
 
+    // Wipe the secret key from memory before dropping it
+    fn clear_key(&mut self) {
+        if let Some(sk) = self.key.as_mut() {
+            secret::wipe_tsk(sk);
+        }
+        self.key = None;
+    }
+
     pub fn lock(&mut self) -> io::Result<()> {
         match self.keytype {
             WalletTKeyType::HdKey => {
                 // For HD keys, just empty out the keys, since they will be reconstructed from the hdkey_num
-                self.key = None;
+                self.clear_key();
                 self.locked = true;
             }
             WalletTKeyType::ImportedKey => {
@@ -247,7 +256,7 @@ impl WalletTKey {
                         "Can't lock when imported key is not encrypted",
                     ));
                 }
-                self.key = None;
+                self.clear_key();
                 self.locked = true;
             }
         }
@@ -284,7 +293,7 @@ impl WalletTKey {
                 // For imported keys, we need to decrypt from the encrypted key
                 let nonce = secretbox::Nonce::from_slice(&self.nonce.as_ref().unwrap()).unwrap();
                 let sk_bytes = match secretbox::open(&self.enc_key.as_ref().unwrap(), &nonce, &key) {
-                    Ok(s) => s,
+                    Ok(s) => Zeroizing::new(s),
                     Err(_) => {
                         return Err(io::Error::new(
                             ErrorKind::InvalidData,
@@ -304,11 +313,23 @@ impl WalletTKey {
     }
 
     pub fn encrypt(&mut self, key: &secretbox::Key) -> io::Result<()> {
+        self.reencrypt(key)?;
+
+        // Also lock after encrypt
+        self.lock()
+    }
+
+    // (Re)seal the secret key under `key` without locking. The key has to be unlocked.
+    pub fn reencrypt(&mut self, key: &secretbox::Key) -> io::Result<()> {
         match self.keytype {
             WalletTKeyType::HdKey => {
                 // For HD keys, we don't need to do anything, since the hdnum has all the info to recreate this key
             }
             WalletTKeyType::ImportedKey => {
+                if self.key.is_none() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Can't encrypt while locked"));
+                }
+
                 // For imported keys, encrypt the key into enckey
                 let nonce = secretbox::gen_nonce();
 
@@ -319,8 +340,7 @@ impl WalletTKey {
             }
         }
 
-        // Also lock after encrypt
-        self.lock()
+        Ok(())
     }
 
     pub fn remove_encryption(&mut self) -> io::Result<()> {
@@ -345,6 +365,12 @@ impl WalletTKey {
     }
 }
 
+impl Drop for WalletTKey {
+    fn drop(&mut self) {
+        self.clear_key();
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -11,10 +11,11 @@ use zcash_primitives::{
     sapling::PaymentAddress,
     zip32::{ExtendedFullViewingKey, ExtendedSpendingKey},
 };
+use zeroize::Zeroizing;
 
 use crate::lightclient::lightclient_config::LightClientConfig;
 
-use super::keys::Keys;
+use super::{keys::Keys, secret};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum WalletZKeyType {
@@ -178,11 +179,19 @@ impl WalletZKey {
         })
     }
 
+    // Wipe the spending key from memory before dropping it
+    fn clear_extsk(&mut self) {
+        if let Some(extsk) = self.extsk.as_mut() {
+            secret::wipe_extsk(extsk);
+        }
+        self.extsk = None;
+    }
+
     pub fn lock(&mut self) -> io::Result<()> {
         match self.keytype {
             WalletZKeyType::HdKey => {
                 // For HD keys, just empty out the keys, since they will be reconstructed from the hdkey_num
-                self.extsk = None;
+                self.clear_extsk();
                 self.locked = true;
             }
             WalletZKeyType::ImportedSpendingKey => {
@@ -194,7 +203,7 @@ impl WalletZKey {
                         "Can't lock when imported key is not encrypted",
                     ));
                 }
-                self.extsk = None;
+                self.clear_extsk();
                 self.locked = true;
             }
             WalletZKeyType::ImportedViewKey => {
@@ -247,7 +256,7 @@ impl WalletZKey {
                 // For imported keys, we need to decrypt from the encrypted key
                 let nonce = secretbox::Nonce::from_slice(&self.nonce.as_ref().unwrap()).unwrap();
                 let extsk_bytes = match secretbox::open(&self.enc_key.as_ref().unwrap(), &nonce, &key) {
-                    Ok(s) => s,
+                    Ok(s) => Zeroizing::new(s),
                     Err(_) => {
                         return Err(io::Error::new(
                             ErrorKind::InvalidData,
@@ -268,16 +277,28 @@ impl WalletZKey {
     }
 
     pub fn encrypt(&mut self, key: &secretbox::Key) -> io::Result<()> {
+        self.reencrypt(key)?;
+
+        // Also lock after encrypt
+        self.lock()
+    }
+
+    // (Re)seal the spending key under `key` without locking. The key has to be unlocked.
+    pub fn reencrypt(&mut self, key: &secretbox::Key) -> io::Result<()> {
         match self.keytype {
             WalletZKeyType::HdKey => {
                 // For HD keys, we don't need to do anything, since the hdnum has all the info to recreate this key
             }
             WalletZKeyType::ImportedSpendingKey => {
+                if self.extsk.is_none() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Can't encrypt while locked"));
+                }
+
                 // For imported keys, encrypt the key into enckey
                 let nonce = secretbox::gen_nonce();
 
-                let mut sk_bytes = vec![];
-                self.extsk.as_ref().unwrap().write(&mut sk_bytes)?;
+                let mut sk_bytes = Zeroizing::new(vec![]);
+                self.extsk.as_ref().unwrap().write(&mut *sk_bytes)?;
 
                 self.enc_key = Some(secretbox::seal(&sk_bytes, &nonce, &key));
                 self.nonce = Some(nonce.as_ref().to_vec());
@@ -287,8 +308,7 @@ impl WalletZKey {
             }
         }
 
-        // Also lock after encrypt
-        self.lock()
+        Ok(())
     }
 
     pub fn remove_encryption(&mut self) -> io::Result<()> {
@@ -317,6 +337,12 @@ impl WalletZKey {
     }
 }
 
+impl Drop for WalletZKey {
+    fn drop(&mut self) {
+        self.clear_extsk();
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use sodiumoxide::crypto::secretbox;
@@ -326,6 +352,7 @@ pub mod tests {
 
     use super::WalletZKey;
     use crate::lightclient::lightclient_config::{LightClientConfig, UnitTestNetwork};
+    use crate::lightwallet::secret::WIPED_KEYS;
 
     fn get_config() -> LightClientConfig<UnitTestNetwork> {
         LightClientConfig {
@@ -424,6 +451,30 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn lock_and_drop_wipe_sk() {
+        let config = get_config();
+        let wiped = || WIPED_KEYS.with(|w| w.get());
+
+        let privkey = "secret-extended-key-main1q0p44m9zqqqqpqyxfvy5w2vq6ahvxyrwsk2w4h2zleun4cft4llmnsjlv77lhuuknv6x9jgu5g2clf3xq0wz9axxxq8klvv462r5pa32gjuj5uhxnvps6wsrdg6xll05unwks8qpgp4psmvy5e428uxaggn4l29duk82k3sv3njktaaj453fdmfmj2fup8rls4egqxqtj2p5a3yt4070khn99vzxj5ag5qjngc4v2kq0ctl9q2rpc2phu4p3e26egu9w88mchjf83sqgh3cev";
+        let esk = decode_extended_spending_key(config.hrp_sapling_private_key(), privkey)
+            .unwrap()
+            .unwrap();
+        let mut wzk = WalletZKey::new_imported_sk(esk);
+        let key = secretbox::Key::from_slice(&[0; 32]).unwrap();
+        wzk.encrypt(&key).unwrap();
+
+        // Locking wipes the spending key before it's thrown away
+        let before = wiped();
+        wzk.lock().unwrap();
+        assert_eq!(wiped(), before + 1);
+
+        // So does dropping an unlocked key
+        wzk.unlock(&config, &[], &key).unwrap();
+        drop(wzk);
+        assert_eq!(wiped(), before + 2);
+    }
+
     #[test]
     fn test_encrypt_decrypt_vk() {
         let config = get_config();
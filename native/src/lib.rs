@@ -4,9 +4,12 @@ extern crate lazy_static;
 use neon::prelude::Context;
 use neon::prelude::FunctionContext;
 use neon::prelude::JsBoolean;
+use neon::prelude::JsFunction;
 use neon::prelude::JsNumber;
 use neon::prelude::JsResult;
 use neon::prelude::JsString;
+use neon::prelude::JsValue;
+use neon::prelude::Object;
 use neon::register_module;
 use zecwalletlitelib::lightclient::lightclient_config::LightClientConfig;
 use zecwalletlitelib::MainNetwork;
@@ -34,8 +37,13 @@ register_module!(mut m, {
         "litelib_initialize_new_from_phrase",
         litelib_initialize_new_from_phrase,
     )?;
+    m.export_function(
+        "litelib_initialize_new_from_shares",
+        litelib_initialize_new_from_shares,
+    )?;
     m.export_function("litelib_deinitialize", litelib_deinitialize)?;
     m.export_function("litelib_execute", litelib_execute)?;
+    m.export_function("litelib_subscribe_events", litelib_subscribe_events)?;
     Ok(())
 });
 
@@ -47,9 +55,14 @@ fn litelib_wallet_exists(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     Ok(cx.boolean(config.wallet_exists()))
 }
 
-/// Create a new wallet and return the seed for the newly created wallet.
+/// Create a new wallet, with an optional BIP39 passphrase, and return the seed for the newly created wallet.
 fn litelib_initialize_new(mut cx: FunctionContext) -> JsResult<JsString> {
     let server_uri = cx.argument::<JsString>(0)?.value(&mut cx);
+    let passphrase = if cx.len() > 1 {
+        cx.argument::<JsString>(1)?.value(&mut cx)
+    } else {
+        "".to_string()
+    };
 
     let resp = || {
         let server = LightClientConfig::<MainNetwork>::get_server_or_default(Some(server_uri));
@@ -61,7 +74,11 @@ fn litelib_initialize_new(mut cx: FunctionContext) -> JsResult<JsString> {
                 }
             };
 
-        let lightclient = match LightClient::new(&config, latest_block_height.saturating_sub(100)) {
+        let lightclient = match LightClient::new_with_passphrase(
+            &config,
+            latest_block_height.saturating_sub(100),
+            passphrase,
+        ) {
             Ok(l) => l,
             Err(e) => {
                 return format!("Error: {}", e);
@@ -90,12 +107,17 @@ fn litelib_initialize_new(mut cx: FunctionContext) -> JsResult<JsString> {
     Ok(cx.string(resp()))
 }
 
-/// Restore a wallet from the seed phrase
+/// Restore a wallet from the seed phrase, and an optional BIP39 passphrase
 fn litelib_initialize_new_from_phrase(mut cx: FunctionContext) -> JsResult<JsString> {
     let server_uri = cx.argument::<JsString>(0)?.value(&mut cx);
     let seed = cx.argument::<JsString>(1)?.value(&mut cx);
     let birthday = cx.argument::<JsNumber>(2)?.value(&mut cx);
     let overwrite = cx.argument::<JsBoolean>(3)?.value(&mut cx);
+    let passphrase = if cx.len() > 4 {
+        cx.argument::<JsString>(4)?.value(&mut cx)
+    } else {
+        "".to_string()
+    };
 
     let resp = || {
         let server = LightClientConfig::<MainNetwork>::get_server_or_default(Some(server_uri));
@@ -107,14 +129,75 @@ fn litelib_initialize_new_from_phrase(mut cx: FunctionContext) -> JsResult<JsStr
                 }
             };
 
-        let lightclient =
-            match LightClient::new_from_phrase(seed, &config, birthday as u64, overwrite) {
-                Ok(l) => l,
+        let lightclient = match LightClient::new_from_phrase_with_passphrase(
+            seed,
+            passphrase,
+            &config,
+            birthday as u64,
+            overwrite,
+        ) {
+            Ok(l) => l,
+            Err(e) => {
+                return format!("Error: {}", e);
+            }
+        };
+
+        // Initialize logging
+        let _ = lightclient.init_logging();
+
+        let lc = Arc::new(lightclient);
+        LightClient::start_mempool_monitor(lc.clone());
+
+        LIGHTCLIENT.lock().unwrap().replace(Some(lc));
+
+        format!("OK")
+    };
+
+    Ok(cx.string(resp()))
+}
+
+/// Restore a wallet from its SLIP-39 seed shares, one per line, and an optional BIP39 passphrase
+fn litelib_initialize_new_from_shares(mut cx: FunctionContext) -> JsResult<JsString> {
+    let server_uri = cx.argument::<JsString>(0)?.value(&mut cx);
+    let shares = cx.argument::<JsString>(1)?.value(&mut cx);
+    let birthday = cx.argument::<JsNumber>(2)?.value(&mut cx);
+    let overwrite = cx.argument::<JsBoolean>(3)?.value(&mut cx);
+    let passphrase = if cx.len() > 4 {
+        cx.argument::<JsString>(4)?.value(&mut cx)
+    } else {
+        "".to_string()
+    };
+
+    let resp = || {
+        let server = LightClientConfig::<MainNetwork>::get_server_or_default(Some(server_uri));
+        let (config, _latest_block_height) =
+            match LightClientConfig::create(MainNetwork, server, None) {
+                Ok((c, h)) => (c, h),
                 Err(e) => {
                     return format!("Error: {}", e);
                 }
             };
 
+        let shares = shares
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>();
+
+        let lightclient = match LightClient::new_from_shares(
+            shares,
+            passphrase,
+            &config,
+            birthday as u64,
+            overwrite,
+        ) {
+            Ok(l) => l,
+            Err(e) => {
+                return format!("Error: {}", e);
+            }
+        };
+
         // Initialize logging
         let _ = lightclient.init_logging();
 
@@ -230,3 +313,40 @@ fn litelib_execute(mut cx: FunctionContext) -> JsResult<JsString> {
 
     Ok(cx.string(resp()))
 }
+
+/// Call the JS callback with the JSON of every wallet event, like sync batches, new and confirmed transactions and
+/// send progress, so the app doesn't have to poll for them
+fn litelib_subscribe_events(mut cx: FunctionContext) -> JsResult<JsString> {
+    let callback = Arc::new(cx.argument::<JsFunction>(0)?.root(&mut cx));
+    let channel = cx.channel();
+
+    let lightclient: Arc<LightClient<MainNetwork>>;
+    {
+        let lc = LIGHTCLIENT.lock().unwrap();
+
+        if lc.borrow().is_none() {
+            return Ok(cx.string(format!("Error: Light Client is not initialized")));
+        }
+
+        lightclient = lc.borrow().as_ref().unwrap().clone();
+    };
+
+    let mut events = lightclient.subscribe();
+    thread::spawn(move || {
+        while let Some(event) = events.blocking_recv() {
+            let callback = callback.clone();
+            let json = event.to_json().dump();
+
+            channel.send(move |mut cx| {
+                let callback = callback.to_inner(&mut cx);
+                let this = cx.undefined();
+                let args = vec![cx.string(json).upcast::<JsValue>()];
+                callback.call(&mut cx, this, args)?;
+
+                Ok(())
+            });
+        }
+    });
+
+    Ok(cx.string(format!("OK")))
+}
@@ -190,19 +190,32 @@ export function litelib_wallet_exists(chain_name: string): boolean {
   return native.litelib_wallet_exists(chain_name);
 }
 
-export function litelib_initialize_new(server_uri: string): string {
+export function litelib_initialize_new(server_uri: string, passphrase: string = ""): string {
   const native = loadNativeModule();
-  return native.litelib_initialize_new(server_uri);
+  return native.litelib_initialize_new(server_uri, passphrase);
 }
 
 export function litelib_initialize_new_from_phrase(
   server_uri: string,
   seed: string,
   birthday: number,
-  overwrite: boolean
+  overwrite: boolean,
+  passphrase: string = ""
 ): string {
   const native = loadNativeModule();
-  return native.litelib_initialize_new_from_phrase(server_uri, seed, birthday, overwrite);
+  return native.litelib_initialize_new_from_phrase(server_uri, seed, birthday, overwrite, passphrase);
+}
+
+// `shares` holds the SLIP-39 shares of the seed, one per line
+export function litelib_initialize_new_from_shares(
+  server_uri: string,
+  shares: string,
+  birthday: number,
+  overwrite: boolean,
+  passphrase: string = ""
+): string {
+  const native = loadNativeModule();
+  return native.litelib_initialize_new_from_shares(server_uri, shares, birthday, overwrite, passphrase);
 }
 
 export function litelib_initialize_existing(server_uri: string): string {
@@ -218,4 +231,10 @@ export function litelib_deinitialize(): string {
 export function litelib_execute(cmd: string, args: string): string {
   const native = loadNativeModule();
   return native.litelib_execute(cmd, args);
+}
+
+// `callback` is called with the JSON of every wallet event, until the wallet is deinitialized
+export function litelib_subscribe_events(callback: (json: string) => void): string {
+  const native = loadNativeModule();
+  return native.litelib_subscribe_events(callback);
 }
\ No newline at end of file